@@ -1,8 +1,8 @@
 import { Badge } from "@/components/ui/badge"
-import { convertState } from "@/types/sol"
+import { ContractState, convertState } from "@/types/sol"
 
 interface ContractStateBadgeProps {
-  state: number;
+  state: ContractState;
 }
 
 export function ContractStateBadge({ state }: ContractStateBadgeProps) {
@@ -11,11 +11,11 @@ export function ContractStateBadge({ state }: ContractStateBadgeProps) {
   // Map states to colors
   let variant: "yellow" | "green" | "orange" | "red" = "yellow";
 
-  if (state >= 4 && state <= 6) {
-    variant = "green"; // Confirmed, Pre-Closed, Closed
-  } else if (state === 9) {
-    variant = "orange"; // Refunded
-  } else if (state >= 7) {
+  if (state === "confirmed" || state === "pre-closed" || state === "closed") {
+    variant = "green";
+  } else if (state === "refunded") {
+    variant = "orange";
+  } else if (state === "failed-accept" || state === "failed-sign" || state === "rejected") {
     variant = "red"; // Failed states
   }
   // Default to yellow for Offer, Accept, Signed states
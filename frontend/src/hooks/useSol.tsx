@@ -1,6 +1,6 @@
 import { createContext, useContext, useMemo, FC } from 'react';
 import axios from 'axios';
-import { SolBalance, StoredContract, EnumerationContractParams, CreateEnumerationContractResponse, NostrCounterparty, CreateParlayContractResponse, InfoResponse, MarketStats, Peer, Transaction, LocalOutput, BalanceHistory, TimePeriod } from '@/types/sol';
+import { SolBalance, StoredContract, EnumerationContractParams, CreateEnumerationContractResponse, NostrCounterparty, CreateParlayContractResponse, InfoResponse, MarketStats, Peer, Transaction, LocalOutput, BalanceHistory, TimePeriod, Offer, Page } from '@/types/sol';
 import { ForgotParams, LoginParams, LoginResponse, MagicLinkParams, RegisterParams, ResetParams } from '@/types/auth';
 import config from '@/lib/config';
 import { SendOfferBody, AcceptOfferBody, CreateParlayContractParams, ContractFilter } from '@/types/sol';
@@ -110,9 +110,12 @@ export const SolProvider: FC<SolProviderProps> = ({ children }) => {
       return data;
     },
     getOffers: async (offerId?: string) => {
-      const endpoint = offerId ? `/offers?id=${offerId}` : '/offers';
-      const { data } = await instance.get(endpoint);
-      return data;
+      if (offerId) {
+        const { data } = await instance.get<Offer>(`/offers?id=${offerId}`);
+        return data;
+      }
+      const { data } = await instance.get<Page<Offer>>('/offers');
+      return data.items;
     },
     sendOffer: async (body: SendOfferBody) => {
       const { data } = await instance.post('/offers', body);
@@ -148,8 +151,8 @@ export const SolProvider: FC<SolProviderProps> = ({ children }) => {
       return data;
     },
     getContracts: async (filter?: ContractFilter) => {
-      const { data } = await instance.get<StoredContract[]>(`/contracts?filter=${filter}`);
-      return data;
+      const { data } = await instance.get<Page<StoredContract>>(`/contracts?filter=${filter}`);
+      return data.items;
     },
     getContract: async (id: string) => {
       const { data } = await instance.get<StoredContract>(`/contracts?id=${id}`);
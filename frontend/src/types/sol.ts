@@ -30,8 +30,25 @@ export enum ContractFilter {
   Failed = "failed",
 }
 
+export type ContractState =
+  | "offered"
+  | "accepted"
+  | "signed"
+  | "confirmed"
+  | "pre-closed"
+  | "closed"
+  | "failed-accept"
+  | "failed-sign"
+  | "refunded"
+  | "rejected";
+
+export interface Page<T> {
+  items: T[];
+  next_cursor: string | null;
+}
+
 export interface StoredContract {
-  state: number;
+  state: ContractState;
   id: string;
   counter_party: string;
   is_offer_party: boolean;
@@ -41,29 +58,31 @@ export interface StoredContract {
   cet_locktime: number;
   refund_locktime: number;
   pnl: number | null;
+  created_at: string;
+  oracle_event_id: string | null;
 }
 
-export const convertState = (state: number) => {
+export const convertState = (state: ContractState) => {
   switch (state) {
-    case 1:
+    case "offered":
       return "Offer";
-    case 2:
+    case "accepted":
       return "Accept";
-    case 3:
+    case "signed":
       return "Signed";
-    case 4:
+    case "confirmed":
       return "Confirmed";
-    case 5:
+    case "pre-closed":
       return "Pre-Closed";
-    case 6:
+    case "closed":
       return "Closed";
-    case 7:
+    case "failed-accept":
       return "Failed Accept";
-    case 8:
+    case "failed-sign":
       return "Failed Sign";
-    case 9:
+    case "refunded":
       return "Refunded";
-    case 10:
+    case "rejected":
       return "Rejected";
     default:
       return "unknown";
@@ -72,7 +91,7 @@ export const convertState = (state: number) => {
 
 export interface Offer {
   id: string;
-  state: number;
+  state: ContractState;
   is_offer_party: boolean;
   counter_party: string;
   offer_collateral: number;
@@ -6,6 +6,20 @@ mod m20220101_000001_users;
 mod m20250412_194431_add_nostr_profile_to_users;
 mod m20250428_163347_seeds;
 mod m20250507_171821_balances;
+mod m20250601_120000_offer_expiries;
+mod m20250605_090000_close_proposals;
+mod m20250610_080000_contract_events;
+mod m20250612_100000_add_oracle_event_id_to_contract_events;
+mod m20250615_090000_utxo_labels;
+mod m20250618_090000_add_encrypted_to_seeds;
+mod m20250620_090000_prices;
+mod m20250622_090000_balance_valuations;
+mod m20250625_090000_webhooks;
+mod m20250625_090100_webhook_deliveries;
+mod m20250627_090000_add_next_attempt_at_to_webhook_deliveries;
+mod m20250629_090000_index_contract_events;
+mod m20250701_090000_psbt_reservations;
+mod m20250703_090000_add_published_at_to_contract_events;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -16,6 +30,20 @@ impl MigratorTrait for Migrator {
             Box::new(m20250412_194431_add_nostr_profile_to_users::Migration),
             Box::new(m20250428_163347_seeds::Migration),
             Box::new(m20250507_171821_balances::Migration),
+            Box::new(m20250601_120000_offer_expiries::Migration),
+            Box::new(m20250605_090000_close_proposals::Migration),
+            Box::new(m20250610_080000_contract_events::Migration),
+            Box::new(m20250612_100000_add_oracle_event_id_to_contract_events::Migration),
+            Box::new(m20250615_090000_utxo_labels::Migration),
+            Box::new(m20250618_090000_add_encrypted_to_seeds::Migration),
+            Box::new(m20250620_090000_prices::Migration),
+            Box::new(m20250622_090000_balance_valuations::Migration),
+            Box::new(m20250625_090000_webhooks::Migration),
+            Box::new(m20250625_090100_webhook_deliveries::Migration),
+            Box::new(m20250627_090000_add_next_attempt_at_to_webhook_deliveries::Migration),
+            Box::new(m20250629_090000_index_contract_events::Migration),
+            Box::new(m20250701_090000_psbt_reservations::Migration),
+            Box::new(m20250703_090000_add_published_at_to_contract_events::Migration),
             // inject-above (do not remove this comment)
         ]
     }
@@ -0,0 +1,32 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "close_proposals",
+            &[
+                ("contract_id", ColType::String),
+                ("counter_party", ColType::String),
+                ("is_proposer", ColType::Boolean),
+                ("offer_payout", ColType::BigInteger),
+                ("accept_payout", ColType::BigInteger),
+                ("fee_rate", ColType::BigInteger),
+                ("signature", ColType::String),
+                ("status", ColType::String),
+                ("txid", ColType::StringNull),
+            ],
+            &[],
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "close_proposals").await
+    }
+}
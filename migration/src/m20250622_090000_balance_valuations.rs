@@ -0,0 +1,28 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "balance_valuations",
+            &[
+                ("currency", ColType::String),
+                ("bitcoin_price", ColType::Decimal),
+                ("bitcoin_balance", ColType::Decimal),
+                ("contract_balance", ColType::Decimal),
+                ("pnl", ColType::Decimal),
+            ],
+            &[("balance", "")],
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "balance_valuations").await
+    }
+}
@@ -0,0 +1,30 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(
+            m,
+            "webhook_deliveries",
+            &[
+                ("event", ColType::String),
+                ("payload", ColType::Json),
+                ("status", ColType::String),
+                ("attempts", ColType::Integer),
+                ("response_status", ColType::IntegerNull),
+                ("error", ColType::TextNull),
+                ("delivered_at", ColType::TimestampWithTimeZoneNull),
+            ],
+            &[("webhook", "")],
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "webhook_deliveries").await
+    }
+}
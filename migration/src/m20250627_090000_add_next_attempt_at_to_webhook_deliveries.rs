@@ -0,0 +1,30 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        add_column(
+            m,
+            "webhook_deliveries",
+            "next_attempt_at",
+            ColType::TimestampWithTimeZoneNull,
+        )
+        .await?;
+        // Deliveries still pending were retried inline before, they are due now.
+        m.get_connection()
+            .execute_unprepared(
+                "UPDATE webhook_deliveries SET next_attempt_at = now() WHERE status = 'pending'",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "webhook_deliveries", "next_attempt_at").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const INDEXES: [(&str, &str); 2] = [
+    ("idx-contract_events-contract_id", "contract_id"),
+    (
+        "idx-contract_events-temporary_contract_id",
+        "temporary_contract_id",
+    ),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        for (name, column) in INDEXES {
+            m.create_index(
+                Index::create()
+                    .name(name)
+                    .table(Alias::new("contract_events"))
+                    .col(Alias::new(column))
+                    .to_owned(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        for (name, _) in INDEXES {
+            m.drop_index(
+                Index::drop()
+                    .name(name)
+                    .table(Alias::new("contract_events"))
+                    .to_owned(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        add_column(
+            m,
+            "contract_events",
+            "published_at",
+            ColType::TimestampWithTimeZoneNull,
+        )
+        .await?;
+        // Events recorded before were already published by snapshot diffs, or never will be.
+        m.get_connection()
+            .execute_unprepared("UPDATE contract_events SET published_at = created_at")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "contract_events", "published_at").await?;
+        Ok(())
+    }
+}
@@ -16,11 +16,17 @@ use std::{path::Path, sync::Arc};
 use tokio::sync::OnceCell;
 use tower_cookies::CookieManagerLayer;
 
-use crate::{common::settings::Settings, sol::SonsOfLiberty};
+use crate::{
+    common::{settings::Settings, sync, webhooks},
+    sol::SonsOfLiberty,
+};
 // use crate::common::market::Market;
 #[allow(unused_imports)]
 use crate::{
-    controllers, initializers, models::_entities::users, tasks, workers::downloader::DownloadWorker,
+    controllers, initializers,
+    models::_entities::users,
+    tasks,
+    workers::{downloader::DownloadWorker, webhook::WebhookWorker},
 };
 
 pub static SONS_OF_LIBERTY: OnceCell<Arc<SonsOfLiberty>> = OnceCell::const_new();
@@ -61,16 +67,20 @@ impl Hooks for App {
     fn routes(_ctx: &AppContext) -> AppRoutes {
         AppRoutes::with_default_routes() // controller routes below
             .add_route(controllers::sync::routes())
+            .add_route(controllers::events::routes())
             .add_route(controllers::nostr::routes())
             .add_route(controllers::create::routes())
             // .add_route(controllers::hashrate::routes())
             .add_route(controllers::wallet::routes())
             .add_route(controllers::peers::routes())
             .add_route(controllers::contracts::routes())
+            .add_route(controllers::close::routes())
             .add_route(controllers::offers::routes())
             .add_route(controllers::info::routes())
             .add_route(controllers::balance::routes())
             .add_route(controllers::auth::routes())
+            .add_route(controllers::seed::routes())
+            .add_route(controllers::webhooks::routes())
     }
 
     async fn after_routes(router: AxumRouter, ctx: &AppContext) -> Result<AxumRouter> {
@@ -106,6 +116,9 @@ impl Hooks for App {
             }
         });
 
+        sync::spawn_sync_loop(ctx.db.clone(), ddk.clone());
+        webhooks::spawn_delivery_loop(ctx.clone(), settings.webhooks.clone());
+
         // let market = Arc::new(Market::new(&ctx.config.database.uri).await?);
 
         Ok(router
@@ -127,6 +140,7 @@ impl Hooks for App {
 
     async fn connect_workers(ctx: &AppContext, queue: &Queue) -> Result<()> {
         queue.register(DownloadWorker::build(ctx)).await?;
+        queue.register(WebhookWorker::build(ctx)).await?;
         Ok(())
     }
 
@@ -134,6 +148,12 @@ impl Hooks for App {
     fn register_tasks(tasks: &mut Tasks) {
         tasks.register(tasks::balance_updater::BalanceUpdater);
         tasks.register(tasks::freshdb::Freshdb);
+        tasks.register(tasks::offer_expiry::OfferExpiry);
+        tasks.register(tasks::seed_backup::SeedBackup);
+        tasks.register(tasks::seed_restore::SeedRestore);
+        tasks.register(tasks::encrypt_seeds::EncryptSeeds);
+        tasks.register(tasks::rotate_seed::RotateSeed);
+        tasks.register(tasks::ledger_export::LedgerExport);
         // tasks-inject (do not remove)
     }
     async fn truncate(ctx: &AppContext) -> Result<()> {
@@ -1,11 +1,145 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ddk_manager::{Oracle, Storage};
+use dlc_messages::oracle_msgs::{DigitDecompositionEventDescriptor, EventDescriptor};
+use loco_rs::prelude::*;
 use serde::{Deserialize, Deserializer, Serialize};
-// use serde_this_or_that::as_f64;
+use thiserror::Error;
+
+use crate::{
+    common::{dlcdevkit::offered_contract, settings::Settings},
+    models::prices,
+    sol::SonsOfLiberty,
+};
+
+/// Currency balances have always been valued in, and the only one oracles attest.
+pub const DEFAULT_CURRENCY: &str = "USD";
+/// Unit of the numeric oracle events that attest the BTC/USD price.
+pub const BTC_USD_UNIT: &str = "usd/btc";
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Last aggregated price per currency, shared by everything running in this process.
+static MEMORY_CACHE: Mutex<Vec<CachedPrice>> = Mutex::new(Vec::new());
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct SpotPrice {
     pub currency: String,
     #[serde(deserialize_with = "string_to_f64")]
     pub amount: f64,
+    /// Provider the price came from, or how several providers were aggregated.
+    #[serde(default)]
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
+struct CachedPrice {
+    price: SpotPrice,
+    fetched_at: DateTime<Utc>,
+}
+
+#[derive(Error, Debug)]
+pub enum PriceError {
+    #[error("Request to {0} failed: {1}")]
+    Http(&'static str, reqwest::Error),
+    #[error("Unexpected response from {0}: {1}")]
+    Malformed(&'static str, String),
+    #[error("Price provider {0} is not configured")]
+    NotConfigured(&'static str),
+    #[error("Price provider {0} has no {1} price")]
+    Unsupported(&'static str, String),
+    #[error("{0} is not a currency code")]
+    InvalidCurrency(String),
+    #[error("No attested oracle price younger than {0} seconds")]
+    NoAttestation(u64),
+    #[error("Only {0} of {1} required price providers answered")]
+    NotEnoughQuotes(usize, usize),
+}
+
+/// Where BTC prices can be fetched from, in the order they are listed in the settings.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSource {
+    Coinbase,
+    Kraken,
+    Bitstamp,
+    /// Latest numeric outcome attested by our oracles for one of our contracts.
+    Oracle,
+    /// `price.static_price` and `price.static_prices`, for tests and offline setups.
+    Static,
+    /// Price read from `price.file`, for tests and offline setups.
+    File,
+}
+
+/// How answers of several providers become one price.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceAggregation {
+    /// Asks providers in order and takes the first answer.
+    #[default]
+    Fallback,
+    /// Asks every provider and takes the median of the answers.
+    Median,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PriceSettings {
+    pub providers: Vec<PriceSource>,
+    pub aggregation: PriceAggregation,
+    /// Providers that must answer for a median price.
+    pub min_quotes: usize,
+    /// Seconds a fetched price is reused before asking the providers again.
+    pub cache_secs: u64,
+    /// Seconds a stored price is still used when every provider fails.
+    pub max_stale_secs: u64,
+    pub timeout_secs: u64,
+    /// Extra attempts per provider after a failed request.
+    pub retries: u32,
+    /// Oldest oracle attestation the oracle provider accepts, in seconds.
+    pub oracle_max_age_secs: u64,
+    /// Fixed USD price of the static provider.
+    pub static_price: Option<f64>,
+    /// Fixed prices of the static provider in other currencies, keyed by currency code.
+    pub static_prices: HashMap<String, f64>,
+    pub file: Option<String>,
+}
+
+impl Default for PriceSettings {
+    fn default() -> Self {
+        Self {
+            providers: vec![
+                PriceSource::Coinbase,
+                PriceSource::Kraken,
+                PriceSource::Bitstamp,
+            ],
+            aggregation: PriceAggregation::default(),
+            min_quotes: 1,
+            cache_secs: 60,
+            max_stale_secs: 60 * 60,
+            timeout_secs: 10,
+            retries: 1,
+            oracle_max_age_secs: 60 * 60 * 24,
+            static_price: None,
+            static_prices: HashMap::new(),
+            file: None,
+        }
+    }
+}
+
+/// A source of the BTC spot price.
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Price of one bitcoin in `currency`, an upper case ISO 4217 code.
+    async fn price(&self, currency: &str) -> Result<f64, PriceError>;
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,7 +156,7 @@ where
     s.parse::<f64>().map_err(serde::de::Error::custom)
 }
 
-/// The response from Coinbase for a spot Bitcoin price in USD.
+/// The response from Coinbase for a spot Bitcoin price.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SpotPriceResponse {
     #[serde(deserialize_with = "string_to_f64")]
@@ -31,16 +165,702 @@ pub struct SpotPriceResponse {
     pub currency: String,
 }
 
-pub async fn get_bitcoin_price() -> Result<SpotPrice, loco_rs::Error> {
-    let price_from_coinbase = reqwest::get("https://api.coinbase.com/v2/prices/BTC-USD/spot")
-        .await?
-        .json::<CoinbaseResponse>()
-        .await?;
+/// The response from Kraken's ticker, keyed by their name of the pair.
+#[derive(Debug, Deserialize)]
+struct KrakenResponse {
+    error: Vec<String>,
+    #[serde(default)]
+    result: std::collections::HashMap<String, KrakenTicker>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenTicker {
+    /// Last trade closed, as price and lot volume.
+    c: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BitstampResponse {
+    #[serde(deserialize_with = "string_to_f64")]
+    last: f64,
+}
+
+pub struct CoinbaseProvider(reqwest::Client);
+
+#[async_trait]
+impl PriceProvider for CoinbaseProvider {
+    fn name(&self) -> &'static str {
+        "coinbase"
+    }
+
+    async fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        let url = format!("https://api.coinbase.com/v2/prices/BTC-{currency}/spot");
+        let response: CoinbaseResponse = get_json(&self.0, self.name(), &url).await?;
+        Ok(response.data.amount)
+    }
+}
+
+pub struct KrakenProvider(reqwest::Client);
+
+#[async_trait]
+impl PriceProvider for KrakenProvider {
+    fn name(&self) -> &'static str {
+        "kraken"
+    }
+
+    async fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        let url = format!("https://api.kraken.com/0/public/Ticker?pair=XBT{currency}");
+        let response: KrakenResponse = get_json(&self.0, self.name(), &url).await?;
+        kraken_price(response)
+    }
+}
+
+fn kraken_price(response: KrakenResponse) -> Result<f64, PriceError> {
+    if !response.error.is_empty() {
+        return Err(PriceError::Malformed("kraken", response.error.join(", ")));
+    }
+    response
+        .result
+        .into_values()
+        .next()
+        .and_then(|ticker| ticker.c.into_iter().next())
+        .and_then(|last| last.parse().ok())
+        .ok_or_else(|| PriceError::Malformed("kraken", "missing last trade price".to_string()))
+}
+
+pub struct BitstampProvider(reqwest::Client);
+
+#[async_trait]
+impl PriceProvider for BitstampProvider {
+    fn name(&self) -> &'static str {
+        "bitstamp"
+    }
+
+    async fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        let url = format!(
+            "https://www.bitstamp.net/api/v2/ticker/btc{}/",
+            currency.to_lowercase()
+        );
+        let response: BitstampResponse = get_json(&self.0, self.name(), &url).await?;
+        Ok(response.last)
+    }
+}
+
+/// Reads the price from the attestations of our numeric contracts. The most recently matured
+/// BTC/USD event is the latest price our oracles saw.
+pub struct OracleProvider<'a> {
+    sol: &'a SonsOfLiberty,
+    max_age_secs: u64,
+}
 
-    tracing::info!(price = price_from_coinbase.data.amount);
+#[async_trait]
+impl PriceProvider for OracleProvider<'_> {
+    fn name(&self) -> &'static str {
+        "oracle"
+    }
 
+    async fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        if currency != DEFAULT_CURRENCY {
+            return Err(PriceError::Unsupported(self.name(), currency.to_string()));
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let oldest = now.saturating_sub(self.max_age_secs);
+
+        let contracts = self
+            .sol
+            .dlcdevkit
+            .storage
+            .get_contracts()
+            .await
+            .map_err(|e| PriceError::Malformed("oracle", e.to_string()))?;
+        let mut announcements = contracts
+            .iter()
+            .filter_map(offered_contract)
+            .flat_map(|offered| &offered.contract_info)
+            .flat_map(|info| &info.oracle_announcements)
+            .filter(|announcement| {
+                let maturity = u64::from(announcement.oracle_event.event_maturity_epoch);
+                maturity <= now && maturity >= oldest
+            })
+            .filter_map(
+                |announcement| match &announcement.oracle_event.event_descriptor {
+                    EventDescriptor::DigitDecompositionEvent(descriptor)
+                        if is_btc_usd(descriptor) =>
+                    {
+                        Some((announcement, descriptor))
+                    }
+                    _ => None,
+                },
+            )
+            .collect::<Vec<_>>();
+        announcements.sort_by_key(|(announcement, _)| {
+            std::cmp::Reverse(announcement.oracle_event.event_maturity_epoch)
+        });
+
+        for (announcement, descriptor) in announcements {
+            let Some(oracle) = self
+                .sol
+                .oracles
+                .iter()
+                .find(|oracle| oracle.get_public_key() == announcement.oracle_public_key)
+            else {
+                continue;
+            };
+            match oracle
+                .get_attestation(&announcement.oracle_event.event_id)
+                .await
+            {
+                Ok(attestation) => return decode_outcome(descriptor, &attestation.outcomes),
+                Err(e) => tracing::debug!(
+                    "No attestation for event {}: {:?}",
+                    announcement.oracle_event.event_id,
+                    e
+                ),
+            }
+        }
+        Err(PriceError::NoAttestation(self.max_age_secs))
+    }
+}
+
+/// Whether a numeric event attests the BTC/USD price rather than some other number.
+pub fn is_btc_usd(descriptor: &DigitDecompositionEventDescriptor) -> bool {
+    descriptor.unit.eq_ignore_ascii_case(BTC_USD_UNIT)
+}
+
+/// Turns the attested digits of a numeric event back into the number they encode.
+fn decode_outcome(
+    descriptor: &DigitDecompositionEventDescriptor,
+    outcomes: &[String],
+) -> Result<f64, PriceError> {
+    attested_value(descriptor, outcomes)
+        .map(|value| value as f64 * 10f64.powi(descriptor.precision))
+        .ok_or_else(|| PriceError::Malformed("oracle", format!("outcome {outcomes:?}")))
+}
+
+/// The attested outcome of a numeric event, before its precision is applied.
+pub fn attested_value(
+    descriptor: &DigitDecompositionEventDescriptor,
+    outcomes: &[String],
+) -> Option<i64> {
+    let (negative, digits) = match (descriptor.is_signed, outcomes.split_first()) {
+        (true, Some((sign, digits))) => (sign == "-", digits),
+        (true, None) => return None,
+        (false, _) => (false, outcomes),
+    };
+    if digits.len() != descriptor.nb_digits as usize {
+        return None;
+    }
+
+    let mut value = 0i64;
+    for digit in digits {
+        let digit = digit.parse::<u16>().ok()?;
+        if digit >= descriptor.base {
+            return None;
+        }
+        value = value
+            .checked_mul(i64::from(descriptor.base))?
+            .checked_add(i64::from(digit))?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Fixed prices from the settings, keyed by currency.
+pub struct StaticProvider(pub HashMap<String, f64>);
+
+impl StaticProvider {
+    pub fn usd(price: f64) -> Self {
+        Self(HashMap::from([(DEFAULT_CURRENCY.to_string(), price)]))
+    }
+}
+
+#[async_trait]
+impl PriceProvider for StaticProvider {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    async fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        self.0
+            .get(currency)
+            .copied()
+            .ok_or_else(|| PriceError::Unsupported(self.name(), currency.to_string()))
+    }
+}
+
+/// A price written to a file, either as a bare USD number or as a Coinbase spot response.
+pub struct FileProvider(pub PathBuf);
+
+#[async_trait]
+impl PriceProvider for FileProvider {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn price(&self, currency: &str) -> Result<f64, PriceError> {
+        let malformed = |reason: String| {
+            PriceError::Malformed("file", format!("{}: {reason}", self.0.display()))
+        };
+        let contents = tokio::fs::read_to_string(&self.0)
+            .await
+            .map_err(|e| malformed(e.to_string()))?;
+        let contents = contents.trim();
+        let (price_currency, price) = match contents.parse() {
+            Ok(price) => (DEFAULT_CURRENCY.to_string(), price),
+            Err(_) => serde_json::from_str::<CoinbaseResponse>(contents)
+                .map(|response| (response.data.currency, response.data.amount))
+                .map_err(|e| malformed(e.to_string()))?,
+        };
+        if price_currency != currency {
+            return Err(PriceError::Unsupported(self.name(), currency.to_string()));
+        }
+        Ok(price)
+    }
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    client: &reqwest::Client,
+    name: &'static str,
+    url: &str,
+) -> Result<T, PriceError> {
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| PriceError::Http(name, e))?
+        .json()
+        .await
+        .map_err(|e| PriceError::Malformed(name, e.to_string()))
+}
+
+/// Builds the configured providers. The oracle provider needs a running node and is left out
+/// without one.
+pub fn providers<'a>(
+    settings: &PriceSettings,
+    sol: Option<&'a SonsOfLiberty>,
+) -> Result<Vec<Box<dyn PriceProvider + 'a>>, PriceError> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(settings.timeout_secs))
+        .build()
+        .map_err(|e| PriceError::Http("client", e))?;
+
+    let mut providers: Vec<Box<dyn PriceProvider + 'a>> = Vec::new();
+    for source in &settings.providers {
+        match source {
+            PriceSource::Coinbase => providers.push(Box::new(CoinbaseProvider(client.clone()))),
+            PriceSource::Kraken => providers.push(Box::new(KrakenProvider(client.clone()))),
+            PriceSource::Bitstamp => providers.push(Box::new(BitstampProvider(client.clone()))),
+            PriceSource::Oracle => match sol {
+                Some(sol) => providers.push(Box::new(OracleProvider {
+                    sol,
+                    max_age_secs: settings.oracle_max_age_secs,
+                })),
+                None => tracing::warn!("Skipping the oracle price provider without a node"),
+            },
+            PriceSource::Static => {
+                let mut prices = settings
+                    .static_prices
+                    .iter()
+                    .map(|(currency, price)| (currency.to_uppercase(), *price))
+                    .collect::<HashMap<_, _>>();
+                if let Some(price) = settings.static_price {
+                    prices.insert(DEFAULT_CURRENCY.to_string(), price);
+                }
+                if prices.is_empty() {
+                    return Err(PriceError::NotConfigured("static"));
+                }
+                providers.push(Box::new(StaticProvider(prices)));
+            }
+            PriceSource::File => {
+                let path = settings
+                    .file
+                    .as_deref()
+                    .filter(|path| !path.is_empty())
+                    .ok_or(PriceError::NotConfigured("file"))?;
+                providers.push(Box::new(FileProvider(PathBuf::from(path))));
+            }
+        }
+    }
+    Ok(providers)
+}
+
+async fn quote(
+    provider: &dyn PriceProvider,
+    currency: &str,
+    retries: u32,
+) -> Result<f64, PriceError> {
+    let mut attempt = 0;
+    loop {
+        let result = provider.price(currency).await.and_then(|price| {
+            if price.is_finite() && price > 0.0 {
+                Ok(price)
+            } else {
+                Err(PriceError::Malformed(
+                    provider.name(),
+                    format!("price {price}"),
+                ))
+            }
+        });
+        match result {
+            Ok(price) => return Ok(price),
+            Err(e @ PriceError::Unsupported(..)) => return Err(e),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                tracing::debug!("Retrying price provider {}: {e}", provider.name());
+                tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Asks the providers for a price the way the settings aggregate them. Providers without a price
+/// in `currency` are skipped.
+pub async fn fetch_price(
+    settings: &PriceSettings,
+    providers: &[Box<dyn PriceProvider + '_>],
+    currency: &str,
+) -> Result<SpotPrice, PriceError> {
+    let mut quotes = Vec::new();
+    for provider in providers {
+        match quote(provider.as_ref(), currency, settings.retries).await {
+            Ok(price) => {
+                tracing::info!(provider = provider.name(), currency, price);
+                quotes.push((provider.name(), price));
+                if settings.aggregation == PriceAggregation::Fallback {
+                    break;
+                }
+            }
+            Err(e @ PriceError::Unsupported(..)) => tracing::debug!("{e}"),
+            Err(e) => tracing::warn!("Price provider {} failed: {e}", provider.name()),
+        }
+    }
+
+    let required = match settings.aggregation {
+        PriceAggregation::Fallback => 1,
+        PriceAggregation::Median => settings.min_quotes.max(1),
+    };
+    if quotes.len() < required {
+        return Err(PriceError::NotEnoughQuotes(quotes.len(), required));
+    }
+    let (amount, source) = aggregate(&quotes);
     Ok(SpotPrice {
-        currency: "USD".to_string(),
-        amount: price_from_coinbase.data.amount,
+        currency: currency.to_string(),
+        amount,
+        source,
     })
 }
+
+/// Median of the quotes, named after the providers that made it up.
+fn aggregate(quotes: &[(&str, f64)]) -> (f64, String) {
+    if let [(name, price)] = quotes {
+        return (*price, (*name).to_string());
+    }
+
+    let mut prices = quotes.iter().map(|(_, price)| *price).collect::<Vec<_>>();
+    prices.sort_by(f64::total_cmp);
+    let middle = prices.len() / 2;
+    let median = if prices.len() % 2 == 0 {
+        (prices[middle - 1] + prices[middle]) / 2.0
+    } else {
+        prices[middle]
+    };
+    let names = quotes
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(",");
+    (median, format!("median({names})"))
+}
+
+/// Upper cases a currency code and checks it is three letters.
+pub fn normalize_currency(currency: &str) -> Result<String, PriceError> {
+    let currency = currency.trim().to_uppercase();
+    if currency.len() == 3 && currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(currency)
+    } else {
+        Err(PriceError::InvalidCurrency(currency))
+    }
+}
+
+fn cached(currency: &str, max_age_secs: u64) -> Option<SpotPrice> {
+    let cache = MEMORY_CACHE.lock().ok()?;
+    let cached = cache
+        .iter()
+        .find(|cached| cached.price.currency == currency)?;
+    let age = (Utc::now() - cached.fetched_at).num_seconds();
+    (age >= 0 && age.unsigned_abs() < max_age_secs).then(|| cached.price.clone())
+}
+
+fn remember(price: &SpotPrice, fetched_at: DateTime<Utc>) {
+    if let Ok(mut cache) = MEMORY_CACHE.lock() {
+        cache.retain(|cached| cached.price.currency != price.currency);
+        cache.push(CachedPrice {
+            price: price.clone(),
+            fetched_at,
+        });
+    }
+}
+
+fn stored_price(stored: prices::Model) -> SpotPrice {
+    SpotPrice {
+        currency: stored.currency,
+        amount: stored.amount_f64(),
+        source: stored.source,
+    }
+}
+
+/// The BTC/USD price, see [`get_bitcoin_price_in`].
+pub async fn get_bitcoin_price(
+    db: &DatabaseConnection,
+    settings: &Settings,
+    sol: Option<&SonsOfLiberty>,
+) -> Result<SpotPrice> {
+    get_bitcoin_price_in(db, settings, sol, DEFAULT_CURRENCY).await
+}
+
+/// The BTC price in every currency of `fiat_currencies`. Currencies no provider has a price for
+/// are left out.
+pub async fn get_fiat_prices(
+    db: &DatabaseConnection,
+    settings: &Settings,
+    sol: Option<&SonsOfLiberty>,
+) -> Result<Vec<SpotPrice>> {
+    let mut prices: Vec<SpotPrice> = Vec::new();
+    for currency in &settings.fiat_currencies {
+        let currency = normalize_currency(currency).map_err(|e| Error::string(&e.to_string()))?;
+        if prices.iter().any(|price| price.currency == currency) {
+            continue;
+        }
+        match get_bitcoin_price_in(db, settings, sol, &currency).await {
+            Ok(price) => prices.push(price),
+            Err(e) => tracing::warn!("Skipping the {currency} valuation: {e}"),
+        }
+    }
+    Ok(prices)
+}
+
+/// The BTC price in `currency`. Prices are cached in memory and in the database for
+/// `price.cache_secs`, so separate task runs share them. When every provider fails, the last
+/// stored price is used as long as it is younger than `price.max_stale_secs`.
+pub async fn get_bitcoin_price_in(
+    db: &DatabaseConnection,
+    settings: &Settings,
+    sol: Option<&SonsOfLiberty>,
+    currency: &str,
+) -> Result<SpotPrice> {
+    let currency = normalize_currency(currency).map_err(|e| Error::string(&e.to_string()))?;
+    let price_settings = &settings.price;
+    if let Some(price) = cached(&currency, price_settings.cache_secs) {
+        return Ok(price);
+    }
+
+    let latest = prices::Model::latest(db, &currency).await?;
+    if let Some(stored) = latest
+        .as_ref()
+        .filter(|stored| stored.age_secs() < price_settings.cache_secs as i64)
+    {
+        let price = stored_price(stored.clone());
+        remember(&price, stored.created_at.to_utc());
+        return Ok(price);
+    }
+
+    let fetched = match providers(price_settings, sol) {
+        Ok(providers) => fetch_price(price_settings, &providers, &currency).await,
+        Err(e) => Err(e),
+    };
+    match fetched {
+        Ok(price) => {
+            prices::ActiveModel::record(db, &price.currency, price.amount, &price.source).await?;
+            remember(&price, Utc::now());
+            Ok(price)
+        }
+        Err(e) => {
+            let stale = latest
+                .filter(|stored| stored.age_secs() < price_settings.max_stale_secs as i64)
+                .ok_or_else(|| {
+                    Error::string(&format!("Failed to get bitcoin price in {currency}: {e}"))
+                })?;
+            tracing::warn!(
+                "Failed to get bitcoin price in {currency}, using the {} second old price: {e}",
+                stale.age_secs()
+            );
+            Ok(stored_price(stale))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price_settings(aggregation: PriceAggregation, min_quotes: usize) -> PriceSettings {
+        PriceSettings {
+            aggregation,
+            min_quotes,
+            retries: 0,
+            ..PriceSettings::default()
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl PriceProvider for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn price(&self, _currency: &str) -> Result<f64, PriceError> {
+            Err(PriceError::NotConfigured("failing"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_takes_first_answer() {
+        let providers: Vec<Box<dyn PriceProvider>> = vec![
+            Box::new(Failing),
+            Box::new(StaticProvider::usd(0.0)),
+            Box::new(StaticProvider::usd(65_000.0)),
+            Box::new(StaticProvider::usd(70_000.0)),
+        ];
+        let price = fetch_price(
+            &price_settings(PriceAggregation::Fallback, 1),
+            &providers,
+            "USD",
+        )
+        .await
+        .unwrap();
+        assert_eq!(price.amount, 65_000.0);
+        assert_eq!(price.source, "static");
+    }
+
+    #[tokio::test]
+    async fn test_median_of_answers() {
+        let providers: Vec<Box<dyn PriceProvider>> = vec![
+            Box::new(StaticProvider::usd(60_000.0)),
+            Box::new(Failing),
+            Box::new(StaticProvider::usd(64_000.0)),
+            Box::new(StaticProvider::usd(61_000.0)),
+        ];
+        let price = fetch_price(
+            &price_settings(PriceAggregation::Median, 3),
+            &providers,
+            "USD",
+        )
+        .await
+        .unwrap();
+        assert_eq!(price.amount, 61_000.0);
+        assert_eq!(price.source, "median(static,static,static)");
+
+        assert!(matches!(
+            fetch_price(
+                &price_settings(PriceAggregation::Median, 4),
+                &providers,
+                "USD"
+            )
+            .await,
+            Err(PriceError::NotEnoughQuotes(3, 4))
+        ));
+        assert_eq!(aggregate(&[("a", 1.0), ("b", 4.0)]).0, 2.5);
+    }
+
+    #[tokio::test]
+    async fn test_prices_per_currency() {
+        let providers: Vec<Box<dyn PriceProvider>> = vec![
+            Box::new(StaticProvider::usd(65_000.0)),
+            Box::new(StaticProvider(HashMap::from([(
+                "EUR".to_string(),
+                60_000.0,
+            )]))),
+        ];
+        let settings = price_settings(PriceAggregation::Fallback, 1);
+        let price = fetch_price(&settings, &providers, "EUR").await.unwrap();
+        assert_eq!(price.amount, 60_000.0);
+        assert_eq!(price.currency, "EUR");
+        assert!(fetch_price(&settings, &providers, "CHF").await.is_err());
+
+        assert_eq!(normalize_currency(" chf ").unwrap(), "CHF");
+        assert!(normalize_currency("US/").is_err());
+        assert!(normalize_currency("EURO").is_err());
+    }
+
+    #[test]
+    fn test_parse_exchange_responses() {
+        let kraken: KrakenResponse = serde_json::from_str(
+            r#"{"error":[],"result":{"XXBTZUSD":{"a":["1"],"c":["64123.40000","0.01"]}}}"#,
+        )
+        .unwrap();
+        assert_eq!(kraken_price(kraken).unwrap(), 64_123.4);
+
+        let kraken: KrakenResponse =
+            serde_json::from_str(r#"{"error":["EQuery:Unknown asset pair"]}"#).unwrap();
+        assert!(kraken_price(kraken).is_err());
+
+        let bitstamp: BitstampResponse =
+            serde_json::from_str(r#"{"last":"64100","bid":"64099"}"#).unwrap();
+        assert_eq!(bitstamp.last, 64_100.0);
+    }
+
+    #[test]
+    fn test_decode_oracle_outcome() {
+        let descriptor = DigitDecompositionEventDescriptor {
+            base: 2,
+            is_signed: false,
+            unit: "usd/btc".to_string(),
+            precision: 0,
+            nb_digits: 4,
+        };
+        let digits = |d: &[&str]| d.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            decode_outcome(&descriptor, &digits(&["1", "0", "1", "1"])).unwrap(),
+            11.0
+        );
+        assert!(decode_outcome(&descriptor, &digits(&["1", "0", "2", "1"])).is_err());
+        assert!(decode_outcome(&descriptor, &digits(&["1", "0"])).is_err());
+        assert!(is_btc_usd(&descriptor));
+        assert!(!is_btc_usd(&DigitDecompositionEventDescriptor {
+            unit: "eh/s".to_string(),
+            ..descriptor.clone()
+        }));
+
+        let signed = DigitDecompositionEventDescriptor {
+            base: 10,
+            is_signed: true,
+            precision: -1,
+            nb_digits: 3,
+            ..descriptor
+        };
+        assert_eq!(
+            decode_outcome(&signed, &digits(&["-", "1", "2", "5"])).unwrap(),
+            -12.5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_provider() {
+        let path = std::env::temp_dir().join(format!(
+            "sol-price-{}",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        std::fs::write(&path, "63000.5\n").unwrap();
+        assert_eq!(
+            FileProvider(path.clone()).price("USD").await.unwrap(),
+            63_000.5
+        );
+
+        std::fs::write(
+            &path,
+            r#"{"data":{"amount":"62000","base":"BTC","currency":"USD"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            FileProvider(path.clone()).price("USD").await.unwrap(),
+            62_000.0
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
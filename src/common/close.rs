@@ -0,0 +1,328 @@
+use bitcoin::{
+    absolute::LockTime,
+    ecdsa,
+    secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey},
+    sighash::{EcdsaSighashType, SighashCache},
+    transaction::Version,
+    Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use ddk_manager::contract::signed_contract::SignedContract;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Virtual size of a close transaction spending the 2-of-2 funding output to two outputs,
+/// rounded up so the fee rate is never undershot.
+pub const CLOSE_TX_VBYTES: u64 = 200;
+
+/// Outputs below this value are left to the miners instead of being created.
+pub const DUST_LIMIT: u64 = 1_000;
+
+#[derive(Error, Debug)]
+pub enum CloseError {
+    #[error("Payouts of {0} sats do not match the total collateral of {1} sats")]
+    PayoutMismatch(u64, u64),
+    #[error("Payouts of {0} sats are more than the funding output of {1} sats")]
+    FundingShortfall(u64, u64),
+    #[error("Fee of {0} sats is more than the total collateral")]
+    FeeTooHigh(u64),
+    #[error("Failed to compute sighash: {0}")]
+    Sighash(String),
+    #[error("Invalid close signature: {0}")]
+    InvalidSignature(#[from] bitcoin::secp256k1::Error),
+    #[error("Close terms overflow")]
+    Overflow,
+}
+
+/// Payout split a cooperative close is negotiated on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloseTerms {
+    pub offer_payout: u64,
+    pub accept_payout: u64,
+    pub fee_rate: u64,
+}
+
+impl CloseTerms {
+    pub fn fee(&self) -> Result<u64, CloseError> {
+        self.fee_rate
+            .checked_mul(CLOSE_TX_VBYTES)
+            .ok_or(CloseError::Overflow)
+    }
+
+    /// Sum of both payouts.
+    pub fn payout_total(&self) -> Result<u64, CloseError> {
+        self.offer_payout
+            .checked_add(self.accept_payout)
+            .ok_or(CloseError::Overflow)
+    }
+
+    /// Output values when spending a funding output of `fund_value` sats. The funding output
+    /// holds the collateral plus the fee both parties reserved for the CET, which goes to the
+    /// close fee first. Each party pays half of what is left of the fee. A party that cannot
+    /// cover its half leaves the rest to the other one, and dust outputs are dropped.
+    pub fn output_values(&self, fund_value: u64) -> Result<(u64, u64), CloseError> {
+        let payout_total = self.payout_total()?;
+        if payout_total > fund_value {
+            return Err(CloseError::FundingShortfall(payout_total, fund_value));
+        }
+        let fee = self.fee()?.saturating_sub(fund_value - payout_total);
+        if fee >= payout_total {
+            return Err(CloseError::FeeTooHigh(fee));
+        }
+        let offer_fee = (fee / 2).min(self.offer_payout);
+        let accept_fee = fee - offer_fee;
+        let (offer_fee, accept_fee) = if accept_fee > self.accept_payout {
+            (fee - self.accept_payout, self.accept_payout)
+        } else {
+            (offer_fee, accept_fee)
+        };
+
+        let dust = |value: u64| if value < DUST_LIMIT { 0 } else { value };
+        Ok((
+            dust(self.offer_payout - offer_fee),
+            dust(self.accept_payout - accept_fee),
+        ))
+    }
+
+    /// Realized PnL of a party closing with these terms.
+    pub fn pnl(
+        &self,
+        is_offer_party: bool,
+        collateral: u64,
+        fund_value: u64,
+    ) -> Result<i64, CloseError> {
+        let (offer_value, accept_value) = self.output_values(fund_value)?;
+        let value = if is_offer_party {
+            offer_value
+        } else {
+            accept_value
+        };
+        let value = i64::try_from(value).map_err(|_| CloseError::Overflow)?;
+        let collateral = i64::try_from(collateral).map_err(|_| CloseError::Overflow)?;
+        value.checked_sub(collateral).ok_or(CloseError::Overflow)
+    }
+}
+
+/// Builds the transaction spending the funding output straight to both payout addresses. Both
+/// parties build it from their own copy of the contract, so it must be deterministic.
+pub fn build_close_transaction(
+    contract: &SignedContract,
+    terms: &CloseTerms,
+) -> Result<Transaction, CloseError> {
+    let offered = &contract.accepted_contract.offered_contract;
+    let payout_total = terms.payout_total()?;
+    if payout_total != offered.total_collateral {
+        return Err(CloseError::PayoutMismatch(
+            payout_total,
+            offered.total_collateral,
+        ));
+    }
+
+    let (offer_value, accept_value) = terms.output_values(fund_value(contract))?;
+    let output = [
+        (offer_value, &offered.offer_params.payout_script_pubkey),
+        (
+            accept_value,
+            &contract
+                .accepted_contract
+                .accept_params
+                .payout_script_pubkey,
+        ),
+    ]
+    .into_iter()
+    .filter(|(value, _)| *value > 0)
+    .map(|(value, script_pubkey)| TxOut {
+        value: Amount::from_sat(value),
+        script_pubkey: script_pubkey.clone(),
+    })
+    .collect();
+
+    Ok(Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: funding_outpoint(contract),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }],
+        output,
+    })
+}
+
+fn funding_outpoint(contract: &SignedContract) -> OutPoint {
+    contract
+        .accepted_contract
+        .dlc_transactions
+        .get_fund_outpoint()
+}
+
+/// Value of the funding output, collateral and reserved CET fee included.
+pub fn fund_value(contract: &SignedContract) -> u64 {
+    contract
+        .accepted_contract
+        .dlc_transactions
+        .get_fund_output()
+        .value
+        .to_sat()
+}
+
+fn close_sighash(contract: &SignedContract, tx: &Transaction) -> Result<Message, CloseError> {
+    let dlc_transactions = &contract.accepted_contract.dlc_transactions;
+    let sighash = SighashCache::new(tx)
+        .p2wsh_signature_hash(
+            0,
+            &dlc_transactions.funding_script_pubkey,
+            dlc_transactions.get_fund_output().value,
+            EcdsaSighashType::All,
+        )
+        .map_err(|e| CloseError::Sighash(e.to_string()))?;
+    Ok(Message::from(sighash))
+}
+
+/// Signs the funding input of the close transaction with our funding key.
+pub fn sign_close_transaction(
+    contract: &SignedContract,
+    tx: &Transaction,
+    secret_key: &SecretKey,
+) -> Result<Signature, CloseError> {
+    let message = close_sighash(contract, tx)?;
+    Ok(Secp256k1::signing_only().sign_ecdsa(&message, secret_key))
+}
+
+/// Checks the counterparty's signature over the close transaction.
+pub fn verify_close_signature(
+    contract: &SignedContract,
+    tx: &Transaction,
+    signature: &Signature,
+    public_key: &PublicKey,
+) -> Result<(), CloseError> {
+    let message = close_sighash(contract, tx)?;
+    Secp256k1::verification_only().verify_ecdsa(&message, signature, public_key)?;
+    Ok(())
+}
+
+/// Adds the 2-of-2 witness to the close transaction. Signatures are ordered like the public keys
+/// in the funding script.
+pub fn finalize_close_transaction(
+    contract: &SignedContract,
+    mut tx: Transaction,
+    offer_signature: Signature,
+    accept_signature: Signature,
+) -> Transaction {
+    let offer_pubkey = contract
+        .accepted_contract
+        .offered_contract
+        .offer_params
+        .fund_pubkey;
+    let accept_pubkey = contract.accepted_contract.accept_params.fund_pubkey;
+
+    let mut signatures = [
+        (offer_pubkey, offer_signature),
+        (accept_pubkey, accept_signature),
+    ];
+    signatures.sort_by_key(|(public_key, _)| public_key.serialize());
+
+    let mut witness = Witness::new();
+    witness.push([]);
+    for (_, signature) in signatures {
+        witness.push(
+            ecdsa::Signature {
+                signature,
+                sighash_type: EcdsaSighashType::All,
+            }
+            .to_vec(),
+        );
+    }
+    witness.push(
+        contract
+            .accepted_contract
+            .dlc_transactions
+            .funding_script_pubkey
+            .as_bytes(),
+    );
+    tx.input[0].witness = witness;
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(offer_payout: u64, accept_payout: u64) -> CloseTerms {
+        CloseTerms {
+            offer_payout,
+            accept_payout,
+            fee_rate: 10,
+        }
+    }
+
+    #[test]
+    fn test_fee_is_split_between_parties() {
+        assert_eq!(
+            terms(50_000, 50_000).output_values(100_000).unwrap(),
+            (49_000, 49_000)
+        );
+    }
+
+    #[test]
+    fn test_reserved_cet_fee_pays_the_close_fee_first() {
+        assert_eq!(
+            terms(50_000, 50_000).output_values(101_000).unwrap(),
+            (49_500, 49_500)
+        );
+        assert_eq!(
+            terms(50_000, 50_000).output_values(103_000).unwrap(),
+            (50_000, 50_000)
+        );
+    }
+
+    #[test]
+    fn test_payouts_above_fund_output_are_rejected() {
+        assert!(matches!(
+            terms(50_000, 50_000).output_values(99_999),
+            Err(CloseError::FundingShortfall(100_000, 99_999))
+        ));
+    }
+
+    #[test]
+    fn test_party_without_payout_pays_no_fee() {
+        assert_eq!(
+            terms(0, 100_000).output_values(100_000).unwrap(),
+            (0, 98_000)
+        );
+        assert_eq!(
+            terms(100_000, 500).output_values(100_500).unwrap(),
+            (98_500, 0)
+        );
+    }
+
+    #[test]
+    fn test_fee_above_collateral_is_rejected() {
+        assert!(terms(1_000, 500).output_values(1_500).is_err());
+    }
+
+    #[test]
+    fn test_overflowing_terms_are_rejected() {
+        let overflowing = CloseTerms {
+            offer_payout: u64::MAX,
+            accept_payout: u64::MAX,
+            fee_rate: u64::MAX,
+        };
+        assert!(matches!(overflowing.fee(), Err(CloseError::Overflow)));
+        assert!(matches!(
+            overflowing.output_values(u64::MAX),
+            Err(CloseError::Overflow)
+        ));
+        assert!(matches!(
+            terms(u64::MAX, 0).pnl(true, 0, u64::MAX),
+            Err(CloseError::Overflow)
+        ));
+    }
+
+    #[test]
+    fn test_pnl_is_relative_to_collateral() {
+        let terms = terms(70_000, 30_000);
+        assert_eq!(terms.pnl(true, 50_000, 100_000).unwrap(), 19_000);
+        assert_eq!(terms.pnl(false, 50_000, 100_000).unwrap(), -21_000);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use bitcoin::{
+    secp256k1::{PublicKey, Secp256k1},
+    Address, Network, OutPoint, Psbt, ScriptBuf,
+};
+use ddk::{wallet::DlcDevKitWallet, Transport};
+use ddk_manager::{
+    contract::{contract_input::ContractInput, offered_contract::OfferedContract, Contract},
+    contract_updater,
+    error::Error as ManagerError,
+    manager::REFUND_DELAY,
+    ContractId, Storage, SystemTimeProvider, Utxo, Wallet as ManagerWallet,
+};
+use dlc_messages::{oracle_msgs::OracleAnnouncement, AcceptDlc, Message, OfferDlc};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+
+use crate::{
+    common::wallet::{self, Coin},
+    sol::SonsOfLiberty,
+};
+
+/// Spendable coins, narrowed down to the selected ones when a selection is given. Selected coins
+/// must belong to the wallet, not be frozen and not already fund another contract.
+pub async fn selected_coins(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    selected: Option<&[OutPoint]>,
+) -> Result<Vec<Coin>> {
+    let coins = wallet::spendable_utxos(db, sol).await?;
+    let Some(selected) = selected else {
+        return Ok(coins);
+    };
+    if selected.is_empty() {
+        return Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason("Select at least one coin"),
+        ));
+    }
+
+    let spendable = coins
+        .iter()
+        .map(|coin| coin.outpoint)
+        .collect::<HashSet<_>>();
+    let unavailable = selected
+        .iter()
+        .filter(|outpoint| !spendable.contains(outpoint))
+        .collect::<Vec<_>>();
+    if !unavailable.is_empty() {
+        return Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail {
+                error: Some(join(unavailable)),
+                description: Some("Selected coins are frozen, reserved or unknown".to_string()),
+            },
+        ));
+    }
+    Ok(coins
+        .into_iter()
+        .filter(|coin| selected.contains(&coin.outpoint))
+        .collect())
+}
+
+/// Checks coins picked to fund a contract before DDK is asked to fund it.
+pub async fn check_selected_coins(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    selected: Option<&[OutPoint]>,
+) -> Result<()> {
+    if selected.is_some() {
+        selected_coins(db, sol, selected).await?;
+    }
+    Ok(())
+}
+
+/// DDK's wallet with coin selection restricted to the coins coin control allows. Everything but
+/// coin selection goes to DDK's wallet, so keys and addresses stay DDK's.
+struct CoinControlWallet<'a> {
+    wallet: &'a DlcDevKitWallet,
+    coins: Vec<Coin>,
+    /// Fund the contract with every coin, as picked by the user.
+    spend_all: bool,
+    network: Network,
+}
+
+impl CoinControlWallet<'_> {
+    fn select(&self, amount: u64, fee_rate: u64) -> Option<Vec<&Coin>> {
+        let mut coins = self.coins.iter().collect::<Vec<_>>();
+        if !self.spend_all {
+            coins.sort_by_key(|coin| std::cmp::Reverse(coin.txout.value));
+        }
+
+        let mut selected = Vec::new();
+        let mut value = 0u64;
+        for coin in coins {
+            value = value.checked_add(coin.txout.value.to_sat())?;
+            selected.push(coin);
+            let inputs_fee = wallet::INPUT_VBYTES
+                .checked_mul(selected.len() as u64)?
+                .checked_mul(fee_rate)?;
+            if !self.spend_all && value >= amount.checked_add(inputs_fee)? {
+                return Some(selected);
+            }
+        }
+        let inputs_fee = wallet::INPUT_VBYTES
+            .checked_mul(selected.len() as u64)?
+            .checked_mul(fee_rate)?;
+        (self.spend_all && value >= amount.checked_add(inputs_fee)?).then_some(selected)
+    }
+}
+
+#[async_trait]
+impl ManagerWallet for CoinControlWallet<'_> {
+    async fn get_new_address(&self) -> std::result::Result<Address, ManagerError> {
+        self.wallet.get_new_address().await
+    }
+
+    async fn get_new_change_address(&self) -> std::result::Result<Address, ManagerError> {
+        self.wallet.get_new_change_address().await
+    }
+
+    async fn get_utxos_for_amount(
+        &self,
+        amount: u64,
+        fee_rate: u64,
+        _lock_utxos: bool,
+    ) -> std::result::Result<Vec<Utxo>, ManagerError> {
+        let selected = self.select(amount, fee_rate).ok_or_else(|| {
+            ManagerError::InvalidParameters(format!(
+                "The allowed coins cannot fund {amount} sats at {fee_rate} sat/vB"
+            ))
+        })?;
+        selected
+            .into_iter()
+            .map(|coin| {
+                let address = Address::from_script(&coin.txout.script_pubkey, self.network)
+                    .map_err(|e| ManagerError::InvalidParameters(e.to_string()))?;
+                Ok(Utxo {
+                    tx_out: coin.txout.clone(),
+                    outpoint: coin.outpoint,
+                    address,
+                    redeem_script: ScriptBuf::new(),
+                    reserved: false,
+                })
+            })
+            .collect()
+    }
+
+    fn import_address(&self, address: &Address) -> std::result::Result<(), ManagerError> {
+        self.wallet.import_address(address)
+    }
+
+    async fn sign_psbt_input(
+        &self,
+        psbt: &mut Psbt,
+        input_index: usize,
+    ) -> std::result::Result<(), ManagerError> {
+        self.wallet.sign_psbt_input(psbt, input_index).await
+    }
+
+    fn unreserve_utxos(&self, outpoints: &[OutPoint]) -> std::result::Result<(), ManagerError> {
+        self.wallet.unreserve_utxos(outpoints)
+    }
+}
+
+async fn coin_control_wallet<'a>(
+    db: &DatabaseConnection,
+    sol: &'a SonsOfLiberty,
+    selected: Option<&[OutPoint]>,
+) -> Result<CoinControlWallet<'a>> {
+    Ok(CoinControlWallet {
+        wallet: &sol.dlcdevkit.wallet,
+        coins: selected_coins(db, sol, selected).await?,
+        spend_all: selected.is_some(),
+        network: sol.network,
+    })
+}
+
+/// Builds an offer funded from the allowed coins only, stores it and sends it. Frozen coins and
+/// coins reserved by contracts or exported PSBTs are never handed to DDK, and when coins are
+/// selected, the offer spends exactly those.
+pub async fn send_offer(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    contract_input: &ContractInput,
+    counter_party: PublicKey,
+    announcements: Vec<Vec<OracleAnnouncement>>,
+    selected: Option<&[OutPoint]>,
+) -> Result<OfferDlc> {
+    contract_input.validate().map_err(manager_error)?;
+    let wallet = coin_control_wallet(db, sol, selected).await?;
+
+    let (offered, offer) = contract_updater::offer_contract(
+        &Secp256k1::new(),
+        contract_input,
+        announcements,
+        REFUND_DELAY,
+        &counter_party,
+        &&wallet,
+        &sol.blockchain,
+        &&SystemTimeProvider {},
+        &sol.dlcdevkit.wallet,
+    )
+    .await
+    .map_err(manager_error)?;
+    offered.validate().map_err(manager_error)?;
+
+    sol.dlcdevkit
+        .storage
+        .create_contract(&offered)
+        .await
+        .map_err(storage_error)?;
+    sol.dlcdevkit
+        .transport
+        .send_message(counter_party, Message::Offer(offer.clone()))
+        .await;
+    Ok(offer)
+}
+
+/// Accepts a received offer, funded from the allowed coins like [`send_offer`], and sends the
+/// acceptance.
+pub async fn accept_offer(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    offered: &OfferedContract,
+    selected: Option<&[OutPoint]>,
+) -> Result<(ContractId, AcceptDlc)> {
+    let wallet = coin_control_wallet(db, sol, selected).await?;
+
+    let (accepted, accept) = contract_updater::accept_contract(
+        &Secp256k1::new(),
+        offered,
+        &&wallet,
+        &sol.dlcdevkit.wallet,
+        &sol.blockchain,
+    )
+    .await
+    .map_err(manager_error)?;
+    let contract_id = accepted.get_contract_id();
+
+    sol.dlcdevkit
+        .storage
+        .update_contract(&Contract::Accepted(accepted))
+        .await
+        .map_err(storage_error)?;
+    sol.dlcdevkit
+        .transport
+        .send_message(offered.counter_party, Message::Accept(accept.clone()))
+        .await;
+    Ok((contract_id, accept))
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn manager_error(e: ManagerError) -> Error {
+    Error::CustomError(
+        StatusCode::BAD_REQUEST,
+        ErrorDetail {
+            error: Some(e.to_string()),
+            description: Some("Failed to fund the contract".to_string()),
+        },
+    )
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn storage_error(e: ManagerError) -> Error {
+    Error::CustomError(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorDetail::with_reason(e.to_string()),
+    )
+}
+
+fn join<'a>(outpoints: impl IntoIterator<Item = &'a OutPoint>) -> String {
+    outpoints
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -2,13 +2,19 @@ use std::sync::Arc;
 
 use axum::http::StatusCode;
 use bitcoin::{Address, Transaction};
-use ddk::storage::postgres::PostgresStore;
-use ddk::storage::sqlx::ContractRowNoBytes;
 use ddk::wallet::LocalOutput;
-use ddk::Balance;
+use ddk::{Balance, Transport};
+use ddk_manager::{
+    contract::{contract_info::ContractInfo, offered_contract::OfferedContract, Contract},
+    ContractId, Oracle, Storage,
+};
+use dlc_messages::{oracle_msgs::OracleAttestation, Message, Reject};
 use loco_rs::{controller::ErrorDetail, prelude::*};
 
-use crate::{controllers::contracts::ContractFilter, sol::SonsOfLiberty};
+use crate::{
+    models::{contract_events, contracts::ContractState},
+    sol::SonsOfLiberty,
+};
 
 pub async fn get_balance(ddk: Arc<SonsOfLiberty>) -> Result<Balance> {
     ddk.dlcdevkit.balance().await.map_err(|e| {
@@ -19,13 +25,6 @@ pub async fn get_balance(ddk: Arc<SonsOfLiberty>) -> Result<Balance> {
     })
 }
 
-pub async fn get_offers(storage: Arc<PostgresStore>) -> Result<Vec<ContractRowNoBytes>> {
-    storage
-        .get_offer_rows()
-        .await
-        .map_err(sqlx_error_to_http_error)
-}
-
 pub async fn get_new_addresses(ddk: Arc<SonsOfLiberty>) -> Result<Address> {
     let address = ddk
         .dlcdevkit
@@ -51,65 +50,235 @@ pub fn get_utxos(ddk: &Arc<SonsOfLiberty>) -> Result<Vec<LocalOutput>> {
         .map_err(wallet_error_to_http_error)
 }
 
-pub async fn get_filtered_contracts(
-    storage: Arc<PostgresStore>,
-    filter: Option<ContractFilter>,
-) -> Result<Vec<ContractRowNoBytes>, loco_rs::Error> {
-    let filter = filter.unwrap_or(ContractFilter::All);
-    match filter {
-        ContractFilter::All => {
-            let contracts = storage
-                .get_contract_rows(None)
-                .await
-                .map_err(sqlx_error_to_http_error)?;
-            Ok(contracts)
+#[allow(clippy::needless_pass_by_value)]
+fn wallet_error_to_http_error(e: ddk::error::WalletError) -> Error {
+    Error::CustomError(
+        StatusCode::BAD_REQUEST,
+        ErrorDetail::with_reason(e.to_string()),
+    )
+}
+
+/// Closes confirmed contracts whose announcements come from more than one oracle.
+///
+/// DDK only queries its own oracle when closing, so contracts with a threshold above one would
+/// never settle. This collects attestations from every configured oracle and closes the contract
+/// once enough of them are available, and runs before DDK's check so every multi-oracle contract
+/// closes here. Announcements of oracles that are not configured are skipped, so a contract
+/// offered by a peer using other oracles only closes if the configured ones reach its threshold.
+/// Returns the ids of the closed contracts.
+pub async fn close_with_oracle_attestations(sol: &SonsOfLiberty) -> Result<Vec<String>> {
+    let contracts = sol
+        .dlcdevkit
+        .storage
+        .get_confirmed_contracts()
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    let now = u32::try_from(chrono::Utc::now().timestamp()).unwrap_or(u32::MAX);
+    let mut closed = Vec::new();
+    for contract in contracts {
+        // Any contract info whose oracles reached their threshold can close the contract.
+        let mut attestations = None;
+        for info in &contract.accepted_contract.offered_contract.contract_info {
+            attestations = threshold_attestations(sol, info, now).await;
+            if attestations.is_some() {
+                break;
+            }
         }
-        ContractFilter::Active => {
-            let active_contracts = storage
-                .get_contract_rows(Some(vec![
-                    "accepted".to_string().into(),
-                    "signed".to_string().into(),
-                    "confirmed".to_string().into(),
-                    "pre-closed".to_string().into(),
-                ]))
-                .await
-                .map_err(sqlx_error_to_http_error)?;
-            Ok(active_contracts)
+        let Some(attestations) = attestations else {
+            continue;
+        };
+
+        let contract_id = contract.accepted_contract.get_contract_id();
+        match sol
+            .dlcdevkit
+            .manager
+            .close_confirmed_contract(&contract_id, attestations)
+            .await
+        {
+            Ok(_) => closed.push(hex::encode(contract_id)),
+            Err(e) => tracing::error!(
+                "Failed to close contract {}: {:?}",
+                hex::encode(contract_id),
+                e
+            ),
         }
-        ContractFilter::Closed => {
-            let closed_contracts = storage
-                .get_contract_rows(Some(vec!["closed".to_string().into()]))
-                .await
-                .map_err(sqlx_error_to_http_error)?;
-            Ok(closed_contracts)
+    }
+
+    Ok(closed)
+}
+
+/// Attestations of a matured multi-oracle contract info, indexed by announcement, once at least
+/// its threshold of oracles attested.
+async fn threshold_attestations(
+    sol: &SonsOfLiberty,
+    info: &ContractInfo,
+    now: u32,
+) -> Option<Vec<(usize, OracleAttestation)>> {
+    let matured = info
+        .oracle_announcements
+        .iter()
+        .all(|announcement| announcement.oracle_event.event_maturity_epoch <= now);
+    if info.oracle_announcements.len() < 2 || !matured {
+        return None;
+    }
+
+    let mut attestations = Vec::new();
+    for (index, announcement) in info.oracle_announcements.iter().enumerate() {
+        let Some(oracle) = sol
+            .oracles
+            .iter()
+            .find(|oracle| oracle.get_public_key() == announcement.oracle_public_key)
+        else {
+            continue;
+        };
+        match oracle
+            .get_attestation(&announcement.oracle_event.event_id)
+            .await
+        {
+            Ok(attestation) => attestations.push((index, attestation)),
+            Err(e) => tracing::warn!(
+                "No attestation for event {}: {:?}",
+                announcement.oracle_event.event_id,
+                e
+            ),
         }
-        ContractFilter::Failed => {
-            let failed_contracts = storage
-                .get_contract_rows(Some(vec![
-                    "refunded".to_string().into(),
-                    "failed-accept".to_string().into(),
-                    "failed-sign".to_string().into(),
-                    "rejected".to_string().into(),
-                ]))
-                .await
-                .map_err(sqlx_error_to_http_error)?;
-            Ok(failed_contracts)
+        if attestations.len() >= info.threshold {
+            return Some(attestations);
         }
     }
+    None
 }
 
-#[allow(clippy::needless_pass_by_value)]
-fn sqlx_error_to_http_error(e: ddk::storage::sqlx::SqlxError) -> Error {
-    Error::CustomError(
-        StatusCode::NOT_FOUND,
-        ErrorDetail::with_reason(e.to_string()),
-    )
+/// Loads an offer we received and have not acted on yet.
+pub async fn get_received_offer(
+    sol: &SonsOfLiberty,
+    offer_id: &ContractId,
+) -> Result<OfferedContract> {
+    let contract = sol
+        .dlcdevkit
+        .storage
+        .get_contract(offer_id)
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    match contract {
+        Some(Contract::Offered(offered)) if !offered.is_offer_party => Ok(offered),
+        Some(Contract::Offered(_)) => Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason("Offer was sent by us, not received"),
+        )),
+        Some(_) => Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason("Contract is no longer an open offer"),
+        )),
+        None => Err(Error::CustomError(
+            StatusCode::NOT_FOUND,
+            ErrorDetail::with_reason("Offer not found"),
+        )),
+    }
 }
 
-#[allow(clippy::needless_pass_by_value)]
-fn wallet_error_to_http_error(e: ddk::error::WalletError) -> Error {
-    Error::CustomError(
-        StatusCode::BAD_REQUEST,
-        ErrorDetail::with_reason(e.to_string()),
-    )
+/// Marks an offer as rejected and tells the counterparty over the transport. Works for offers we
+/// received as well as offers we sent and want to withdraw.
+pub async fn reject_offer(sol: &SonsOfLiberty, offered: OfferedContract) -> Result<()> {
+    let contract_id = offered.id;
+    let counter_party = offered.counter_party;
+
+    sol.dlcdevkit
+        .storage
+        .update_contract(&Contract::Rejected(offered))
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    sol.dlcdevkit
+        .transport
+        .send_message(counter_party, Message::Reject(Reject { contract_id }))
+        .await;
+
+    tracing::info!("Rejected offer {}", hex::encode(contract_id));
+    Ok(())
+}
+
+/// State name of a contract, as used in ddk's contract rows.
+pub fn contract_state(contract: &Contract) -> &'static str {
+    ContractState::from(contract).name()
+}
+
+/// Terms of a contract as they were offered. Closed contracts no longer carry them.
+pub fn offered_contract(contract: &Contract) -> Option<&OfferedContract> {
+    match contract {
+        Contract::Offered(offered) | Contract::Rejected(offered) => Some(offered),
+        Contract::Accepted(accepted) => Some(&accepted.offered_contract),
+        Contract::Signed(signed) | Contract::Confirmed(signed) | Contract::Refunded(signed) => {
+            Some(&signed.accepted_contract.offered_contract)
+        }
+        Contract::PreClosed(pre_closed) => Some(
+            &pre_closed
+                .signed_contract
+                .accepted_contract
+                .offered_contract,
+        ),
+        Contract::FailedAccept(failed) => Some(&failed.offered_contract),
+        Contract::FailedSign(failed) => Some(&failed.accepted_contract.offered_contract),
+        Contract::Closed(_) => None,
+    }
+}
+
+/// Event id of the first oracle announcement of a contract.
+pub fn oracle_event_id(contract: &Contract) -> Option<String> {
+    offered_contract(contract)?
+        .contract_info
+        .first()?
+        .oracle_announcements
+        .first()
+        .map(|announcement| announcement.oracle_event.event_id.clone())
+}
+
+/// Records a timeline event for every contract whose state changed since it was last seen.
+/// Returns the number of recorded events.
+pub async fn record_contract_events(db: &DatabaseConnection, sol: &SonsOfLiberty) -> Result<usize> {
+    let contracts = sol.dlcdevkit.storage.get_contracts().await.map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(format!("Failed to get contracts: {e}")),
+        )
+    })?;
+    let latest_states = contract_events::Model::latest_states(db).await?;
+
+    let mut recorded = 0;
+    for contract in contracts {
+        let temporary_id = hex::encode(contract.get_temporary_id());
+        let state = contract_state(&contract);
+        if latest_states.get(&temporary_id).map(String::as_str) == Some(state) {
+            continue;
+        }
+        let id = hex::encode(contract.get_id());
+        contract_events::ActiveModel::record(
+            db,
+            &id,
+            &temporary_id,
+            state,
+            oracle_event_id(&contract),
+        )
+        .await?;
+        recorded += 1;
+    }
+
+    Ok(recorded)
 }
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use ddk_manager::Storage;
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use tokio::sync::broadcast;
+
+use crate::{
+    common::{dlcdevkit, history, webhooks},
+    models::{contract_events, contracts::ContractState},
+    sol::SonsOfLiberty,
+    views::events::{BalanceSats, ContractRef, NodeEvent},
+};
+
+/// Events buffered per subscriber before a slow one starts missing them.
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TxSnapshot {
+    net: i64,
+    block_height: Option<u32>,
+}
+
+/// What the wallet looked like after a sync, compared with the next one to find events. Contract
+/// events come from the recorded timeline instead, see [`publish_contract_events`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    transactions: HashMap<String, TxSnapshot>,
+    balance: BalanceSats,
+}
+
+/// Broadcasts node events to every subscriber of the event stream.
+#[derive(Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<NodeEvent>,
+    last: std::sync::Mutex<Option<Snapshot>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+            last: std::sync::Mutex::new(None),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.sender.subscribe()
+    }
+
+    fn publish(&self, events: &[NodeEvent]) {
+        for event in events {
+            // Sending only fails when nobody is listening.
+            let _ = self.sender.send(event.clone());
+        }
+    }
+
+    /// Publishes what changed since the previous snapshot and returns it. The first snapshot is
+    /// only a baseline, wallet changes made while the server was down are not replayed.
+    pub fn publish_changes(&self, current: Snapshot) -> Vec<NodeEvent> {
+        let mut last = self
+            .last
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let events = last
+            .as_ref()
+            .map(|previous| diff(previous, &current))
+            .unwrap_or_default();
+        *last = Some(current);
+        drop(last);
+
+        self.publish(&events);
+        events
+    }
+}
+
+fn contract_event(
+    state: ContractState,
+    contract_ref: ContractRef,
+    is_offer_party: bool,
+) -> Option<NodeEvent> {
+    match state {
+        ContractState::Offered if !is_offer_party => Some(NodeEvent::OfferReceived(contract_ref)),
+        ContractState::Accepted => Some(NodeEvent::ContractAccepted(contract_ref)),
+        ContractState::Signed => Some(NodeEvent::ContractSigned(contract_ref)),
+        ContractState::Confirmed => Some(NodeEvent::ContractConfirmed(contract_ref)),
+        ContractState::Closed => Some(NodeEvent::ContractClosed(contract_ref)),
+        ContractState::Refunded => Some(NodeEvent::ContractRefunded(contract_ref)),
+        _ => None,
+    }
+}
+
+/// Publishes the contract state changes recorded in the timeline that were not published yet,
+/// and logs their webhook deliveries. Changes recorded while the server was down or while a
+/// previous publish failed go out with the next sync. A failure after the deliveries were logged
+/// delivers the same events again.
+pub async fn publish_contract_events(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+) -> Result<Vec<NodeEvent>> {
+    let recorded = contract_events::Model::unpublished(db).await?;
+    if recorded.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Counterparty and role by temporary id, which stays the same across states.
+    let parties = sol
+        .dlcdevkit
+        .storage
+        .get_contracts()
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(format!("Failed to get contracts: {e}")),
+            )
+        })?
+        .into_iter()
+        .map(|contract| {
+            let is_offer_party = dlcdevkit::offered_contract(&contract)
+                .is_some_and(|offered| offered.is_offer_party);
+            (
+                hex::encode(contract.get_temporary_id()),
+                (contract.get_counter_party_id().to_string(), is_offer_party),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let events = recorded
+        .iter()
+        .filter_map(|event| {
+            let (counter_party, is_offer_party) = parties.get(&event.temporary_contract_id)?;
+            let contract_ref = ContractRef {
+                contract_id: event.contract_id.clone(),
+                counter_party: counter_party.clone(),
+            };
+            contract_event(
+                ContractState::from_name(&event.state)?,
+                contract_ref,
+                *is_offer_party,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    webhooks::record(db, &events).await?;
+    let ids = recorded.iter().map(|event| event.id).collect::<Vec<_>>();
+    contract_events::ActiveModel::mark_published(db, &ids).await?;
+    sol.events.publish(&events);
+    Ok(events)
+}
+
+/// Events that lead from `previous` to `current`: wallet transactions appearing or confirming
+/// and balance changes.
+fn diff(previous: &Snapshot, current: &Snapshot) -> Vec<NodeEvent> {
+    let mut events = Vec::new();
+
+    let mut transactions = current.transactions.iter().collect::<Vec<_>>();
+    transactions.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (txid, tx) in transactions {
+        let previous = previous.transactions.get(txid);
+        if previous.is_none() {
+            events.push(NodeEvent::WalletTxSeen {
+                txid: txid.clone(),
+                net: tx.net,
+            });
+        }
+        let confirmed = tx.block_height.is_some()
+            && previous.is_none_or(|previous| previous.block_height.is_none());
+        if confirmed {
+            events.push(NodeEvent::WalletTxConfirmed {
+                txid: txid.clone(),
+                net: tx.net,
+                block_height: tx.block_height,
+            });
+        }
+    }
+
+    if previous.balance != current.balance {
+        events.push(NodeEvent::BalanceChanged {
+            previous: previous.balance,
+            current: current.balance,
+        });
+    }
+    events
+}
+
+/// Wallet transactions and balance as they are in storage now.
+pub async fn snapshot(db: &DatabaseConnection, sol: &SonsOfLiberty) -> Result<Snapshot> {
+    let internal = |e: String| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(e),
+        )
+    };
+
+    let transactions = history::wallet_history(db, sol)
+        .await?
+        .into_iter()
+        .map(|tx| {
+            let snapshot = TxSnapshot {
+                net: tx.net,
+                block_height: tx.block_height,
+            };
+            (tx.txid, snapshot)
+        })
+        .collect();
+
+    let balance = sol
+        .dlcdevkit
+        .balance()
+        .await
+        .map_err(|e| internal(format!("Failed to get balance: {e}")))?;
+
+    Ok(Snapshot {
+        transactions,
+        balance: BalanceSats {
+            confirmed: balance.confirmed.to_sat(),
+            unconfirmed: (balance.change_unconfirmed + balance.foreign_unconfirmed).to_sat(),
+            contract: balance.contract.to_sat(),
+            contract_pnl: balance.contract_pnl,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(txid: &str, block_height: Option<u32>) -> (String, TxSnapshot) {
+        (
+            txid.to_string(),
+            TxSnapshot {
+                net: 10_000,
+                block_height,
+            },
+        )
+    }
+
+    #[test]
+    fn test_contract_events_by_state() {
+        let contract_ref = || ContractRef {
+            contract_id: "a".to_string(),
+            counter_party: "02aa".to_string(),
+        };
+        let name = |state, is_offer_party| {
+            contract_event(state, contract_ref(), is_offer_party).map(|event| event.name())
+        };
+
+        assert_eq!(name(ContractState::Offered, false), Some("offer_received"));
+        assert_eq!(name(ContractState::Offered, true), None);
+        assert_eq!(name(ContractState::Signed, true), Some("contract_signed"));
+        assert_eq!(name(ContractState::Closed, false), Some("contract_closed"));
+        assert_eq!(name(ContractState::Rejected, false), None);
+    }
+
+    #[test]
+    fn test_diff_wallet_transactions_and_balance() {
+        let previous = Snapshot {
+            transactions: HashMap::from([tx("a", None), tx("b", Some(100))]),
+            ..Snapshot::default()
+        };
+        let current = Snapshot {
+            transactions: HashMap::from([tx("a", Some(101)), tx("b", Some(100)), tx("c", None)]),
+            balance: BalanceSats {
+                confirmed: 10_000,
+                ..BalanceSats::default()
+            },
+            ..Snapshot::default()
+        };
+
+        let names = diff(&previous, &current)
+            .iter()
+            .map(NodeEvent::name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["wallet_tx_confirmed", "wallet_tx_seen", "balance_changed"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_first_snapshot_is_a_baseline() {
+        let bus = EventBus::default();
+        let mut receiver = bus.subscribe();
+        let snapshot = Snapshot {
+            transactions: HashMap::from([tx("a", None)]),
+            ..Snapshot::default()
+        };
+
+        assert!(bus.publish_changes(snapshot.clone()).is_empty());
+        assert!(bus.publish_changes(snapshot).is_empty());
+
+        let confirmed = Snapshot {
+            transactions: HashMap::from([tx("a", Some(1))]),
+            ..Snapshot::default()
+        };
+        assert_eq!(bus.publish_changes(confirmed).len(), 1);
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            NodeEvent::WalletTxConfirmed {
+                txid: "a".to_string(),
+                net: 10_000,
+                block_height: Some(1),
+            }
+        );
+    }
+}
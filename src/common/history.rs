@@ -0,0 +1,429 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use axum::http::StatusCode;
+use bitcoin::{consensus::deserialize, OutPoint, ScriptBuf, Transaction, TxOut, Txid};
+use ddk_manager::{
+    contract::{signed_contract::SignedContract, Contract},
+    Storage,
+};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use miniscript::{Descriptor, DescriptorPublicKey};
+use serde::Deserialize;
+
+use crate::{
+    models::{
+        anchor_tx, block, close_proposals, keychain, tx, txout, wallet_table::find_by_wallet,
+    },
+    sol::SonsOfLiberty,
+    views::wallet::{ContractLink, ContractTxKind, TxDirection, WalletTransaction},
+};
+
+/// Scripts bdk derives past the last revealed index of each keychain.
+const LOOKAHEAD: u32 = 25;
+
+/// Anchor stored by bdk for a confirmed transaction.
+#[derive(Debug, Deserialize)]
+struct Anchor {
+    block_id: BlockId,
+    confirmation_time: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockId {
+    height: u32,
+    hash: String,
+}
+
+/// What a transaction moves in and out of the wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxSummary {
+    pub received: u64,
+    pub sent: u64,
+    pub fee: Option<u64>,
+    pub direction: TxDirection,
+}
+
+impl TxSummary {
+    pub fn net(&self) -> i64 {
+        self.received as i64 - self.sent as i64
+    }
+}
+
+/// Scripts of the wallet's keychains, up to the lookahead past the last revealed index.
+pub fn wallet_scripts(keychains: &[keychain::Model]) -> HashSet<ScriptBuf> {
+    let mut scripts = HashSet::new();
+    for keychain in keychains {
+        let Ok(descriptor) = Descriptor::<DescriptorPublicKey>::from_str(&keychain.descriptor)
+        else {
+            tracing::warn!(
+                "Skipping unparsable descriptor of keychain {}",
+                keychain.keychainkind
+            );
+            continue;
+        };
+        let last_index = keychain
+            .last_revealed
+            .and_then(|index| u32::try_from(index).ok())
+            .unwrap_or(0);
+        for index in 0..=last_index + LOOKAHEAD {
+            if let Ok(derived) = descriptor.at_derivation_index(index) {
+                scripts.insert(derived.script_pubkey());
+            }
+        }
+    }
+    scripts
+}
+
+/// Amounts received and sent by the wallet in `tx`, and its fee when every spent output is known.
+pub fn summarize(
+    tx: &Transaction,
+    scripts: &HashSet<ScriptBuf>,
+    prevouts: &HashMap<OutPoint, TxOut>,
+) -> TxSummary {
+    let received = tx
+        .output
+        .iter()
+        .filter(|txout| scripts.contains(&txout.script_pubkey))
+        .map(|txout| txout.value.to_sat())
+        .sum::<u64>();
+
+    let spent = tx
+        .input
+        .iter()
+        .map(|input| prevouts.get(&input.previous_output))
+        .collect::<Vec<_>>();
+    let sent = spent
+        .iter()
+        .flatten()
+        .filter(|txout| scripts.contains(&txout.script_pubkey))
+        .map(|txout| txout.value.to_sat())
+        .sum::<u64>();
+
+    let output_value = tx
+        .output
+        .iter()
+        .map(|txout| txout.value.to_sat())
+        .sum::<u64>();
+    let fee = spent
+        .iter()
+        .map(|txout| txout.map(|txout| txout.value.to_sat()))
+        .sum::<Option<u64>>()
+        .and_then(|input_value| input_value.checked_sub(output_value));
+
+    let direction = if sent == 0 {
+        TxDirection::Incoming
+    } else if received == output_value
+        && spent
+            .iter()
+            .all(|txout| txout.is_some_and(|txout| scripts.contains(&txout.script_pubkey)))
+    {
+        TxDirection::SelfTransfer
+    } else {
+        TxDirection::Outgoing
+    };
+
+    TxSummary {
+        received,
+        sent,
+        fee,
+        direction,
+    }
+}
+
+fn link(
+    links: &mut HashMap<Txid, ContractLink>,
+    txid: Txid,
+    contract_id: &str,
+    kind: ContractTxKind,
+) {
+    links.insert(
+        txid,
+        ContractLink {
+            contract_id: contract_id.to_string(),
+            kind,
+        },
+    );
+}
+
+fn fund_txid(contract: &SignedContract) -> Txid {
+    contract
+        .accepted_contract
+        .dlc_transactions
+        .fund
+        .compute_txid()
+}
+
+/// Contracts that funded, settled, refunded or cooperatively closed through each transaction.
+pub async fn contract_links(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+) -> Result<HashMap<Txid, ContractLink>> {
+    let contracts = sol.dlcdevkit.storage.get_contracts().await.map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(format!("Failed to get contracts: {e}")),
+        )
+    })?;
+
+    let mut links = HashMap::new();
+    for contract in &contracts {
+        let contract_id = hex::encode(contract.get_id());
+        match contract {
+            Contract::Accepted(accepted) => link(
+                &mut links,
+                accepted.dlc_transactions.fund.compute_txid(),
+                &contract_id,
+                ContractTxKind::Funding,
+            ),
+            Contract::Signed(signed) | Contract::Confirmed(signed) => link(
+                &mut links,
+                fund_txid(signed),
+                &contract_id,
+                ContractTxKind::Funding,
+            ),
+            Contract::PreClosed(pre_closed) => {
+                link(
+                    &mut links,
+                    fund_txid(&pre_closed.signed_contract),
+                    &contract_id,
+                    ContractTxKind::Funding,
+                );
+                link(
+                    &mut links,
+                    pre_closed.signed_cet.compute_txid(),
+                    &contract_id,
+                    ContractTxKind::Cet,
+                );
+            }
+            Contract::Refunded(refunded) => {
+                link(
+                    &mut links,
+                    fund_txid(refunded),
+                    &contract_id,
+                    ContractTxKind::Funding,
+                );
+                link(
+                    &mut links,
+                    refunded
+                        .accepted_contract
+                        .dlc_transactions
+                        .refund
+                        .compute_txid(),
+                    &contract_id,
+                    ContractTxKind::Refund,
+                );
+            }
+            Contract::Closed(closed) => {
+                // Closed contracts only keep the transaction that spent the funding output.
+                if let Some(cet) = &closed.signed_cet {
+                    if let Some(input) = cet.input.first() {
+                        link(
+                            &mut links,
+                            input.previous_output.txid,
+                            &contract_id,
+                            ContractTxKind::Funding,
+                        );
+                    }
+                    link(
+                        &mut links,
+                        cet.compute_txid(),
+                        &contract_id,
+                        ContractTxKind::Cet,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for proposal in close_proposals::Model::list(db).await? {
+        if let Some(txid) = proposal.txid.as_deref().and_then(|txid| txid.parse().ok()) {
+            link(
+                &mut links,
+                txid,
+                &proposal.contract_id,
+                ContractTxKind::Close,
+            );
+        }
+    }
+    Ok(links)
+}
+
+/// Decoded history of the DDK wallet, unconfirmed transactions first, then newest first.
+pub async fn wallet_history(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+) -> Result<Vec<WalletTransaction>> {
+    let wallet_name = &sol.settings.name;
+    let scripts = wallet_scripts(&find_by_wallet::<keychain::Entity>(db, wallet_name).await?);
+
+    let transactions = find_by_wallet::<tx::Entity>(db, wallet_name)
+        .await?
+        .into_iter()
+        .filter_map(|row| {
+            let tx = deserialize::<Transaction>(row.whole_tx.as_deref()?).ok()?;
+            Some((tx, row.last_seen))
+        })
+        .collect::<Vec<_>>();
+
+    let mut prevouts = HashMap::new();
+    for floating in find_by_wallet::<txout::Entity>(db, wallet_name).await? {
+        let (Ok(txid), Ok(vout)) = (floating.txid.parse(), u32::try_from(floating.vout)) else {
+            continue;
+        };
+        prevouts.insert(
+            OutPoint::new(txid, vout),
+            TxOut {
+                value: bitcoin::Amount::from_sat(u64::try_from(floating.value).unwrap_or(0)),
+                script_pubkey: ScriptBuf::from_bytes(floating.script),
+            },
+        );
+    }
+    for (tx, _) in &transactions {
+        let txid = tx.compute_txid();
+        for (vout, txout) in tx.output.iter().enumerate() {
+            prevouts.insert(OutPoint::new(txid, vout as u32), txout.clone());
+        }
+    }
+
+    // Only anchors in blocks of the wallet's local chain count, others were reorged out.
+    let blocks = find_by_wallet::<block::Entity>(db, wallet_name).await?;
+    let tip_height = blocks
+        .iter()
+        .map(|block| u32::try_from(block.height).unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+    let chain = blocks
+        .into_iter()
+        .map(|block| block.hash)
+        .collect::<HashSet<_>>();
+    let mut anchors: HashMap<String, Anchor> = HashMap::new();
+    for row in find_by_wallet::<anchor_tx::Entity>(db, wallet_name).await? {
+        if !chain.contains(&row.block_hash) {
+            continue;
+        }
+        if let Ok(anchor) = serde_json::from_value::<Anchor>(row.anchor) {
+            let replace = anchors
+                .get(&row.txid)
+                .is_none_or(|existing| existing.block_id.height < anchor.block_id.height);
+            if replace {
+                anchors.insert(row.txid, anchor);
+            }
+        }
+    }
+
+    let links = contract_links(db, sol).await?;
+
+    let mut history = transactions
+        .into_iter()
+        .map(|(tx, last_seen)| {
+            let txid = tx.compute_txid();
+            let summary = summarize(&tx, &scripts, &prevouts);
+            let anchor = anchors.get(&txid.to_string());
+            WalletTransaction {
+                txid: txid.to_string(),
+                direction: summary.direction,
+                net: summary.net(),
+                received: summary.received,
+                sent: summary.sent,
+                fee: summary.fee,
+                confirmed: anchor.is_some(),
+                block_height: anchor.map(|anchor| anchor.block_id.height),
+                block_time: anchor.map(|anchor| anchor.confirmation_time),
+                confirmations: anchor.map_or(0, |anchor| {
+                    tip_height.saturating_sub(anchor.block_id.height) + 1
+                }),
+                last_seen,
+                contract: links.get(&txid).cloned(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    history.sort_by(|a, b| {
+        let key = |tx: &WalletTransaction| (tx.block_height.unwrap_or(u32::MAX), tx.last_seen);
+        key(b).cmp(&key(a))
+    });
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute::LockTime, hashes::Hash, transaction::Version, Amount, Sequence, TxIn,
+        WPubkeyHash, Witness,
+    };
+
+    fn script(byte: u8) -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([byte; 20]))
+    }
+
+    fn txout(value: u64, byte: u8) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: script(byte),
+        }
+    }
+
+    fn transaction(inputs: &[OutPoint], outputs: Vec<TxOut>) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs
+                .iter()
+                .map(|outpoint| TxIn {
+                    previous_output: *outpoint,
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: outputs,
+        }
+    }
+
+    fn outpoint(vout: u32) -> OutPoint {
+        OutPoint::new(Txid::all_zeros(), vout)
+    }
+
+    #[test]
+    fn test_incoming_transaction() {
+        let ours = HashSet::from([script(1)]);
+        let tx = transaction(&[outpoint(0)], vec![txout(40_000, 1), txout(9_000, 2)]);
+
+        // The sender's coin is unknown to the wallet, so is the fee.
+        let summary = summarize(&tx, &ours, &HashMap::new());
+        assert_eq!(summary.direction, TxDirection::Incoming);
+        assert_eq!(summary.net(), 40_000);
+        assert_eq!(summary.fee, None);
+    }
+
+    #[test]
+    fn test_outgoing_transaction_includes_fee() {
+        let ours = HashSet::from([script(1)]);
+        let prevouts = HashMap::from([(outpoint(0), txout(50_000, 1))]);
+        let tx = transaction(&[outpoint(0)], vec![txout(30_000, 2), txout(19_000, 1)]);
+
+        let summary = summarize(&tx, &ours, &prevouts);
+        assert_eq!(summary.direction, TxDirection::Outgoing);
+        assert_eq!(summary.fee, Some(1_000));
+        assert_eq!(summary.net(), -31_000);
+    }
+
+    #[test]
+    fn test_consolidation_is_a_self_transfer() {
+        let ours = HashSet::from([script(1)]);
+        let prevouts = HashMap::from([
+            (outpoint(0), txout(20_000, 1)),
+            (outpoint(1), txout(30_000, 1)),
+        ]);
+        let tx = transaction(&[outpoint(0), outpoint(1)], vec![txout(49_500, 1)]);
+
+        let summary = summarize(&tx, &ours, &prevouts);
+        assert_eq!(summary.direction, TxDirection::SelfTransfer);
+        assert_eq!(summary.net(), -500);
+    }
+}
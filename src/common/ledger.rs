@@ -0,0 +1,601 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, NaiveDate, Utc};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use sea_orm::prelude::Decimal;
+
+use crate::{
+    common::{
+        bitcoin_price::{get_bitcoin_price_in, normalize_currency},
+        history,
+    },
+    models::{
+        _entities::contracts::Column,
+        balances, contract_events,
+        contracts::{self, ContractState},
+        prices,
+    },
+    sol::SonsOfLiberty,
+    views::{
+        ledger::{Ledger, LedgerEntry, LedgerKind, LedgerTotals},
+        wallet::{ContractLink, ContractTxKind, TxDirection, WalletTransaction},
+    },
+};
+
+const SETTLED: [ContractState; 2] = [ContractState::Closed, ContractState::Refunded];
+
+/// Furthest a recorded price may be from a transaction to value it.
+const MAX_PRICE_GAP_SECS: i64 = 86_400;
+
+const SATS_PER_BTC: i64 = 100_000_000;
+
+/// Coins acquired together, with what they cost in fiat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Lot {
+    sats: u64,
+    basis: Decimal,
+}
+
+/// Coins held, consumed first in, first out.
+#[derive(Debug, Default)]
+struct Lots(VecDeque<Lot>);
+
+impl Lots {
+    fn acquire(&mut self, sats: u64, basis: Decimal) {
+        if sats > 0 {
+            self.0.push_back(Lot { sats, basis });
+        }
+    }
+
+    /// Removes `sats` from the oldest lots and returns their cost basis. Coins the lots do not
+    /// cover, like ones received before the first price was recorded, have no basis.
+    fn dispose(&mut self, mut sats: u64) -> Decimal {
+        let mut basis = Decimal::ZERO;
+        while sats > 0 {
+            let Some(lot) = self.0.front_mut() else {
+                break;
+            };
+            if lot.sats <= sats {
+                sats -= lot.sats;
+                basis += lot.basis;
+                self.0.pop_front();
+            } else {
+                let part = lot.basis * Decimal::from(sats) / Decimal::from(lot.sats);
+                lot.sats -= sats;
+                lot.basis -= part;
+                basis += part;
+                sats = 0;
+            }
+        }
+        basis
+    }
+}
+
+/// Prices recorded over time, oldest first.
+pub(crate) struct PriceHistory(Vec<(DateTime<Utc>, Decimal)>);
+
+impl PriceHistory {
+    /// Prices in `currency` recorded before `until`, from balance snapshots and price providers.
+    pub(crate) async fn load(
+        db: &DatabaseConnection,
+        currency: &str,
+        until: DateTime<Utc>,
+    ) -> Result<Self> {
+        let mut points = balances::Model::price_history(db, currency, until)
+            .await?
+            .into_iter()
+            .map(|point| (point.created_at.with_timezone(&Utc), point.price))
+            .chain(
+                prices::Model::until(db, currency, until)
+                    .await?
+                    .into_iter()
+                    .map(|price| (price.created_at.with_timezone(&Utc), price.amount)),
+            )
+            .collect::<Vec<_>>();
+        points.sort_by_key(|(at, _)| *at);
+        Ok(Self(points))
+    }
+
+    /// Price recorded closest to `time`, if any is within [`MAX_PRICE_GAP_SECS`].
+    pub(crate) fn at(&self, time: DateTime<Utc>) -> Option<Decimal> {
+        let index = self.0.partition_point(|(at, _)| *at < time);
+        let before = index.checked_sub(1).and_then(|index| self.0.get(index));
+        let after = self.0.get(index);
+        [before, after]
+            .into_iter()
+            .flatten()
+            .map(|(at, price)| ((*at - time).num_seconds().abs(), *price))
+            .filter(|(gap, _)| *gap <= MAX_PRICE_GAP_SECS)
+            .min_by_key(|(gap, _)| *gap)
+            .map(|(_, price)| price)
+    }
+}
+
+fn fiat(sats: u64, price: Decimal) -> Decimal {
+    Decimal::from(sats) * price / Decimal::from(SATS_PER_BTC)
+}
+
+fn bad_request(reason: impl Into<String>) -> Error {
+    Error::CustomError(StatusCode::BAD_REQUEST, ErrorDetail::with_reason(reason))
+}
+
+/// Confirmed wallet activity of a calendar year (UTC) with fiat values and realized gains.
+///
+/// Cost basis is tracked first in, first out over the whole wallet history, so disposals early
+/// in the year consume coins acquired in earlier years. Contract collateral is still held while
+/// a contract is open; settlements realize the contract's PnL as a gain or a loss. Settlements
+/// that paid the wallet nothing still get an entry, so a lost contract realizes its loss.
+pub async fn build_ledger(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    year: i32,
+    currency: &str,
+) -> Result<Ledger> {
+    let currency = normalize_currency(currency).map_err(|e| bad_request(e.to_string()))?;
+    let (Some(start), Some(end)) = (
+        NaiveDate::from_ymd_opt(year, 1, 1),
+        year.checked_add(1)
+            .and_then(|next| NaiveDate::from_ymd_opt(next, 1, 1)),
+    ) else {
+        return Err(bad_request(format!("{year} is not a valid year")));
+    };
+    let start = start.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let end = end.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+
+    let mut transactions = history::wallet_history(db, sol)
+        .await?
+        .into_iter()
+        .filter_map(|tx| {
+            let time = DateTime::from_timestamp(i64::try_from(tx.block_time?).ok()?, 0)?;
+            (time < end).then_some((time, tx))
+        })
+        .collect::<Vec<_>>();
+
+    let settled = settled_contracts(db, sol, &transactions).await?;
+    let pnl = settled
+        .iter()
+        .filter_map(|contract| Some((contract.id.clone(), contract.pnl?)))
+        .collect::<HashMap<_, _>>();
+    transactions.extend(
+        settled
+            .into_iter()
+            .filter_map(|contract| contract.unpaid)
+            .filter(|(time, _)| *time < end),
+    );
+    transactions.sort_by_key(|(time, _)| *time);
+
+    let mut prices = PriceHistory::load(db, &currency, end).await?;
+    let now = Utc::now();
+    let stale = prices
+        .0
+        .last()
+        .is_none_or(|(at, _)| (now - *at).num_seconds() > MAX_PRICE_GAP_SECS);
+    let recent = transactions
+        .last()
+        .is_some_and(|(time, _)| (now - *time).num_seconds() <= MAX_PRICE_GAP_SECS);
+    if stale && recent {
+        match get_bitcoin_price_in(db, &sol.settings, Some(sol), &currency).await {
+            Ok(price) => {
+                if let Some(amount) = Decimal::from_f64_retain(price.amount) {
+                    prices.0.push((now, amount));
+                }
+            }
+            Err(e) => tracing::warn!("Ledger without a current {currency} price: {e}"),
+        }
+    }
+
+    let mut lots = Lots::default();
+    let mut entries = Vec::new();
+    for (time, tx) in transactions {
+        let entry = entry(&mut lots, time, tx, prices.at(time), &pnl);
+        if time >= start {
+            entries.push(entry);
+        }
+    }
+
+    Ok(Ledger {
+        year,
+        currency,
+        totals: totals(&entries),
+        entries,
+    })
+}
+
+/// A settled contract, with the entry standing in for its settlement when no wallet transaction
+/// received a payout from it.
+struct SettledContract {
+    id: String,
+    pnl: Option<i64>,
+    unpaid: Option<(DateTime<Utc>, WalletTransaction)>,
+}
+
+async fn settled_contracts(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    transactions: &[(DateTime<Utc>, WalletTransaction)],
+) -> Result<Vec<SettledContract>> {
+    let rows = contracts::Entity::find()
+        .filter(Column::State.is_in(SETTLED.map(ContractState::as_i16)))
+        .all(db)
+        .await?;
+    let paid = transactions
+        .iter()
+        .filter_map(|(_, tx)| tx.contract.as_ref())
+        .filter(|link| link.kind != ContractTxKind::Funding)
+        .map(|link| link.contract_id.as_str())
+        .collect::<HashSet<_>>();
+    let unpaid_ids = rows
+        .iter()
+        .filter(|row| !paid.contains(row.id.as_str()))
+        .map(|row| row.id.clone())
+        .collect::<Vec<_>>();
+
+    let (events, txids) = if unpaid_ids.is_empty() {
+        (HashMap::new(), HashMap::new())
+    } else {
+        let txids = history::contract_links(db, sol)
+            .await?
+            .into_iter()
+            .filter(|(_, link)| link.kind != ContractTxKind::Funding)
+            .map(|(txid, link)| (link.contract_id, txid.to_string()))
+            .collect::<HashMap<_, _>>();
+        (
+            contract_events::Model::by_contract(db, &unpaid_ids).await?,
+            txids,
+        )
+    };
+    let settled_names = SETTLED.map(ContractState::name);
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let settled_at = events
+                .get(&row.id)
+                .and_then(|events| {
+                    events
+                        .iter()
+                        .find(|event| settled_names.contains(&event.state.as_str()))
+                })
+                .map(|event| event.created_at.with_timezone(&Utc));
+            let refunded = row.state == ContractState::Refunded.as_i16();
+            let unpaid = settled_at.map(|time| {
+                (
+                    time,
+                    unpaid_settlement(
+                        &row.id,
+                        txids.get(&row.id).cloned().unwrap_or_default(),
+                        time,
+                        refunded,
+                    ),
+                )
+            });
+            SettledContract {
+                id: row.id,
+                pnl: row.pnl,
+                unpaid,
+            }
+        })
+        .collect())
+}
+
+/// Settlement of a contract that paid the wallet nothing, like a CET giving the counterparty
+/// everything. The wallet balance does not change, the contract's PnL is the lost collateral.
+fn unpaid_settlement(
+    contract_id: &str,
+    txid: String,
+    time: DateTime<Utc>,
+    refunded: bool,
+) -> WalletTransaction {
+    WalletTransaction {
+        txid,
+        direction: TxDirection::SelfTransfer,
+        net: 0,
+        received: 0,
+        sent: 0,
+        fee: None,
+        confirmed: true,
+        block_height: None,
+        block_time: u64::try_from(time.timestamp()).ok(),
+        confirmations: 0,
+        last_seen: None,
+        contract: Some(ContractLink {
+            contract_id: contract_id.to_string(),
+            kind: if refunded {
+                ContractTxKind::Refund
+            } else {
+                ContractTxKind::Cet
+            },
+        }),
+    }
+}
+
+/// Values a transaction and updates the lots it acquires or disposes of. Settlements whose
+/// contract PnL is unknown keep their fiat columns empty rather than realizing nothing.
+fn entry(
+    lots: &mut Lots,
+    time: DateTime<Utc>,
+    tx: WalletTransaction,
+    price: Option<Decimal>,
+    pnl: &HashMap<String, i64>,
+) -> LedgerEntry {
+    let value = |sats: u64| price.map(|price| fiat(sats, price));
+    let fee = tx.fee.unwrap_or_default();
+    let contract = tx.contract.as_ref();
+
+    let (kind, pnl_sats) = match contract.map(|link| link.kind) {
+        Some(ContractTxKind::Funding) => (LedgerKind::Funding, None),
+        Some(ContractTxKind::Cet | ContractTxKind::Close) => (
+            LedgerKind::Settlement,
+            contract.and_then(|link| pnl.get(&link.contract_id).copied()),
+        ),
+        Some(ContractTxKind::Refund) => (
+            LedgerKind::Refund,
+            contract.and_then(|link| pnl.get(&link.contract_id).copied()),
+        ),
+        None => match tx.direction {
+            TxDirection::Incoming => (LedgerKind::Deposit, None),
+            TxDirection::Outgoing => (LedgerKind::Withdrawal, None),
+            TxDirection::SelfTransfer => (LedgerKind::Transfer, None),
+        },
+    };
+
+    // (sats acquired, sats disposed, proceeds of the disposal)
+    let (acquired, disposed, proceeds) = match kind {
+        LedgerKind::Deposit => (tx.net.unsigned_abs(), 0, None),
+        LedgerKind::Withdrawal => {
+            let sent = tx.net.unsigned_abs();
+            (0, sent, value(sent.saturating_sub(fee)))
+        }
+        LedgerKind::Transfer | LedgerKind::Funding => (0, fee, Some(Decimal::ZERO)),
+        LedgerKind::Settlement | LedgerKind::Refund => match pnl_sats {
+            Some(gain) if gain > 0 => (gain.unsigned_abs(), 0, None),
+            Some(loss) => (0, loss.unsigned_abs(), Some(Decimal::ZERO)),
+            None => (0, 0, None),
+        },
+    };
+
+    let income = value(acquired);
+    lots.acquire(acquired, income.unwrap_or_default());
+    let cost_basis = (disposed > 0).then(|| lots.dispose(disposed));
+
+    // Contract profits count as income at their value when received.
+    let (proceeds, cost_basis) = match kind {
+        LedgerKind::Settlement | LedgerKind::Refund if acquired > 0 => {
+            (income, income.map(|_| Decimal::ZERO))
+        }
+        _ => (proceeds, cost_basis),
+    };
+    let realized_gain = match (proceeds, cost_basis) {
+        (Some(proceeds), Some(basis)) => Some(proceeds - basis),
+        _ => None,
+    };
+
+    let round = |value: Option<Decimal>| value.map(|value| value.round_dp(2));
+    LedgerEntry {
+        time,
+        kind,
+        contract_id: contract.map(|link| link.contract_id.clone()),
+        amount_sats: tx.net,
+        fee_sats: tx.fee,
+        pnl_sats,
+        bitcoin_price: price,
+        fiat_value: round(value(tx.net.unsigned_abs()).map(|value| {
+            if tx.net < 0 {
+                -value
+            } else {
+                value
+            }
+        })),
+        proceeds: round(proceeds),
+        cost_basis: round(cost_basis),
+        realized_gain: round(realized_gain),
+        txid: tx.txid,
+    }
+}
+
+fn totals(entries: &[LedgerEntry]) -> LedgerTotals {
+    let mut totals = LedgerTotals::default();
+    for entry in entries {
+        totals.proceeds += entry.proceeds.unwrap_or_default();
+        totals.cost_basis += entry.cost_basis.unwrap_or_default();
+        totals.realized_gain += entry.realized_gain.unwrap_or_default();
+        if entry.bitcoin_price.is_none() {
+            totals.unpriced += 1;
+        }
+        if matches!(entry.kind, LedgerKind::Settlement | LedgerKind::Refund)
+            && entry.pnl_sats.is_none()
+        {
+            totals.missing_pnl += 1;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn tx(net: i64, fee: Option<u64>, direction: TxDirection) -> WalletTransaction {
+        WalletTransaction {
+            txid: "aa".repeat(32),
+            direction,
+            net,
+            received: u64::try_from(net.max(0)).unwrap(),
+            sent: net.min(0).unsigned_abs(),
+            fee,
+            confirmed: true,
+            block_height: Some(1),
+            block_time: Some(0),
+            confirmations: 1,
+            last_seen: None,
+            contract: None,
+        }
+    }
+
+    #[test]
+    fn test_lots_are_consumed_first_in_first_out() {
+        let mut lots = Lots::default();
+        lots.acquire(100, Decimal::from(10));
+        lots.acquire(100, Decimal::from(30));
+
+        assert_eq!(lots.dispose(150), Decimal::from(25));
+        assert_eq!(lots.dispose(50), Decimal::from(15));
+        // Nothing left, the rest has no basis.
+        assert_eq!(lots.dispose(10), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_price_at_nearest_point_within_gap() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let prices = PriceHistory(vec![
+            (t0, Decimal::from(100)),
+            (t0 + Duration::hours(10), Decimal::from(200)),
+        ]);
+
+        assert_eq!(prices.at(t0 + Duration::hours(4)), Some(Decimal::from(100)));
+        assert_eq!(prices.at(t0 + Duration::hours(6)), Some(Decimal::from(200)));
+        assert_eq!(
+            prices.at(t0 - Duration::hours(23)),
+            Some(Decimal::from(100))
+        );
+        assert_eq!(prices.at(t0 + Duration::days(3)), None);
+    }
+
+    #[test]
+    fn test_withdrawal_realizes_gain_over_deposit_basis() {
+        let mut lots = Lots::default();
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let pnl = HashMap::new();
+
+        let deposit = entry(
+            &mut lots,
+            time,
+            tx(1_000_000, None, TxDirection::Incoming),
+            Some(Decimal::from(50_000)),
+            &pnl,
+        );
+        assert_eq!(deposit.kind, LedgerKind::Deposit);
+        assert_eq!(deposit.fiat_value, Some(Decimal::from(500)));
+        assert_eq!(deposit.realized_gain, None);
+
+        let withdrawal = entry(
+            &mut lots,
+            time,
+            tx(-500_000, Some(1_000), TxDirection::Outgoing),
+            Some(Decimal::from(100_000)),
+            &pnl,
+        );
+        assert_eq!(withdrawal.kind, LedgerKind::Withdrawal);
+        assert_eq!(withdrawal.proceeds, Some(Decimal::from(499)));
+        assert_eq!(withdrawal.cost_basis, Some(Decimal::from(250)));
+        assert_eq!(withdrawal.realized_gain, Some(Decimal::from(249)));
+    }
+
+    #[test]
+    fn test_settlement_realizes_contract_pnl() {
+        let mut lots = Lots::default();
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let pnl = HashMap::from([("won".to_string(), 20_000), ("lost".to_string(), -20_000)]);
+        lots.acquire(100_000, Decimal::from(50));
+
+        let settle = |lots: &mut Lots, contract_id: &str| {
+            let mut cet = tx(80_000, None, TxDirection::Incoming);
+            cet.contract = Some(ContractLink {
+                contract_id: contract_id.to_string(),
+                kind: ContractTxKind::Cet,
+            });
+            entry(lots, time, cet, Some(Decimal::from(100_000)), &pnl)
+        };
+
+        let won = settle(&mut lots, "won");
+        assert_eq!(won.kind, LedgerKind::Settlement);
+        assert_eq!(won.pnl_sats, Some(20_000));
+        assert_eq!(won.realized_gain, Some(Decimal::from(20)));
+
+        let lost = settle(&mut lots, "lost");
+        assert_eq!(lost.proceeds, Some(Decimal::ZERO));
+        assert_eq!(lost.cost_basis, Some(Decimal::from(10)));
+        assert_eq!(lost.realized_gain, Some(Decimal::from(-10)));
+    }
+
+    #[test]
+    fn test_unpaid_settlement_realizes_lost_collateral() {
+        let mut lots = Lots::default();
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let pnl = HashMap::from([("lost".to_string(), -100_000)]);
+        lots.acquire(100_000, Decimal::from(50));
+
+        let lost = entry(
+            &mut lots,
+            time,
+            unpaid_settlement("lost", "bb".repeat(32), time, false),
+            Some(Decimal::from(100_000)),
+            &pnl,
+        );
+        assert_eq!(lost.kind, LedgerKind::Settlement);
+        assert_eq!(lost.amount_sats, 0);
+        assert_eq!(lost.pnl_sats, Some(-100_000));
+        assert_eq!(lost.proceeds, Some(Decimal::ZERO));
+        assert_eq!(lost.cost_basis, Some(Decimal::from(50)));
+        assert_eq!(lost.realized_gain, Some(Decimal::from(-50)));
+    }
+
+    #[test]
+    fn test_settlement_without_pnl_is_flagged() {
+        let mut lots = Lots::default();
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        lots.acquire(100_000, Decimal::from(50));
+
+        let unknown = entry(
+            &mut lots,
+            time,
+            unpaid_settlement("unknown", String::new(), time, true),
+            Some(Decimal::from(100_000)),
+            &HashMap::new(),
+        );
+        assert_eq!(unknown.kind, LedgerKind::Refund);
+        assert_eq!(unknown.pnl_sats, None);
+        assert_eq!(unknown.realized_gain, None);
+        // The collateral lots are left for later disposals.
+        assert_eq!(lots.dispose(100_000), Decimal::from(50));
+
+        let totals = totals(std::slice::from_ref(&unknown));
+        assert_eq!(totals.missing_pnl, 1);
+        assert_eq!(totals.realized_gain, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut lots = Lots::default();
+        let mut entry = entry(
+            &mut lots,
+            time,
+            tx(1_000, None, TxDirection::Incoming),
+            None,
+            &HashMap::new(),
+        );
+        entry.contract_id = Some("a,\"b\"".to_string());
+        let ledger = Ledger {
+            year: 2023,
+            currency: "USD".to_string(),
+            totals: totals(std::slice::from_ref(&entry)),
+            entries: vec![entry],
+        };
+
+        let csv = ledger.to_csv();
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("time,kind,txid,"));
+        assert_eq!(
+            lines.next().unwrap(),
+            format!(
+                "2023-11-14T22:13:20+00:00,deposit,{},\"a,\"\"b\"\"\",1000,,,,,,,",
+                "aa".repeat(32)
+            )
+        );
+        assert_eq!(ledger.totals.unpriced, 1);
+    }
+}
@@ -1,5 +1,17 @@
 pub mod bitcoin_price;
+pub mod close;
+pub mod coin_control;
 pub mod dlcdevkit;
+pub mod events;
+pub mod history;
+pub mod ledger;
 pub mod market;
 pub mod nostr;
+pub mod offer_expiry;
+pub mod pnl;
+pub mod preview;
+pub mod seed_encryption;
 pub mod settings;
+pub mod sync;
+pub mod wallet;
+pub mod webhooks;
@@ -1,16 +1,19 @@
 use std::time::Duration;
 
 use nostr::{
-    event::{Event, EventBuilder, Kind, Tag, TagKind},
+    event::{Event, EventBuilder, EventId, Kind, Tag, TagKind},
     filter::{Alphabet, Filter, SingleLetterTag},
     key::{Keys, PublicKey, SecretKey},
     nips::nip01::Metadata,
+    types::Timestamp,
     util::JsonUtil,
 };
 use nostr_sdk::Client;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::views::close::CloseMessage;
+
 #[derive(Error, Debug)]
 pub enum TradeCounterpartyError {
     #[error("Invalid bytes for nostr secret key: {0}")]
@@ -21,8 +24,13 @@ pub enum TradeCounterpartyError {
     NostrEvent(#[from] nostr::event::Error),
     #[error("Nostr profile does not exist: {0}")]
     NostrProfileDoesNotExist(PublicKey),
+    #[error("Invalid nostr message: {0}")]
+    InvalidMessage(#[from] serde_json::Error),
 }
 
+/// Gift wraps are backdated by up to two days to hide when they were sent.
+const GIFT_WRAP_BACKDATE_SECS: u64 = 2 * 24 * 60 * 60;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NostrCounterparty {
     pub pubkey: PublicKey,
@@ -163,6 +171,58 @@ impl Nostr {
 
         Ok(profiles)
     }
+
+    /// Sends a close message to the counterparty of a contract as a NIP-17 private direct
+    /// message from our transport key.
+    pub async fn send_close_message(
+        &self,
+        counterparty: PublicKey,
+        message: &CloseMessage,
+    ) -> Result<EventId, TradeCounterpartyError> {
+        let output = self
+            .nostr_client
+            .send_private_msg(counterparty, serde_json::to_string(message)?, [])
+            .await?;
+        Ok(output.val)
+    }
+
+    /// Fetches the close messages sent to us since `since`, with the sender of each message.
+    /// Direct messages that are not close messages are skipped.
+    pub async fn get_close_messages(
+        &self,
+        since: Timestamp,
+    ) -> Result<Vec<(PublicKey, CloseMessage)>, TradeCounterpartyError> {
+        let filter = Filter::new()
+            .kind(Kind::GiftWrap)
+            .pubkey(self.keys.public_key)
+            .since(since - GIFT_WRAP_BACKDATE_SECS);
+        let events = self
+            .nostr_client
+            .fetch_events(filter, Duration::from_secs(5))
+            .await?;
+
+        let mut messages = Vec::new();
+        for event in events.iter() {
+            let unwrapped = match self.nostr_client.unwrap_gift_wrap(event).await {
+                Ok(unwrapped) => unwrapped,
+                Err(e) => {
+                    tracing::warn!("Could not unwrap direct message: {e}");
+                    continue;
+                }
+            };
+            // The seal is signed by the sender, the rumor inside it is not.
+            if unwrapped.rumor.kind != Kind::PrivateDirectMessage
+                || unwrapped.rumor.pubkey != unwrapped.sender
+            {
+                continue;
+            }
+            if let Ok(message) = serde_json::from_str::<CloseMessage>(&unwrapped.rumor.content) {
+                messages.push((unwrapped.sender, message));
+            }
+        }
+
+        Ok(messages)
+    }
 }
 
 fn trade_counterparty_filter() -> Filter {
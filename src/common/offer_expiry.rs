@@ -0,0 +1,192 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use ddk_manager::{contract::offered_contract::OfferedContract, Storage};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+
+use crate::{
+    common::dlcdevkit,
+    models::{contracts, offer_expiries},
+    sol::SonsOfLiberty,
+};
+
+/// Earliest maturity of the oracle events an offer depends on.
+pub fn earliest_maturity(offered: &OfferedContract) -> Option<u32> {
+    offered
+        .contract_info
+        .iter()
+        .flat_map(|info| &info.oracle_announcements)
+        .map(|announcement| announcement.oracle_event.event_maturity_epoch)
+        .min()
+}
+
+/// Whether the oracle event behind an offer matures within `min_time_to_maturity_secs` of `now`.
+pub fn matures_too_soon(
+    offered: &OfferedContract,
+    now: DateTime<Utc>,
+    min_time_to_maturity_secs: u64,
+) -> bool {
+    maturity_too_soon(earliest_maturity(offered), now, min_time_to_maturity_secs)
+}
+
+fn maturity_too_soon(
+    maturity: Option<u32>,
+    now: DateTime<Utc>,
+    min_time_to_maturity_secs: u64,
+) -> bool {
+    maturity.is_some_and(|maturity| {
+        i64::from(maturity) - now.timestamp()
+            < i64::try_from(min_time_to_maturity_secs).unwrap_or(i64::MAX)
+    })
+}
+
+/// Whether the expiry task rejects an offer. Offers we sent are left to the counterparty.
+fn should_expire(
+    is_offer_party: bool,
+    expiry: &offer_expiries::Model,
+    maturity: Option<u32>,
+    now: DateTime<Utc>,
+    min_time_to_maturity_secs: u64,
+) -> bool {
+    !is_offer_party
+        && (expiry.is_expired(now) || maturity_too_soon(maturity, now, min_time_to_maturity_secs))
+}
+
+/// Expiry of an offer, with its TTL counted from when the offer was stored.
+async fn track_offer(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    contract_id: &str,
+    now: DateTime<Utc>,
+) -> Result<offer_expiries::Model> {
+    let offered_at = contracts::Model::find_row(db, contract_id)
+        .await?
+        .map_or(now, |row| row.created_at.with_timezone(&Utc));
+    Ok(offer_expiries::Model::find_or_track(
+        db,
+        contract_id,
+        sol.settings.offer_ttl_secs,
+        offered_at,
+    )
+    .await?)
+}
+
+/// Refuses offers that have outlived the offer TTL or whose oracle event matures too soon.
+pub async fn ensure_offer_acceptable(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    offered: &OfferedContract,
+) -> Result<()> {
+    let now = Utc::now();
+    let contract_id = hex::encode(offered.id);
+    let expiry = track_offer(db, sol, &contract_id, now).await?;
+
+    if expiry.is_expired(now) {
+        return Err(Error::CustomError(
+            StatusCode::GONE,
+            ErrorDetail::with_reason(format!("Offer {contract_id} has expired")),
+        ));
+    }
+
+    if matures_too_soon(offered, now, sol.settings.min_time_to_maturity_secs) {
+        return Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason(format!(
+                "Oracle event of offer {contract_id} matures in less than {} seconds",
+                sol.settings.min_time_to_maturity_secs
+            )),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Rejects every received offer that has outlived the offer TTL or matures too soon. Returns the
+/// ids of the expired offers. An offer that fails to expire is logged and retried on the next run.
+pub async fn expire_offers(db: &DatabaseConnection, sol: &SonsOfLiberty) -> Result<Vec<String>> {
+    let offers = sol
+        .dlcdevkit
+        .storage
+        .get_contract_offers()
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(format!("Failed to get offers: {e}")),
+            )
+        })?;
+
+    let now = Utc::now();
+    let mut expired = Vec::new();
+    for offered in offers.into_iter().filter(|offered| !offered.is_offer_party) {
+        let contract_id = hex::encode(offered.id);
+        match expire_offer(db, sol, offered, now).await {
+            Ok(true) => expired.push(contract_id),
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to expire offer {}: {}", contract_id, e),
+        }
+    }
+
+    Ok(expired)
+}
+
+/// Rejects a single offer if it should expire. Returns whether it was rejected.
+async fn expire_offer(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    offered: OfferedContract,
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    let contract_id = hex::encode(offered.id);
+    let expiry = track_offer(db, sol, &contract_id, now).await?;
+    if !should_expire(
+        offered.is_offer_party,
+        &expiry,
+        earliest_maturity(&offered),
+        now,
+        sol.settings.min_time_to_maturity_secs,
+    ) {
+        return Ok(false);
+    }
+
+    tracing::info!("Expiring offer {}", contract_id);
+    dlcdevkit::reject_offer(sol, offered).await?;
+    offer_expiries::ActiveModel::mark_expired(db, expiry, now).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn expiry(expires_at: DateTime<Utc>) -> offer_expiries::Model {
+        offer_expiries::Model {
+            created_at: expires_at.into(),
+            updated_at: expires_at.into(),
+            id: 1,
+            contract_id: "offer".to_string(),
+            expires_at: expires_at.into(),
+            expired_at: None,
+        }
+    }
+
+    #[test]
+    fn test_only_stale_received_offers_expire() {
+        let now = Utc::now();
+        let stale = expiry(now - Duration::seconds(1));
+        let fresh = expiry(now + Duration::hours(1));
+        let far = u32::try_from((now + Duration::days(7)).timestamp()).ok();
+        let soon = u32::try_from((now + Duration::minutes(5)).timestamp()).ok();
+
+        // Received offers past their TTL or maturing too soon expire.
+        assert!(should_expire(false, &stale, far, now, 3_600));
+        assert!(should_expire(false, &fresh, soon, now, 3_600));
+        assert!(!should_expire(false, &fresh, far, now, 3_600));
+        assert!(!should_expire(false, &fresh, None, now, 3_600));
+
+        // Offers we sent are never expired by us.
+        assert!(!should_expire(true, &stale, far, now, 3_600));
+        assert!(!should_expire(true, &fresh, soon, now, 3_600));
+    }
+}
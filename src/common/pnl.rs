@@ -0,0 +1,546 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::http::StatusCode;
+use bitcoin::ScriptBuf;
+use ddk_manager::{
+    contract::{signed_contract::SignedContract, Contract},
+    Oracle, Storage,
+};
+use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use sea_orm::prelude::Decimal;
+
+use crate::{
+    common::{
+        bitcoin_price::{
+            attested_value, get_bitcoin_price_in, is_btc_usd, normalize_currency, DEFAULT_CURRENCY,
+        },
+        dlcdevkit::offered_contract,
+        ledger::PriceHistory,
+        preview::{preview_descriptor, PayoutRow},
+    },
+    models::{
+        _entities::contracts::Column,
+        contract_events,
+        contracts::{self, ContractState},
+    },
+    sol::SonsOfLiberty,
+    views::balances::{
+        ContractPnl, MarkSource, OracleEventType, PnlGroup, PnlQuery, PnlReport, PnlTotals,
+    },
+};
+
+const SETTLED: [ContractState; 2] = [ContractState::Closed, ContractState::Refunded];
+const ACTIVE: [ContractState; 3] = [
+    ContractState::Signed,
+    ContractState::Confirmed,
+    ContractState::PreClosed,
+];
+
+const SATS_PER_BTC: i64 = 100_000_000;
+
+/// An outcome a contract can be valued at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Enum(String),
+    Numeric(i64),
+}
+
+/// Oracle answers shared by every contract of a report, so each event is asked for once.
+#[derive(Default)]
+struct OracleLookups {
+    event_types: HashMap<String, OracleEventType>,
+    outcomes: HashMap<String, Option<Outcome>>,
+}
+
+/// Realized PnL of settled contracts and a mark-to-market estimate for active ones.
+pub async fn pnl_report(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    query: &PnlQuery,
+) -> Result<PnlReport> {
+    let currency = normalize_currency(query.currency.as_deref().unwrap_or(DEFAULT_CURRENCY))
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    let mut select = contracts::Entity::find().filter(
+        Column::State.is_in(
+            SETTLED
+                .iter()
+                .chain(&ACTIVE)
+                .map(|state| state.as_i16())
+                .collect::<Vec<_>>(),
+        ),
+    );
+    if let Some(counterparty) = &query.counterparty {
+        select = select.filter(Column::CounterParty.eq(counterparty.as_str()));
+    }
+    let rows = select.all(db).await?;
+
+    let ids = rows.iter().map(|row| row.id.clone()).collect::<Vec<_>>();
+    let events = contract_events::Model::by_contract(db, &ids).await?;
+    let settled_names = SETTLED.map(ContractState::name);
+
+    let stored = sol
+        .dlcdevkit
+        .storage
+        .get_contracts()
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?
+        .into_iter()
+        .map(|contract| (hex::encode(contract.get_id()), contract))
+        .collect::<HashMap<_, _>>();
+
+    // Oracles attest numeric events in USD, whatever currency the report is in.
+    let usd_price = price_or_warn(db, sol, DEFAULT_CURRENCY).await;
+    let fiat_price = if currency == DEFAULT_CURRENCY {
+        usd_price
+    } else {
+        price_or_warn(db, sol, &currency).await
+    };
+    let price_history = PriceHistory::load(db, &currency, chrono::Utc::now()).await?;
+
+    let mut lookups = OracleLookups::default();
+    let mut report = Vec::new();
+    for row in rows {
+        let Some(state) = ContractState::from_i16(row.state) else {
+            continue;
+        };
+        let settled = SETTLED.contains(&state);
+        let timeline = events.get(&row.id).map(Vec::as_slice).unwrap_or_default();
+        let settled_at = timeline
+            .iter()
+            .find(|event| settled_names.contains(&event.state.as_str()))
+            .map(|event| event.created_at);
+
+        let at = if settled {
+            settled_at
+        } else {
+            Some(row.created_at)
+        };
+        let in_range = query
+            .from
+            .is_none_or(|from| at.is_some_and(|at| at >= from))
+            && query.to.is_none_or(|to| at.is_some_and(|at| at <= to));
+        if !in_range {
+            continue;
+        }
+
+        let contract = stored.get(&row.id);
+        let oracle_event_id = timeline
+            .iter()
+            .rev()
+            .find_map(|event| event.oracle_event_id.clone());
+        let event_type = match contract
+            .and_then(offered_contract)
+            .and_then(|offered| offered.contract_info.first())
+            .and_then(|info| info.oracle_announcements.first())
+        {
+            Some(announcement) => event_type(announcement),
+            None => match &oracle_event_id {
+                Some(event_id) => fetch_event_type(sol, &mut lookups, event_id).await,
+                None => OracleEventType::Unknown,
+            },
+        };
+        if query.event_type.is_some_and(|wanted| wanted != event_type) {
+            continue;
+        }
+
+        let (unrealized_sats, mark) = match contract {
+            Some(contract) if !settled => mark_to_market(sol, &mut lookups, contract, usd_price)
+                .await
+                .map_or((None, None), |(pnl, mark)| (Some(pnl), Some(mark))),
+            _ => (None, None),
+        };
+        let realized_sats = settled.then(|| row.pnl.unwrap_or_default());
+        let realized_fiat = realized_sats.and_then(|sats| {
+            let price = price_history.at(settled_at?.with_timezone(&chrono::Utc))?;
+            Some(fiat(sats, price))
+        });
+        report.push(ContractPnl {
+            id: row.id,
+            state: state.name().to_string(),
+            counter_party: row.counter_party,
+            event_type,
+            oracle_event_id,
+            collateral: if row.is_offer_party {
+                row.offer_collateral
+            } else {
+                row.accept_collateral
+            },
+            created_at: Some(row.created_at),
+            settled_at,
+            realized_sats,
+            realized_fiat,
+            unrealized_sats,
+            mark,
+        });
+    }
+
+    let group = |key: fn(&ContractPnl) -> String| {
+        let mut groups = BTreeMap::<String, Vec<&ContractPnl>>::new();
+        for contract in &report {
+            groups.entry(key(contract)).or_default().push(contract);
+        }
+        groups
+            .into_iter()
+            .map(|(key, contracts)| PnlGroup {
+                key,
+                totals: totals(contracts, fiat_price),
+            })
+            .collect::<Vec<_>>()
+    };
+    let by_counterparty = group(|contract| contract.counter_party.clone());
+    let by_event_type = group(|contract| contract.event_type.name().to_string());
+
+    Ok(PnlReport {
+        currency,
+        bitcoin_price: fiat_price,
+        totals: totals(&report, fiat_price),
+        by_counterparty,
+        by_event_type,
+        contracts: report,
+    })
+}
+
+async fn price_or_warn(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    currency: &str,
+) -> Option<f64> {
+    match get_bitcoin_price_in(db, &sol.settings, Some(sol), currency).await {
+        Ok(price) => Some(price.amount),
+        Err(e) => {
+            tracing::warn!("PnL report without a {currency} price: {e}");
+            None
+        }
+    }
+}
+
+fn event_type(announcement: &OracleAnnouncement) -> OracleEventType {
+    match announcement.oracle_event.event_descriptor {
+        EventDescriptor::EnumEvent(_) => OracleEventType::Enum,
+        EventDescriptor::DigitDecompositionEvent(_) => OracleEventType::Numeric,
+    }
+}
+
+/// Asks our oracles for the announcement of an event whose contract no longer carries it.
+async fn fetch_event_type(
+    sol: &SonsOfLiberty,
+    lookups: &mut OracleLookups,
+    event_id: &str,
+) -> OracleEventType {
+    if let Some(event_type) = lookups.event_types.get(event_id) {
+        return *event_type;
+    }
+    let mut fetched = OracleEventType::Unknown;
+    for oracle in &sol.oracles {
+        if let Ok(announcement) = oracle.get_announcement(event_id).await {
+            fetched = event_type(&announcement);
+            break;
+        }
+    }
+    lookups.event_types.insert(event_id.to_string(), fetched);
+    fetched
+}
+
+/// Our payout minus our collateral, from the CET once one was broadcast, else from the attested
+/// outcome, else from the current price for BTC/USD contracts.
+async fn mark_to_market(
+    sol: &SonsOfLiberty,
+    lookups: &mut OracleLookups,
+    contract: &Contract,
+    usd_price: Option<f64>,
+) -> Option<(i64, MarkSource)> {
+    let signed = match contract {
+        Contract::Signed(signed) | Contract::Confirmed(signed) => signed,
+        Contract::PreClosed(pre_closed) => {
+            let signed = &pre_closed.signed_contract;
+            let script = payout_script(signed);
+            let payout = pre_closed
+                .signed_cet
+                .output
+                .iter()
+                .filter(|output| output.script_pubkey == *script)
+                .map(|output| output.value.to_sat())
+                .sum::<u64>();
+            return Some((
+                payout as i64 - our_collateral(signed) as i64,
+                MarkSource::Cet,
+            ));
+        }
+        _ => return None,
+    };
+
+    let offered = &signed.accepted_contract.offered_contract;
+    let info = offered.contract_info.first()?;
+    let accept_collateral = offered.total_collateral - offered.offer_params.collateral;
+    let payouts = preview_descriptor(
+        &info.contract_descriptor,
+        offered.offer_params.collateral,
+        accept_collateral,
+    )
+    .ok()?
+    .payouts;
+
+    let (outcome, mark) = match attested_outcome(sol, lookups, &info.oracle_announcements).await {
+        Some(outcome) => (outcome, MarkSource::Attestation),
+        None => {
+            let EventDescriptor::DigitDecompositionEvent(descriptor) = &info
+                .oracle_announcements
+                .first()?
+                .oracle_event
+                .event_descriptor
+            else {
+                return None;
+            };
+            if !is_btc_usd(descriptor) {
+                return None;
+            }
+            let outcome = usd_price? / 10f64.powi(descriptor.precision);
+            (
+                Outcome::Numeric(outcome.round() as i64),
+                MarkSource::PriceFeed,
+            )
+        }
+    };
+    let payout = payout_at(&payouts, &outcome, offered.is_offer_party)?;
+    Some((payout as i64 - our_collateral(signed) as i64, mark))
+}
+
+/// The outcome the first reachable oracle attested, once the event matured.
+async fn attested_outcome(
+    sol: &SonsOfLiberty,
+    lookups: &mut OracleLookups,
+    announcements: &[OracleAnnouncement],
+) -> Option<Outcome> {
+    let now = chrono::Utc::now().timestamp();
+    for announcement in announcements {
+        if i64::from(announcement.oracle_event.event_maturity_epoch) > now {
+            continue;
+        }
+        let event_id = &announcement.oracle_event.event_id;
+        if !lookups.outcomes.contains_key(event_id) {
+            let outcome = fetch_outcome(sol, announcement).await;
+            lookups.outcomes.insert(event_id.clone(), outcome);
+        }
+        if let Some(outcome) = &lookups.outcomes[event_id] {
+            return Some(outcome.clone());
+        }
+    }
+    None
+}
+
+async fn fetch_outcome(sol: &SonsOfLiberty, announcement: &OracleAnnouncement) -> Option<Outcome> {
+    let oracle = sol
+        .oracles
+        .iter()
+        .find(|oracle| oracle.get_public_key() == announcement.oracle_public_key)?;
+    let attestation = oracle
+        .get_attestation(&announcement.oracle_event.event_id)
+        .await
+        .ok()?;
+    match &announcement.oracle_event.event_descriptor {
+        EventDescriptor::EnumEvent(_) => attestation.outcomes.first().cloned().map(Outcome::Enum),
+        EventDescriptor::DigitDecompositionEvent(descriptor) => {
+            attested_value(descriptor, &attestation.outcomes).map(Outcome::Numeric)
+        }
+    }
+}
+
+fn payout_script(signed: &SignedContract) -> &ScriptBuf {
+    let accepted = &signed.accepted_contract;
+    if accepted.offered_contract.is_offer_party {
+        &accepted.offered_contract.offer_params.payout_script_pubkey
+    } else {
+        &accepted.accept_params.payout_script_pubkey
+    }
+}
+
+fn our_collateral(signed: &SignedContract) -> u64 {
+    let offered = &signed.accepted_contract.offered_contract;
+    if offered.is_offer_party {
+        offered.offer_params.collateral
+    } else {
+        offered.total_collateral - offered.offer_params.collateral
+    }
+}
+
+/// Our payout at an outcome. Numeric outcomes outside the payout curve take the payout of the
+/// nearest end of the curve.
+fn payout_at(payouts: &[PayoutRow], outcome: &Outcome, is_offer_party: bool) -> Option<u64> {
+    let row = match outcome {
+        Outcome::Enum(attested) => payouts
+            .iter()
+            .find(|row| matches!(row, PayoutRow::Enum { outcome, .. } if outcome == attested))?,
+        Outcome::Numeric(value) => {
+            let value = (*value).max(0).unsigned_abs();
+            payouts
+                .iter()
+                .find(|row| {
+                    matches!(row, PayoutRow::Range { start, end, .. } if (*start..=*end).contains(&value))
+                })
+                .or_else(|| match payouts.first()? {
+                    first @ PayoutRow::Range { start, .. } if value < *start => Some(first),
+                    _ => payouts.last(),
+                })?
+        }
+    };
+    let (PayoutRow::Enum { offer, accept, .. } | PayoutRow::Range { offer, accept, .. }) = row;
+    Some(if is_offer_party { *offer } else { *accept })
+}
+
+fn fiat(sats: i64, price: Decimal) -> Decimal {
+    (Decimal::from(sats) * price / Decimal::from(SATS_PER_BTC)).round_dp(2)
+}
+
+/// Sums a group of contracts. Realized fiat is only known when every settled contract had a
+/// price recorded when it settled.
+fn totals<'a>(
+    contracts: impl IntoIterator<Item = &'a ContractPnl>,
+    fiat_price: Option<f64>,
+) -> PnlTotals {
+    let mut totals = PnlTotals::default();
+    let mut realized_fiat = Some(Decimal::ZERO);
+    for contract in contracts {
+        totals.contracts += 1;
+        totals.realized_sats += contract.realized_sats.unwrap_or_default();
+        totals.unrealized_sats += contract.unrealized_sats.unwrap_or_default();
+        if contract.realized_sats.is_some() {
+            realized_fiat = realized_fiat
+                .zip(contract.realized_fiat)
+                .map(|(total, fiat)| total + fiat);
+        }
+        if contract.realized_sats.is_none() && contract.unrealized_sats.is_none() {
+            totals.unpriced += 1;
+        }
+    }
+    totals.total_sats = totals.realized_sats + totals.unrealized_sats;
+
+    totals.realized_fiat = realized_fiat;
+    totals.unrealized_fiat = fiat_price
+        .and_then(Decimal::from_f64_retain)
+        .map(|price| fiat(totals.unrealized_sats, price));
+    totals.total_fiat = totals
+        .realized_fiat
+        .zip(totals.unrealized_fiat)
+        .map(|(realized, unrealized)| realized + unrealized);
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges() -> Vec<PayoutRow> {
+        vec![
+            PayoutRow::Range {
+                start: 0,
+                end: 49_999,
+                offer: 0,
+                accept: 200_000,
+            },
+            PayoutRow::Range {
+                start: 50_000,
+                end: 99_999,
+                offer: 100_000,
+                accept: 100_000,
+            },
+            PayoutRow::Range {
+                start: 100_000,
+                end: 1_048_575,
+                offer: 200_000,
+                accept: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_payout_at_outcome() {
+        let payouts = ranges();
+        assert_eq!(
+            payout_at(&payouts, &Outcome::Numeric(60_000), true),
+            Some(100_000)
+        );
+        assert_eq!(
+            payout_at(&payouts, &Outcome::Numeric(-5), false),
+            Some(200_000)
+        );
+        assert_eq!(
+            payout_at(&payouts, &Outcome::Numeric(5_000_000), true),
+            Some(200_000)
+        );
+
+        let enums = vec![
+            PayoutRow::Enum {
+                outcome: "yes".to_string(),
+                offer: 150,
+                accept: 50,
+            },
+            PayoutRow::Enum {
+                outcome: "no".to_string(),
+                offer: 0,
+                accept: 200,
+            },
+        ];
+        assert_eq!(
+            payout_at(&enums, &Outcome::Enum("no".to_string()), false),
+            Some(200)
+        );
+        assert_eq!(
+            payout_at(&enums, &Outcome::Enum("maybe".to_string()), true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_totals() {
+        let contract = |realized_sats: Option<i64>, unrealized_sats| ContractPnl {
+            id: String::new(),
+            state: String::new(),
+            counter_party: String::new(),
+            event_type: OracleEventType::Numeric,
+            oracle_event_id: None,
+            collateral: 100_000,
+            created_at: None,
+            settled_at: None,
+            realized_sats,
+            // Settled when the price was half the current one.
+            realized_fiat: realized_sats.map(|sats| fiat(sats, Decimal::from(50_000))),
+            unrealized_sats,
+            mark: None,
+        };
+        let contracts = [
+            contract(Some(50_000), None),
+            contract(None, Some(-20_000)),
+            contract(None, None),
+        ];
+
+        let priced = totals(&contracts, Some(100_000.0));
+        assert_eq!(priced.contracts, 3);
+        assert_eq!(priced.unpriced, 1);
+        assert_eq!(priced.total_sats, 30_000);
+        assert_eq!(priced.realized_fiat, Some(Decimal::from(25)));
+        assert_eq!(priced.unrealized_fiat, Some(Decimal::from(-20)));
+        assert_eq!(priced.total_fiat, Some(Decimal::from(5)));
+        assert_eq!(totals(&contracts, None).unrealized_fiat, None);
+        assert_eq!(
+            totals(&contracts, None).realized_fiat,
+            Some(Decimal::from(25))
+        );
+
+        // A settlement without a recorded price leaves the realized fiat unknown.
+        let mut unpriced = contract(Some(10_000), None);
+        unpriced.realized_fiat = None;
+        assert_eq!(totals(&[unpriced], Some(100_000.0)).realized_fiat, None);
+    }
+}
@@ -0,0 +1,162 @@
+use axum::http::StatusCode;
+use ddk_manager::{contract::ContractDescriptor, payout_curve::RoundingIntervals};
+use dlc_trie::digit_decomposition::group_by_ignoring_digits;
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use serde::{Deserialize, Serialize};
+
+/// Binary digits assumed for numeric oracle events when previewing without an announcement.
+pub const PREVIEW_NB_DIGITS: usize = 20;
+
+/// A row of the payout table. Enum contracts pay per outcome, numeric contracts per outcome range.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PayoutRow {
+    Enum {
+        outcome: String,
+        offer: u64,
+        accept: u64,
+    },
+    Range {
+        start: u64,
+        end: u64,
+        offer: u64,
+        accept: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartyRisk {
+    pub collateral: u64,
+    pub max_gain: i64,
+    pub max_loss: i64,
+}
+
+/// Terms of a contract as they would be offered, computed without contacting the oracle or the
+/// counterparty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractPreview {
+    pub total_collateral: u64,
+    pub payouts: Vec<PayoutRow>,
+    pub rounding_intervals: Option<RoundingIntervals>,
+    /// One CET is created per payout row.
+    pub cet_count: usize,
+    /// Adaptor signatures needed from a single oracle. Numeric contracts need one per digit
+    /// prefix covering each payout range.
+    pub adaptor_signature_estimate: usize,
+    pub offer_party: PartyRisk,
+    pub accept_party: PartyRisk,
+}
+
+/// Builds the payout table and risk profile of a contract descriptor.
+pub fn preview_descriptor(
+    descriptor: &ContractDescriptor,
+    offer_collateral: u64,
+    accept_collateral: u64,
+) -> Result<ContractPreview> {
+    let total_collateral = offer_collateral
+        .checked_add(accept_collateral)
+        .ok_or_else(|| {
+            Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail {
+                    error: Some("Total collateral overflows".to_string()),
+                    description: Some("Invalid contract collateral".to_string()),
+                },
+            )
+        })?;
+
+    let (payouts, rounding_intervals, adaptor_signature_estimate) = match descriptor {
+        ContractDescriptor::Enum(enum_descriptor) => {
+            let payouts = enum_descriptor
+                .outcome_payouts
+                .iter()
+                .map(|outcome_payout| PayoutRow::Enum {
+                    outcome: outcome_payout.outcome.clone(),
+                    offer: outcome_payout.payout.offer,
+                    accept: outcome_payout.payout.accept,
+                })
+                .collect::<Vec<_>>();
+            let count = payouts.len();
+            (payouts, None, count)
+        }
+        ContractDescriptor::Numerical(numerical) => {
+            let range_payouts = numerical
+                .payout_function
+                .to_range_payouts(total_collateral, &numerical.rounding_intervals)
+                .map_err(|e| {
+                    Error::CustomError(
+                        StatusCode::BAD_REQUEST,
+                        ErrorDetail {
+                            error: Some(e.to_string()),
+                            description: Some("Failed to compute payout ranges".to_string()),
+                        },
+                    )
+                })?;
+
+            let base = numerical.oracle_numeric_infos.base;
+            let nb_digits = numerical
+                .oracle_numeric_infos
+                .nb_digits
+                .first()
+                .copied()
+                .unwrap_or(PREVIEW_NB_DIGITS);
+
+            let adaptor_signatures = range_payouts
+                .iter()
+                .map(|range| {
+                    group_by_ignoring_digits(
+                        range.start,
+                        range.start + range.count - 1,
+                        base,
+                        nb_digits,
+                    )
+                    .len()
+                })
+                .sum();
+
+            let payouts = range_payouts
+                .iter()
+                .map(|range| PayoutRow::Range {
+                    start: range.start as u64,
+                    end: (range.start + range.count - 1) as u64,
+                    offer: range.payout.offer,
+                    accept: range.payout.accept,
+                })
+                .collect::<Vec<_>>();
+
+            (
+                payouts,
+                Some(numerical.rounding_intervals.clone()),
+                adaptor_signatures,
+            )
+        }
+    };
+
+    let offer_payouts = payouts.iter().map(|row| match row {
+        PayoutRow::Enum { offer, .. } | PayoutRow::Range { offer, .. } => *offer,
+    });
+    let accept_payouts = payouts.iter().map(|row| match row {
+        PayoutRow::Enum { accept, .. } | PayoutRow::Range { accept, .. } => *accept,
+    });
+
+    Ok(ContractPreview {
+        total_collateral,
+        cet_count: payouts.len(),
+        adaptor_signature_estimate,
+        offer_party: party_risk(offer_collateral, offer_payouts),
+        accept_party: party_risk(accept_collateral, accept_payouts),
+        payouts,
+        rounding_intervals,
+    })
+}
+
+fn party_risk(collateral: u64, payouts: impl Iterator<Item = u64> + Clone) -> PartyRisk {
+    let max_payout = payouts.clone().max().unwrap_or(collateral);
+    let min_payout = payouts.min().unwrap_or(collateral);
+    let sats = |value: u64| i64::try_from(value).unwrap_or(i64::MAX);
+    PartyRisk {
+        collateral,
+        max_gain: sats(max_payout).saturating_sub(sats(collateral)),
+        max_loss: sats(collateral).saturating_sub(sats(min_payout)).max(0),
+    }
+}
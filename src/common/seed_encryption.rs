@@ -0,0 +1,181 @@
+use std::{fmt, path::Path};
+
+use argon2::Argon2;
+use bitcoin::key::rand::{thread_rng, Fill};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::settings::Settings;
+
+/// Format of an encrypted seed: version, argon2 salt, nonce, then the sealed entropy.
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const SEED_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+pub const ENCRYPTED_SEED_LEN: usize = 1 + SALT_LEN + NONCE_LEN + SEED_LEN + TAG_LEN;
+
+#[derive(Error, Debug)]
+pub enum SeedEncryptionError {
+    #[error("Set either seed_passphrase or seed_key_file, not both")]
+    AmbiguousKey,
+    #[error("Failed to read seed key file {0}: {1}")]
+    KeyFile(String, std::io::Error),
+    #[error("Seed key file {0} is empty")]
+    EmptyKeyFile(String),
+    #[error("Failed to derive seed key: {0}")]
+    KeyDerivation(String),
+    #[error("Failed to generate randomness: {0}")]
+    Random(String),
+    #[error("Failed to decrypt seed, the passphrase or key file is wrong")]
+    WrongKey,
+    #[error("Encrypted seed is malformed")]
+    Malformed,
+}
+
+/// A secret read from the configuration that must never show up in logs.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+/// Key material the seed is encrypted with, from a passphrase or the contents of a key file.
+pub struct SeedKey(Vec<u8>);
+
+impl SeedKey {
+    pub fn from_passphrase(passphrase: &str) -> Self {
+        Self(passphrase.as_bytes().to_vec())
+    }
+
+    pub fn from_key_file(path: &Path) -> Result<Self, SeedEncryptionError> {
+        let display = path.display().to_string();
+        let mut contents =
+            std::fs::read(path).map_err(|e| SeedEncryptionError::KeyFile(display.clone(), e))?;
+        while contents.last().is_some_and(u8::is_ascii_whitespace) {
+            contents.pop();
+        }
+        if contents.is_empty() {
+            return Err(SeedEncryptionError::EmptyKeyFile(display));
+        }
+        Ok(Self(contents))
+    }
+
+    /// The key configured at boot, if any.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, SeedEncryptionError> {
+        let passphrase = settings
+            .seed_passphrase
+            .as_ref()
+            .map(Secret::expose)
+            .filter(|passphrase| !passphrase.is_empty());
+        let key_file = settings
+            .seed_key_file
+            .as_deref()
+            .filter(|path| !path.is_empty());
+        match (passphrase, key_file) {
+            (Some(_), Some(_)) => Err(SeedEncryptionError::AmbiguousKey),
+            (Some(passphrase), None) => Ok(Some(Self::from_passphrase(passphrase))),
+            (None, Some(path)) => Self::from_key_file(Path::new(path)).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Same as [`SeedKey::from_settings`], for callers working with loco errors.
+    pub fn configured(settings: &Settings) -> loco_rs::Result<Option<Self>> {
+        Self::from_settings(settings).map_err(|e| loco_rs::Error::string(e.to_string().as_str()))
+    }
+
+    fn cipher(&self, salt: &[u8]) -> Result<ChaCha20Poly1305, SeedEncryptionError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(&self.0, salt, &mut key)
+            .map_err(|e| SeedEncryptionError::KeyDerivation(e.to_string()))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    pub fn encrypt(&self, entropy: &[u8; SEED_LEN]) -> Result<Vec<u8>, SeedEncryptionError> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        salt.try_fill(&mut thread_rng())
+            .and_then(|()| nonce.try_fill(&mut thread_rng()))
+            .map_err(|e| SeedEncryptionError::Random(e.to_string()))?;
+
+        let ciphertext = self
+            .cipher(&salt)?
+            .encrypt(Nonce::from_slice(&nonce), entropy.as_slice())
+            .map_err(|_| SeedEncryptionError::Malformed)?;
+
+        let mut sealed = Vec::with_capacity(ENCRYPTED_SEED_LEN);
+        sealed.push(VERSION);
+        sealed.extend_from_slice(&salt);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn decrypt(&self, sealed: &[u8]) -> Result<[u8; SEED_LEN], SeedEncryptionError> {
+        if sealed.len() != ENCRYPTED_SEED_LEN || sealed[0] != VERSION {
+            return Err(SeedEncryptionError::Malformed);
+        }
+        let (salt, rest) = sealed[1..].split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let entropy = self
+            .cipher(salt)?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| SeedEncryptionError::WrongKey)?;
+        entropy
+            .try_into()
+            .map_err(|_| SeedEncryptionError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_round_trip() {
+        let key = SeedKey::from_passphrase("correct horse battery staple");
+        let sealed = key.encrypt(&[7; 32]).unwrap();
+        assert_eq!(sealed.len(), ENCRYPTED_SEED_LEN);
+        assert_eq!(key.decrypt(&sealed).unwrap(), [7; 32]);
+
+        // Every encryption uses a fresh salt and nonce.
+        assert_ne!(key.encrypt(&[7; 32]).unwrap(), sealed);
+    }
+
+    #[test]
+    fn test_wrong_key_is_reported() {
+        let sealed = SeedKey::from_passphrase("right").encrypt(&[1; 32]).unwrap();
+        assert!(matches!(
+            SeedKey::from_passphrase("wrong").decrypt(&sealed),
+            Err(SeedEncryptionError::WrongKey)
+        ));
+        assert!(matches!(
+            SeedKey::from_passphrase("right").decrypt(&sealed[..40]),
+            Err(SeedEncryptionError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_secret_is_not_logged() {
+        let secret: Secret = serde_json::from_str("\"hunter2\"").unwrap();
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+    }
+}
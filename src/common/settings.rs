@@ -1,10 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use super::{bitcoin_price::PriceSettings, seed_encryption::Secret, webhooks::WebhookSettings};
+
 // TODO
 // policies
 // start up peers
-// wallet sync time?
-// periodic sync time?
 // ip address fence range
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -17,6 +17,39 @@ pub struct Settings {
     pub name: String,
     #[serde(default = "default_nostr_relay")]
     pub nostr_relay: String,
+    /// Oracles queried alongside `oracle_host` when creating threshold contracts. DDK itself only
+    /// knows `oracle_host`, so contracts with more than one announcement are closed by the sync
+    /// with attestations from these oracles, and only announcements of configured oracles count
+    /// towards a contract's threshold.
+    #[serde(default)]
+    pub oracle_hosts: Vec<String>,
+    /// Number of oracle attestations required to close a contract.
+    #[serde(default = "default_oracle_threshold")]
+    pub oracle_threshold: u16,
+    /// Seconds an offer can be accepted after we first see it.
+    #[serde(default = "default_offer_ttl_secs")]
+    pub offer_ttl_secs: u64,
+    /// Offers whose oracle event matures sooner than this are expired and cannot be accepted.
+    #[serde(default = "default_min_time_to_maturity_secs")]
+    pub min_time_to_maturity_secs: u64,
+    /// Passphrase the node seed is encrypted with. Mutually exclusive with `seed_key_file`.
+    #[serde(default)]
+    pub seed_passphrase: Option<Secret>,
+    /// File holding the key the node seed is encrypted with.
+    #[serde(default)]
+    pub seed_key_file: Option<String>,
+    /// Currencies balance snapshots are valued in, as ISO 4217 codes.
+    #[serde(default = "default_fiat_currencies")]
+    pub fiat_currencies: Vec<String>,
+    /// Seconds between background syncs of the manager and wallet, 0 disables the loop.
+    #[serde(default = "default_sync_interval_secs")]
+    pub sync_interval_secs: u64,
+    /// Where the BTC price comes from and how long it is cached.
+    #[serde(default)]
+    pub price: PriceSettings,
+    /// Attempts, backoff and timeout of webhook deliveries.
+    #[serde(default)]
+    pub webhooks: WebhookSettings,
 }
 
 fn default_network() -> String {
@@ -27,6 +60,26 @@ fn default_nostr_relay() -> String {
     "wss://nostr.dlcdevkit.com".to_string()
 }
 
+fn default_fiat_currencies() -> Vec<String> {
+    vec!["USD".to_string()]
+}
+
+fn default_oracle_threshold() -> u16 {
+    1
+}
+
+fn default_sync_interval_secs() -> u64 {
+    60
+}
+
+fn default_offer_ttl_secs() -> u64 {
+    60 * 60 * 24
+}
+
+fn default_min_time_to_maturity_secs() -> u64 {
+    60 * 60
+}
+
 impl Settings {
     pub fn from_json(value: &serde_json::Value) -> loco_rs::Result<Self> {
         serde_json::from_value(value.clone())
@@ -0,0 +1,242 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use tokio::{sync::Mutex, time::MissedTickBehavior};
+
+use crate::{
+    common::{
+        dlcdevkit::{self, close_with_oracle_attestations},
+        events, webhooks,
+    },
+    models::block,
+    sol::SonsOfLiberty,
+    views::sync::{SyncError, SyncStatus, SyncTrigger},
+};
+
+/// Errors kept in the sync status.
+const MAX_ERRORS: usize = 20;
+
+/// Serializes syncs of the manager and wallet and keeps track of how they went.
+#[derive(Debug)]
+pub struct Syncer {
+    running: Mutex<()>,
+    status: std::sync::Mutex<SyncStatus>,
+}
+
+impl Syncer {
+    pub fn new(interval_secs: u64) -> Self {
+        Self {
+            running: Mutex::new(()),
+            status: std::sync::Mutex::new(SyncStatus {
+                interval_secs,
+                ..SyncStatus::default()
+            }),
+        }
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        let mut status = self.update(|status| status.clone());
+        status.running = self.running.try_lock().is_err();
+        status
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut SyncStatus) -> T) -> T {
+        // A panic while holding the lock leaves the status readable, it is only bookkeeping.
+        let mut status = self
+            .status
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        f(&mut status)
+    }
+
+    fn started(&self, at: DateTime<Utc>) {
+        self.update(|status| status.last_started = Some(at));
+    }
+
+    fn succeeded(&self, at: DateTime<Utc>, tip_height: Option<u32>) {
+        self.update(|status| {
+            status.last_success = Some(at);
+            if tip_height.is_some() {
+                status.tip_height = tip_height;
+            }
+        });
+    }
+
+    fn failed(&self, error: SyncError) {
+        self.update(|status| {
+            status.last_failure = Some(error.at);
+            status.errors.insert(0, error);
+            status.errors.truncate(MAX_ERRORS);
+        });
+    }
+}
+
+/// Checks the manager for contract updates, closes contracts with enough attestations, records
+/// contract events and syncs the wallet, then publishes the recorded contract events and the
+/// wallet changes as node events and logs their webhook deliveries. Returns `None` without
+/// syncing when a sync is already running, otherwise the ids of the contracts closed.
+pub async fn try_sync(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    trigger: SyncTrigger,
+) -> Option<Result<Vec<String>>> {
+    let _running = sol.sync.running.try_lock().ok()?;
+    sol.sync.started(Utc::now());
+
+    Some(match steps(db, sol).await {
+        Ok((closed, tip_height)) => {
+            sol.sync.succeeded(Utc::now(), tip_height);
+            if let Err(e) = events::publish_contract_events(db, sol).await {
+                tracing::error!("Failed to publish contract events: {e}");
+            }
+            match events::snapshot(db, sol).await {
+                Ok(snapshot) => {
+                    let events = sol.events.publish_changes(snapshot);
+                    if let Err(e) = webhooks::record(db, &events).await {
+                        tracing::error!("Failed to log webhook deliveries: {e}");
+                    }
+                }
+                Err(e) => tracing::warn!("Skipping node events of this sync: {e}"),
+            }
+            Ok(closed)
+        }
+        Err((step, e)) => {
+            tracing::error!("Error syncing {step}: {e:?}");
+            sol.sync.failed(SyncError {
+                at: Utc::now(),
+                trigger,
+                step: step.to_string(),
+                message: e.to_string(),
+            });
+            Err(e)
+        }
+    })
+}
+
+/// Like [`try_sync`], answering with a conflict when a sync is already running.
+pub async fn sync_now(db: &DatabaseConnection, sol: &SonsOfLiberty) -> Result<Vec<String>> {
+    try_sync(db, sol, SyncTrigger::Manual)
+        .await
+        .unwrap_or_else(|| {
+            Err(Error::CustomError(
+                StatusCode::CONFLICT,
+                ErrorDetail::with_reason("A sync is already running"),
+            ))
+        })
+}
+
+async fn steps(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+) -> std::result::Result<(Vec<String>, Option<u32>), (&'static str, Error)> {
+    let internal = |e: String| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(e),
+        )
+    };
+
+    // Multi-oracle contracts are closed with the attestations of every configured oracle before
+    // DDK's check, which only asks its own oracle, gets to them.
+    let closed = close_with_oracle_attestations(sol)
+        .await
+        .map_err(|e| ("attestations", e))?;
+    if !closed.is_empty() {
+        tracing::info!("Closed multi-oracle contracts: {:?}", closed);
+    }
+
+    sol.dlcdevkit
+        .manager
+        .periodic_check(false)
+        .await
+        .map_err(|e| ("manager", internal(e.to_string())))?;
+
+    dlcdevkit::record_contract_events(db, sol)
+        .await
+        .map_err(|e| ("contract events", e))?;
+
+    sol.dlcdevkit
+        .wallet
+        .sync()
+        .await
+        .map_err(|e| ("wallet", internal(e.to_string())))?;
+
+    let tip_height = block::Entity::tip_height(db, &sol.settings.name)
+        .await
+        .map_err(|e| ("tip height", Error::from(e)))?;
+    Ok((closed, tip_height))
+}
+
+/// Syncs every `sync_interval_secs` in the background. Ticks that find a manual sync running
+/// are skipped.
+pub fn spawn_sync_loop(db: DatabaseConnection, sol: Arc<SonsOfLiberty>) {
+    let interval_secs = sol.settings.sync_interval_secs;
+    if interval_secs == 0 {
+        tracing::info!("Background sync is disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if try_sync(&db, &sol, SyncTrigger::Interval).await.is_none() {
+                tracing::debug!("Skipping background sync, a sync is already running");
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(at: i64) -> SyncError {
+        SyncError {
+            at: DateTime::from_timestamp(at, 0).unwrap(),
+            trigger: SyncTrigger::Interval,
+            step: "wallet".to_string(),
+            message: "esplora is down".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_status_keeps_newest_errors() {
+        let syncer = Syncer::new(60);
+        for at in 0..30 {
+            syncer.failed(error(at));
+        }
+
+        let status = syncer.status();
+        assert_eq!(status.interval_secs, 60);
+        assert_eq!(status.errors.len(), MAX_ERRORS);
+        assert_eq!(status.errors[0], error(29));
+        assert_eq!(status.last_failure, Some(error(29).at));
+        assert_eq!(status.last_success, None);
+    }
+
+    #[test]
+    fn test_success_keeps_last_known_tip() {
+        let syncer = Syncer::new(60);
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        syncer.succeeded(at, Some(100));
+        syncer.succeeded(at, None);
+
+        let status = syncer.status();
+        assert_eq!(status.tip_height, Some(100));
+        assert_eq!(status.last_success, Some(at));
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_running_sync() {
+        let syncer = Syncer::new(0);
+        assert!(!syncer.status().running);
+
+        let _running = syncer.running.lock().await;
+        assert!(syncer.status().running);
+        assert!(syncer.running.try_lock().is_err());
+    }
+}
@@ -0,0 +1,903 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    str::FromStr,
+};
+
+use axum::http::StatusCode;
+use bitcoin::{
+    absolute::LockTime,
+    bip32::KeySource,
+    consensus::encode::{deserialize_hex, serialize_hex},
+    hashes::Hash,
+    secp256k1::{self, Message, Secp256k1},
+    sighash::SighashCache,
+    transaction::Version,
+    Address, Amount, CompressedPublicKey, Network, OutPoint, Psbt, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid, Witness,
+};
+use ddk::wallet::LocalOutput;
+use ddk_manager::{contract::Contract, Storage, Wallet};
+use dlc_messages::FundingInput;
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use miniscript::{Descriptor, DescriptorPublicKey, ForEachKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    models::{keychain, psbt_reservations, utxo_labels, wallet_table::find_by_wallet},
+    sol::SonsOfLiberty,
+};
+
+/// Transaction version and locktime, input and output counts, rounded up.
+const TX_OVERHEAD_VBYTES: u64 = 11;
+/// A P2WPKH input spent by the DDK wallet.
+pub const INPUT_VBYTES: u64 = 68;
+/// Change goes to a P2WPKH address of the DDK wallet.
+const CHANGE_OUTPUT_VBYTES: u64 = 31;
+/// Change below this value is left to the miners.
+pub const DUST_LIMIT: u64 = 546;
+
+#[derive(Error, Debug)]
+pub enum WalletSendError {
+    #[error("Insufficient funds: {available} sats available, {required} sats required")]
+    InsufficientFunds { available: u64, required: u64 },
+    #[error("Amount of {0} sats is below the dust limit")]
+    BelowDust(u64),
+    #[error("No spendable coins")]
+    NoCoins,
+    #[error("Input {0} is not a P2WPKH input with its witness UTXO")]
+    UnsupportedInput(usize),
+    #[error("Input {0} has no signature matching its script")]
+    UnsignedInput(usize),
+    #[error("Input {0} has a signature that does not sign the transaction")]
+    InvalidSignature(usize),
+    #[error("Failed to extract transaction: {0}")]
+    Extract(String),
+    #[error("Amount or fee overflows")]
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SendAmount {
+    Sats(u64),
+    /// Everything in the selected coins, minus the fee.
+    Max,
+}
+
+/// A wallet output that can be spent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coin {
+    pub outpoint: OutPoint,
+    pub txout: TxOut,
+    /// Index of the key paying to the coin in its keychain.
+    pub derivation_index: u32,
+}
+
+impl From<LocalOutput> for Coin {
+    fn from(utxo: LocalOutput) -> Self {
+        Self {
+            outpoint: utxo.outpoint,
+            txout: utxo.txout,
+            derivation_index: utxo.derivation_index,
+        }
+    }
+}
+
+/// Inputs and outputs of a transaction before it is signed.
+#[derive(Debug, Clone)]
+pub struct TxPlan {
+    pub inputs: Vec<Coin>,
+    pub outputs: Vec<TxOut>,
+    pub amount: u64,
+    pub fee: u64,
+    pub vsize: u64,
+    pub change: Option<u64>,
+}
+
+/// Fee summary returned to the caller before and after broadcasting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeePreview {
+    pub amount: u64,
+    pub fee: u64,
+    pub fee_rate: u64,
+    pub vsize: u64,
+    pub change: Option<u64>,
+    pub inputs: Vec<OutPoint>,
+}
+
+impl TxPlan {
+    /// Pays the change to `script_pubkey` instead of the script the plan was made with. Change
+    /// outputs of the DDK wallet all have the same size, so the fee stays the same.
+    pub fn set_change_script_pubkey(&mut self, script_pubkey: ScriptBuf) {
+        if self.change.is_some() {
+            if let Some(change) = self.outputs.last_mut() {
+                change.script_pubkey = script_pubkey;
+            }
+        }
+    }
+
+    pub fn preview(&self, fee_rate: u64) -> FeePreview {
+        FeePreview {
+            amount: self.amount,
+            fee: self.fee,
+            fee_rate,
+            vsize: self.vsize,
+            change: self.change,
+            inputs: self.inputs.iter().map(|utxo| utxo.outpoint).collect(),
+        }
+    }
+}
+
+fn output_vbytes(script_pubkey: &ScriptBuf) -> u64 {
+    // Value, script length and script.
+    8 + 1 + script_pubkey.len() as u64
+}
+
+fn vsize(inputs: usize, output_vbytes: u64) -> u64 {
+    TX_OVERHEAD_VBYTES + INPUT_VBYTES * inputs as u64 + output_vbytes
+}
+
+fn tx_fee(vsize: u64, fee_rate: u64) -> Result<u64, WalletSendError> {
+    vsize.checked_mul(fee_rate).ok_or(WalletSendError::Overflow)
+}
+
+fn total_value(utxos: &[Coin]) -> Result<u64, WalletSendError> {
+    utxos.iter().try_fold(0u64, |total, utxo| {
+        total
+            .checked_add(utxo.txout.value.to_sat())
+            .ok_or(WalletSendError::Overflow)
+    })
+}
+
+/// Selects coins paying `amount` to `script_pubkey`, largest first. Change that would be dust is
+/// added to the fee.
+pub fn plan_send(
+    mut utxos: Vec<Coin>,
+    script_pubkey: &ScriptBuf,
+    amount: SendAmount,
+    fee_rate: u64,
+    change_script_pubkey: &ScriptBuf,
+) -> Result<TxPlan, WalletSendError> {
+    if utxos.is_empty() {
+        return Err(WalletSendError::NoCoins);
+    }
+    let recipient_vbytes = output_vbytes(script_pubkey);
+
+    let amount = match amount {
+        SendAmount::Max => {
+            let total = total_value(&utxos)?;
+            let vsize = vsize(utxos.len(), recipient_vbytes);
+            let fee = tx_fee(vsize, fee_rate)?;
+            let amount = total.saturating_sub(fee);
+            if amount < DUST_LIMIT {
+                return Err(WalletSendError::InsufficientFunds {
+                    available: total,
+                    required: fee.saturating_add(DUST_LIMIT),
+                });
+            }
+            return Ok(TxPlan {
+                outputs: vec![TxOut {
+                    value: Amount::from_sat(amount),
+                    script_pubkey: script_pubkey.clone(),
+                }],
+                inputs: utxos,
+                amount,
+                fee,
+                vsize,
+                change: None,
+            });
+        }
+        SendAmount::Sats(amount) if amount < DUST_LIMIT => {
+            return Err(WalletSendError::BelowDust(amount))
+        }
+        SendAmount::Sats(amount) => amount,
+    };
+
+    utxos.sort_by_key(|utxo| std::cmp::Reverse(utxo.txout.value));
+    let available = total_value(&utxos)?;
+
+    let mut selected = Vec::new();
+    let mut selected_value = 0u64;
+    for utxo in utxos {
+        selected_value = selected_value
+            .checked_add(utxo.txout.value.to_sat())
+            .ok_or(WalletSendError::Overflow)?;
+        selected.push(utxo);
+
+        let with_change = vsize(selected.len(), recipient_vbytes + CHANGE_OUTPUT_VBYTES);
+        let fee_with_change = tx_fee(with_change, fee_rate)?;
+        let required_with_change = amount
+            .checked_add(fee_with_change)
+            .and_then(|required| required.checked_add(DUST_LIMIT))
+            .ok_or(WalletSendError::Overflow)?;
+        if selected_value >= required_with_change {
+            let change = selected_value - amount - fee_with_change;
+            return Ok(TxPlan {
+                inputs: selected,
+                outputs: vec![
+                    TxOut {
+                        value: Amount::from_sat(amount),
+                        script_pubkey: script_pubkey.clone(),
+                    },
+                    TxOut {
+                        value: Amount::from_sat(change),
+                        script_pubkey: change_script_pubkey.clone(),
+                    },
+                ],
+                amount,
+                fee: fee_with_change,
+                vsize: with_change,
+                change: Some(change),
+            });
+        }
+
+        let without_change = vsize(selected.len(), recipient_vbytes);
+        let required_without_change = amount
+            .checked_add(tx_fee(without_change, fee_rate)?)
+            .ok_or(WalletSendError::Overflow)?;
+        if selected_value >= required_without_change {
+            return Ok(TxPlan {
+                inputs: selected,
+                outputs: vec![TxOut {
+                    value: Amount::from_sat(amount),
+                    script_pubkey: script_pubkey.clone(),
+                }],
+                amount,
+                fee: selected_value - amount,
+                vsize: without_change,
+                change: None,
+            });
+        }
+    }
+
+    Err(WalletSendError::InsufficientFunds {
+        available,
+        required: amount
+            .checked_add(tx_fee(vsize(1, recipient_vbytes), fee_rate)?)
+            .ok_or(WalletSendError::Overflow)?,
+    })
+}
+
+/// Unsigned PSBT spending the planned inputs, with the witness UTXOs needed to sign them and the
+/// origins of the wallet keys found in `descriptors`.
+pub fn build_psbt(plan: &TxPlan, descriptors: &[Descriptor<DescriptorPublicKey>]) -> Result<Psbt> {
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: plan
+            .inputs
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: plan.outputs.clone(),
+    };
+
+    let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(e.to_string()),
+        )
+    })?;
+    for (input, utxo) in psbt.inputs.iter_mut().zip(&plan.inputs) {
+        input.witness_utxo = Some(utxo.txout.clone());
+        input.bip32_derivation = key_origins(descriptors, utxo);
+    }
+    Ok(psbt)
+}
+
+/// Fingerprint and derivation path of the key a coin pays to, from the descriptor deriving its
+/// script. External signers need them to find the key to sign with.
+fn key_origins(
+    descriptors: &[Descriptor<DescriptorPublicKey>],
+    coin: &Coin,
+) -> BTreeMap<secp256k1::PublicKey, KeySource> {
+    let secp = Secp256k1::verification_only();
+    let mut origins = BTreeMap::new();
+    let derived = descriptors
+        .iter()
+        .filter_map(|descriptor| descriptor.at_derivation_index(coin.derivation_index).ok())
+        .find(|derived| derived.script_pubkey() == coin.txout.script_pubkey);
+    if let Some(derived) = derived {
+        derived.for_each_key(|key| {
+            let key_source = key.as_descriptor_public_key();
+            if let (Ok(public_key), Some(path)) = (
+                key.derive_public_key(&secp),
+                key_source.full_derivation_path(),
+            ) {
+                origins.insert(public_key.inner, (key_source.master_fingerprint(), path));
+            }
+            true
+        });
+    }
+    origins
+}
+
+/// Adds the final witness to P2WPKH inputs signed by an external signer and extracts the
+/// transaction. Signatures are checked against the input's sighash, so a PSBT signed for other
+/// inputs or amounts is refused before it reaches the network. Inputs that are already finalized
+/// are kept as they are.
+pub fn finalize_psbt(mut psbt: Psbt) -> Result<Transaction, WalletSendError> {
+    let secp = Secp256k1::verification_only();
+    let mut sighashes = SighashCache::new(&psbt.unsigned_tx);
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        if input.final_script_witness.is_some() {
+            continue;
+        }
+        let txout = match &input.witness_utxo {
+            Some(txout) if txout.script_pubkey.is_p2wpkh() => txout,
+            _ => return Err(WalletSendError::UnsupportedInput(index)),
+        };
+
+        let (public_key, signature) = input
+            .partial_sigs
+            .iter()
+            .find_map(|(public_key, signature)| {
+                let public_key = CompressedPublicKey::try_from(*public_key).ok()?;
+                (ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash()) == txout.script_pubkey)
+                    .then_some((public_key, *signature))
+            })
+            .ok_or(WalletSendError::UnsignedInput(index))?;
+
+        let sighash = sighashes
+            .p2wpkh_signature_hash(
+                index,
+                &txout.script_pubkey,
+                txout.value,
+                signature.sighash_type,
+            )
+            .map_err(|_| WalletSendError::UnsupportedInput(index))?;
+        secp.verify_ecdsa(
+            &Message::from_digest(sighash.to_byte_array()),
+            &signature.signature,
+            &public_key.0,
+        )
+        .map_err(|_| WalletSendError::InvalidSignature(index))?;
+
+        input.final_script_witness = Some(Witness::p2wpkh(&signature, &public_key.0));
+        input.partial_sigs.clear();
+        input.sighash_type = None;
+        input.bip32_derivation.clear();
+    }
+
+    psbt.extract_tx()
+        .map_err(|e| WalletSendError::Extract(e.to_string()))
+}
+
+/// Fee paid by a PSBT, if every input carries its witness UTXO.
+pub fn psbt_fee(psbt: &Psbt) -> Option<u64> {
+    let input_value = psbt
+        .inputs
+        .iter()
+        .map(|input| {
+            input
+                .witness_utxo
+                .as_ref()
+                .map(|txout| txout.value.to_sat())
+        })
+        .sum::<Option<u64>>()?;
+    let output_value = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .map(|txout| txout.value.to_sat())
+        .sum::<u64>();
+    input_value.checked_sub(output_value)
+}
+
+/// Signs every input of the PSBT with the DDK wallet and extracts the final transaction.
+pub async fn sign_psbt(sol: &SonsOfLiberty, mut psbt: Psbt) -> Result<Transaction> {
+    for index in 0..psbt.inputs.len() {
+        sol.dlcdevkit
+            .wallet
+            .sign_psbt_input(&mut psbt, index)
+            .await
+            .map_err(|e| {
+                Error::CustomError(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorDetail::with_reason(format!("Failed to sign input {index}: {e}")),
+                )
+            })?;
+    }
+    psbt.extract_tx().map_err(|e| {
+        Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason(format!("Failed to extract transaction: {e}")),
+        )
+    })
+}
+
+/// Parses an address and checks it belongs to the configured network.
+pub fn parse_address(address: &str, network: &str) -> Result<Address> {
+    let network = Network::from_str(network).map_err(|_| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(format!("Invalid network: {network}")),
+        )
+    })?;
+    Address::from_str(address)
+        .and_then(|address| address.require_network(network))
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail {
+                    error: Some(e.to_string()),
+                    description: Some(format!("Invalid {network} address")),
+                },
+            )
+        })
+}
+
+/// Outpoint spent by a DLC funding input.
+pub fn funding_input_outpoint(input: &FundingInput) -> Option<OutPoint> {
+    let prev_tx = bitcoin::consensus::deserialize::<Transaction>(&input.prev_tx).ok()?;
+    Some(OutPoint::new(prev_tx.compute_txid(), input.prev_tx_vout))
+}
+
+/// Coins used as funding inputs of contracts that are not funded yet. Spending them would make
+/// the funding transaction invalid.
+pub async fn reserved_outpoints(sol: &SonsOfLiberty) -> Result<HashSet<OutPoint>> {
+    let contracts = sol.dlcdevkit.storage.get_contracts().await.map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(format!("Failed to get contracts: {e}")),
+        )
+    })?;
+
+    let mut reserved = HashSet::new();
+    for contract in contracts {
+        match contract {
+            Contract::Offered(offered) if offered.is_offer_party => {
+                reserved.extend(
+                    offered
+                        .funding_inputs
+                        .iter()
+                        .filter_map(funding_input_outpoint),
+                );
+            }
+            Contract::Accepted(accepted) => {
+                reserved.extend(
+                    accepted
+                        .dlc_transactions
+                        .fund
+                        .input
+                        .iter()
+                        .map(|input| input.previous_output),
+                );
+            }
+            Contract::Signed(signed) => {
+                reserved.extend(
+                    signed
+                        .accepted_contract
+                        .dlc_transactions
+                        .fund
+                        .input
+                        .iter()
+                        .map(|input| input.previous_output),
+                );
+            }
+            _ => {}
+        }
+    }
+    Ok(reserved)
+}
+
+/// Unspent wallet coins that are neither frozen nor reserved for contract funding or by an
+/// exported PSBT.
+pub async fn spendable_utxos(db: &DatabaseConnection, sol: &SonsOfLiberty) -> Result<Vec<Coin>> {
+    let mut reserved = reserved_outpoints(sol).await?;
+    reserved.extend(utxo_labels::Model::frozen_outpoints(db).await?);
+    reserved.extend(psbt_reservations::Model::reserved_outpoints(db).await?);
+    let utxos = sol.dlcdevkit.wallet.list_utxos().map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(e.to_string()),
+        )
+    })?;
+    Ok(utxos
+        .into_iter()
+        .filter(|utxo| !utxo.is_spent && !reserved.contains(&utxo.outpoint))
+        .map(Coin::from)
+        .collect())
+}
+
+/// Public descriptors of the DDK wallet's stored keychains.
+pub async fn wallet_descriptors(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+) -> Result<Vec<Descriptor<DescriptorPublicKey>>> {
+    find_by_wallet::<keychain::Entity>(db, &sol.settings.name)
+        .await?
+        .iter()
+        .map(parse_descriptor)
+        .collect()
+}
+
+fn parse_descriptor(keychain: &keychain::Model) -> Result<Descriptor<DescriptorPublicKey>> {
+    Descriptor::<DescriptorPublicKey>::from_str(&keychain.descriptor).map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(format!("Invalid {} descriptor: {e}", keychain.keychainkind)),
+        )
+    })
+}
+
+/// Next change script of the DDK wallet, derived from its stored internal keychain without
+/// revealing it, so previews and exports leave the change index where it is.
+pub async fn change_script_pubkey(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+) -> Result<ScriptBuf> {
+    let internal = |reason: String| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(reason),
+        )
+    };
+    let keychain = find_by_wallet::<keychain::Entity>(db, &sol.settings.name)
+        .await?
+        .into_iter()
+        .find(|keychain| keychain.keychainkind.eq_ignore_ascii_case("internal"))
+        .ok_or_else(|| internal("The wallet has no change keychain".to_string()))?;
+    let descriptor = parse_descriptor(&keychain)?;
+    let index = keychain
+        .last_revealed
+        .and_then(|index| u32::try_from(index).ok())
+        .map_or(0, |index| index + 1);
+    let derived = descriptor
+        .at_derivation_index(index)
+        .map_err(|e| internal(format!("Failed to derive change script {index}: {e}")))?;
+    Ok(derived.script_pubkey())
+}
+
+/// Reveals a fresh change script of the DDK wallet, for transactions that are broadcast.
+pub async fn reveal_change_script_pubkey(sol: &SonsOfLiberty) -> Result<ScriptBuf> {
+    let address = sol
+        .dlcdevkit
+        .wallet
+        .new_change_address()
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+    Ok(address.address.script_pubkey())
+}
+
+/// Fee rate in sat/vB for confirmation within `target` blocks, from esplora's estimates.
+pub async fn estimate_fee_rate(sol: &SonsOfLiberty, target: u16) -> Result<u64> {
+    let url = format!(
+        "{}/fee-estimates",
+        sol.settings.esplora_host.trim_end_matches('/')
+    );
+    let estimates = reqwest::get(url)
+        .await
+        .map_err(esplora_error)?
+        .json::<std::collections::HashMap<String, f64>>()
+        .await
+        .map_err(esplora_error)?;
+
+    let mut estimates = estimates
+        .into_iter()
+        .filter_map(|(blocks, rate)| Some((blocks.parse::<u16>().ok()?, rate)))
+        .collect::<Vec<_>>();
+    estimates.sort_by_key(|(blocks, _)| *blocks);
+
+    // Use the slowest, so cheapest, estimate that still confirms within the target, or the
+    // fastest available when every estimate is slower than the target.
+    estimates
+        .iter()
+        .rev()
+        .find(|(blocks, _)| *blocks <= target)
+        .or_else(|| estimates.first())
+        .map(|(_, rate)| (rate.ceil() as u64).max(1))
+        .ok_or_else(|| {
+            Error::CustomError(
+                StatusCode::BAD_GATEWAY,
+                ErrorDetail::with_reason("No fee estimates available"),
+            )
+        })
+}
+
+/// Broadcasts a transaction through the configured esplora server.
+pub async fn broadcast(sol: &SonsOfLiberty, tx: &Transaction) -> Result<()> {
+    let url = format!("{}/tx", sol.settings.esplora_host.trim_end_matches('/'));
+    let response = reqwest::Client::new()
+        .post(url)
+        .body(serialize_hex(tx))
+        .send()
+        .await
+        .map_err(esplora_error)?;
+
+    if !response.status().is_success() {
+        let reason = response.text().await.unwrap_or_default();
+        return Err(Error::CustomError(
+            StatusCode::BAD_GATEWAY,
+            ErrorDetail {
+                error: Some(reason),
+                description: Some("Failed to broadcast transaction".to_string()),
+            },
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct Outspend {
+    spent: bool,
+    txid: Option<Txid>,
+}
+
+/// Transaction spending an outpoint, once esplora has seen one in the mempool or a block.
+pub async fn spending_transaction(
+    sol: &SonsOfLiberty,
+    outpoint: &OutPoint,
+) -> Result<Option<Transaction>> {
+    let host = sol.settings.esplora_host.trim_end_matches('/');
+    let outspend = reqwest::get(format!(
+        "{host}/tx/{}/outspend/{}",
+        outpoint.txid, outpoint.vout
+    ))
+    .await
+    .map_err(esplora_error)?
+    .json::<Outspend>()
+    .await
+    .map_err(esplora_error)?;
+    let Some(txid) = outspend.txid.filter(|_| outspend.spent) else {
+        return Ok(None);
+    };
+
+    let hex = reqwest::get(format!("{host}/tx/{txid}/hex"))
+        .await
+        .map_err(esplora_error)?
+        .text()
+        .await
+        .map_err(esplora_error)?;
+    deserialize_hex::<Transaction>(hex.trim())
+        .map(Some)
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::BAD_GATEWAY,
+                ErrorDetail::with_reason(format!("Invalid transaction {txid} from esplora: {e}")),
+            )
+        })
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn esplora_error(e: reqwest::Error) -> Error {
+    Error::CustomError(
+        StatusCode::BAD_GATEWAY,
+        ErrorDetail::with_reason(e.to_string()),
+    )
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn send_error(e: WalletSendError) -> Error {
+    Error::CustomError(
+        StatusCode::BAD_REQUEST,
+        ErrorDetail::with_reason(e.to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coin(vout: u32, value: u64) -> Coin {
+        Coin {
+            outpoint: OutPoint::new(bitcoin::Txid::all_zeros(), vout),
+            txout: TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: p2wpkh(),
+            },
+            derivation_index: vout,
+        }
+    }
+
+    fn p2wpkh() -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros())
+    }
+
+    #[test]
+    fn test_send_selects_largest_coins_with_change() {
+        let plan = plan_send(
+            vec![coin(0, 10_000), coin(1, 50_000), coin(2, 30_000)],
+            &p2wpkh(),
+            SendAmount::Sats(40_000),
+            2,
+            &p2wpkh(),
+        )
+        .unwrap();
+        assert_eq!(plan.inputs, vec![coin(1, 50_000)]);
+        assert_eq!(plan.vsize, 11 + 68 + 31 + 31);
+        assert_eq!(plan.fee, plan.vsize * 2);
+        assert_eq!(plan.change, Some(50_000 - 40_000 - plan.fee));
+    }
+
+    #[test]
+    fn test_dust_change_goes_to_fee() {
+        let plan = plan_send(
+            vec![coin(0, 40_500)],
+            &p2wpkh(),
+            SendAmount::Sats(40_000),
+            2,
+            &p2wpkh(),
+        )
+        .unwrap();
+        assert_eq!(plan.change, None);
+        assert_eq!(plan.outputs.len(), 1);
+        assert_eq!(plan.fee, 500);
+    }
+
+    #[test]
+    fn test_send_max_spends_every_coin() {
+        let plan = plan_send(
+            vec![coin(0, 10_000), coin(1, 20_000)],
+            &p2wpkh(),
+            SendAmount::Max,
+            1,
+            &p2wpkh(),
+        )
+        .unwrap();
+        assert_eq!(plan.inputs.len(), 2);
+        assert_eq!(plan.fee, 11 + 2 * 68 + 31);
+        assert_eq!(plan.amount, 30_000 - plan.fee);
+    }
+
+    #[test]
+    fn test_finalize_externally_signed_psbt() {
+        use bitcoin::{
+            bip32::{DerivationPath, Xpriv, Xpub},
+            sighash::EcdsaSighashType,
+        };
+
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Regtest, &[7; 32]).unwrap();
+        let account_path = DerivationPath::from_str("m/84'/1'/0'").unwrap();
+        let account = master.derive_priv(&secp, &account_path).unwrap();
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&format!(
+            "wpkh([{}/84'/1'/0']{}/0/*)",
+            master.fingerprint(&secp),
+            Xpub::from_priv(&secp, &account)
+        ))
+        .unwrap();
+        let key_path = DerivationPath::from_str("m/84'/1'/0'/0/3").unwrap();
+        let secret_key = master.derive_priv(&secp, &key_path).unwrap().private_key;
+        let public_key = bitcoin::PublicKey::new(secret_key.public_key(&secp));
+        let script_pubkey = descriptor.at_derivation_index(3).unwrap().script_pubkey();
+
+        let plan = plan_send(
+            vec![Coin {
+                outpoint: OutPoint::new(bitcoin::Txid::all_zeros(), 0),
+                txout: TxOut {
+                    value: Amount::from_sat(50_000),
+                    script_pubkey: script_pubkey.clone(),
+                },
+                derivation_index: 3,
+            }],
+            &p2wpkh(),
+            SendAmount::Max,
+            1,
+            &p2wpkh(),
+        )
+        .unwrap();
+        let mut psbt = build_psbt(&plan, &[descriptor]).unwrap();
+        assert_eq!(psbt_fee(&psbt), Some(plan.fee));
+        assert_eq!(
+            psbt.inputs[0].bip32_derivation.get(&public_key.inner),
+            Some(&(master.fingerprint(&secp), key_path))
+        );
+        assert!(matches!(
+            finalize_psbt(psbt.clone()),
+            Err(WalletSendError::UnsignedInput(0))
+        ));
+
+        // A signature over anything but the input's sighash is refused.
+        let mut forged = psbt.clone();
+        forged.inputs[0].partial_sigs.insert(
+            public_key,
+            bitcoin::ecdsa::Signature::sighash_all(
+                secp.sign_ecdsa(&Message::from_digest([1; 32]), &secret_key),
+            ),
+        );
+        assert!(matches!(
+            finalize_psbt(forged),
+            Err(WalletSendError::InvalidSignature(0))
+        ));
+
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .p2wpkh_signature_hash(
+                0,
+                &script_pubkey,
+                Amount::from_sat(50_000),
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        let signature =
+            secp.sign_ecdsa(&Message::from_digest(sighash.to_byte_array()), &secret_key);
+        psbt.inputs[0].partial_sigs.insert(
+            public_key,
+            bitcoin::ecdsa::Signature::sighash_all(signature),
+        );
+        let tx = finalize_psbt(psbt).unwrap();
+        assert_eq!(tx.input[0].witness.len(), 2);
+        assert_eq!(tx.output[0].value.to_sat(), plan.amount);
+    }
+
+    #[test]
+    fn test_overflowing_amounts_are_rejected() {
+        assert!(matches!(
+            plan_send(
+                vec![coin(0, 10_000)],
+                &p2wpkh(),
+                SendAmount::Sats(1_000),
+                u64::MAX,
+                &p2wpkh()
+            ),
+            Err(WalletSendError::Overflow)
+        ));
+        assert!(matches!(
+            plan_send(
+                vec![coin(0, 10_000)],
+                &p2wpkh(),
+                SendAmount::Sats(u64::MAX),
+                1,
+                &p2wpkh()
+            ),
+            Err(WalletSendError::Overflow)
+        ));
+        assert!(matches!(
+            plan_send(
+                vec![coin(0, u64::MAX), coin(1, u64::MAX)],
+                &p2wpkh(),
+                SendAmount::Max,
+                1,
+                &p2wpkh()
+            ),
+            Err(WalletSendError::Overflow)
+        ));
+    }
+
+    #[test]
+    fn test_change_script_is_replaced() {
+        let mut plan = plan_send(
+            vec![coin(0, 100_000)],
+            &p2wpkh(),
+            SendAmount::Sats(10_000),
+            1,
+            &p2wpkh(),
+        )
+        .unwrap();
+        let revealed = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([1; 20]));
+        let fee = plan.fee;
+        plan.set_change_script_pubkey(revealed.clone());
+        assert_eq!(plan.outputs[0].script_pubkey, p2wpkh());
+        assert_eq!(plan.outputs[1].script_pubkey, revealed);
+        assert_eq!(plan.fee, fee);
+    }
+
+    #[test]
+    fn test_insufficient_funds() {
+        assert!(matches!(
+            plan_send(
+                vec![coin(0, 10_000)],
+                &p2wpkh(),
+                SendAmount::Sats(10_000),
+                1,
+                &p2wpkh()
+            ),
+            Err(WalletSendError::InsufficientFunds { .. })
+        ));
+        assert!(matches!(
+            plan_send(vec![], &p2wpkh(), SendAmount::Max, 1, &p2wpkh()),
+            Err(WalletSendError::NoCoins)
+        ));
+    }
+}
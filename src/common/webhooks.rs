@@ -0,0 +1,403 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use bitcoin::key::rand::{thread_rng, RngCore};
+use chrono::Utc;
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    models::{
+        webhook_deliveries::{self, DeliveryStatus},
+        webhooks,
+    },
+    views::events::NodeEvent,
+    workers::webhook::{WebhookWorker, WebhookWorkerArgs},
+};
+
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// Hosts that answer with cloud instance metadata, whatever they resolve to.
+const METADATA_HOSTS: [&str; 2] = ["metadata.google.internal", "metadata"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WebhookSettings {
+    /// Attempts per delivery before it is marked failed.
+    pub max_attempts: u32,
+    /// Seconds before the first retry, doubled after every failed attempt.
+    pub backoff_secs: u64,
+    pub timeout_secs: u64,
+    /// Seconds between checks for deliveries that are due, 0 disables delivery.
+    pub poll_secs: u64,
+    /// Allows webhooks on loopback, private, link-local and metadata hosts.
+    pub allow_private_hosts: bool,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff_secs: 5,
+            timeout_secs: 10,
+            poll_secs: 5,
+            allow_private_hosts: false,
+        }
+    }
+}
+
+impl WebhookSettings {
+    /// Wait after the failed attempt number `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_secs(self.backoff_secs.saturating_mul(factor))
+    }
+
+    /// How long a queued attempt keeps its delivery from being queued again.
+    fn lease(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.saturating_mul(2).max(self.poll_secs))
+    }
+}
+
+/// The time `duration` from now, or the latest time there is.
+fn after(duration: Duration) -> chrono::DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| Utc::now().checked_add_signed(duration))
+        .unwrap_or(chrono::DateTime::<Utc>::MAX_UTC)
+}
+
+/// Body posted to webhooks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookPayload {
+    pub event: String,
+    pub created_at: chrono::DateTime<Utc>,
+    pub data: NodeEvent,
+}
+
+/// A random secret for webhooks registered without one.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; 32];
+    thread_rng().fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space, where some clouds serve instance metadata.
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local, including the EC2 metadata address.
+                    || (first & 0xfe00) == 0xfc00
+                    // Link-local.
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Resolves the host of a webhook url and checks it is public, unless `allow_private_hosts`.
+/// Returns the addresses to connect to so the check holds for the request itself.
+pub async fn resolve_url(
+    url: &reqwest::Url,
+    allow_private_hosts: bool,
+) -> std::result::Result<Vec<SocketAddr>, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Webhook url must be http or https".to_string());
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "Webhook url has no host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_lowercase();
+    let local_name = host == "localhost" || host.ends_with(".localhost");
+    if !allow_private_hosts && (local_name || METADATA_HOSTS.contains(&host.as_str())) {
+        return Err(format!("Webhook host {host} is not allowed"));
+    }
+
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|e| format!("Failed to resolve webhook host {host}: {e}"))?
+        .collect::<Vec<_>>();
+    if allow_private_hosts {
+        return Ok(addresses);
+    }
+    if let Some(address) = addresses.iter().find(|address| !is_public_ip(address.ip())) {
+        return Err(format!(
+            "Webhook host {host} resolves to {} which is not a public address",
+            address.ip()
+        ));
+    }
+    Ok(addresses)
+}
+
+/// Logs a delivery of every event to the webhooks subscribed to it. The deliveries are due right
+/// away and picked up by the delivery loop.
+pub async fn record(db: &DatabaseConnection, events: &[NodeEvent]) -> Result<usize> {
+    let mut recorded = 0;
+    for event in events {
+        let webhooks = webhooks::Model::subscribed(db, event.name()).await?;
+        if webhooks.is_empty() {
+            continue;
+        }
+        let payload = serde_json::to_value(WebhookPayload {
+            event: event.name().to_string(),
+            created_at: Utc::now(),
+            data: event.clone(),
+        })?;
+        for webhook in &webhooks {
+            webhook_deliveries::ActiveModel::enqueue(db, webhook.id, event.name(), payload.clone())
+                .await?;
+            recorded += 1;
+        }
+    }
+    Ok(recorded)
+}
+
+/// Queues one attempt of every delivery that is due, leasing them so the next poll leaves them
+/// alone while the attempt runs.
+pub async fn queue_due(ctx: &AppContext, settings: &WebhookSettings) -> Result<usize> {
+    let due = webhook_deliveries::Model::due(&ctx.db, Utc::now()).await?;
+    let queued = due.len();
+    for delivery in due {
+        let delivery = delivery.schedule(&ctx.db, after(settings.lease())).await?;
+        if let Err(e) = WebhookWorker::perform_later(
+            ctx,
+            WebhookWorkerArgs {
+                delivery_id: delivery.id,
+            },
+        )
+        .await
+        {
+            tracing::warn!("Webhook delivery {} attempt failed: {e}", delivery.id);
+        }
+    }
+    Ok(queued)
+}
+
+/// Queues due deliveries every `poll_secs` for as long as the server runs.
+pub fn spawn_delivery_loop(ctx: AppContext, settings: WebhookSettings) {
+    if settings.poll_secs == 0 {
+        tracing::info!("Webhook delivery is disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_secs));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = queue_due(&ctx, &settings).await {
+                tracing::error!("Failed to queue webhook deliveries: {e}");
+            }
+        }
+    });
+}
+
+/// Makes one attempt at a logged delivery and records it. A failed attempt with attempts left
+/// stays pending and is due again after the backoff.
+pub async fn deliver(
+    db: &DatabaseConnection,
+    settings: &WebhookSettings,
+    delivery_id: i32,
+) -> Result<webhook_deliveries::Model> {
+    let Some((delivery, Some(webhook))) = webhook_deliveries::Entity::find_by_id(delivery_id)
+        .find_also_related(webhooks::Entity)
+        .one(db)
+        .await?
+    else {
+        return Err(Error::NotFound);
+    };
+    if delivery.status != DeliveryStatus::Pending.name() {
+        return Ok(delivery);
+    }
+    if !webhook.active {
+        return Ok(delivery
+            .attempted(
+                db,
+                DeliveryStatus::Failed,
+                None,
+                Some("Webhook is disabled".to_string()),
+            )
+            .await?);
+    }
+
+    let (response_status, error) = match attempt(settings, &webhook, &delivery).await {
+        Ok(status) if (200..300).contains(&status) => {
+            return Ok(delivery
+                .attempted(db, DeliveryStatus::Delivered, Some(status), None)
+                .await?);
+        }
+        Ok(status) => (Some(status), format!("Webhook answered {status}")),
+        Err(e) => (None, e),
+    };
+
+    let attempt = u32::try_from(delivery.attempts).unwrap_or(0) + 1;
+    let status = if attempt >= settings.max_attempts {
+        DeliveryStatus::Failed
+    } else {
+        DeliveryStatus::Pending
+    };
+    tracing::warn!(
+        "Webhook delivery {} to {} failed on attempt {attempt}: {error}",
+        delivery.id,
+        webhook.url
+    );
+    let delivery = delivery
+        .attempted(db, status, response_status, Some(error))
+        .await?;
+    if status == DeliveryStatus::Failed {
+        return Ok(delivery);
+    }
+    Ok(delivery
+        .schedule(db, after(settings.backoff(attempt)))
+        .await?)
+}
+
+/// Posts the signed payload once, to an address checked by [`resolve_url`], and returns the
+/// response status.
+async fn attempt(
+    settings: &WebhookSettings,
+    webhook: &webhooks::Model,
+    delivery: &webhook_deliveries::Model,
+) -> std::result::Result<i32, String> {
+    let url = reqwest::Url::parse(&webhook.url).map_err(|e| e.to_string())?;
+    let addresses = resolve_url(&url, settings.allow_private_hosts).await?;
+    let mut client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(settings.timeout_secs))
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(domain) = url.domain() {
+        client = client.resolve_to_addrs(domain, &addresses);
+    }
+    let client = client.build().map_err(|e| e.to_string())?;
+
+    let body = serde_json::to_vec(&delivery.payload).map_err(|e| e.to_string())?;
+    let timestamp = Utc::now().timestamp();
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, webhook.sign(timestamp, &body))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(i32::from(response.status().as_u16()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles() {
+        let settings = WebhookSettings {
+            backoff_secs: 5,
+            ..WebhookSettings::default()
+        };
+        assert_eq!(settings.backoff(1), Duration::from_secs(5));
+        assert_eq!(settings.backoff(2), Duration::from_secs(10));
+        assert_eq!(settings.backoff(4), Duration::from_secs(40));
+        assert_eq!(settings.backoff(100), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn test_sign_is_hmac_sha256_of_timestamp_and_body() {
+        // Key and message of RFC 4231 test case 2, prefixed with the timestamp.
+        let webhook = webhooks::Model {
+            created_at: Utc::now().into(),
+            updated_at: Utc::now().into(),
+            id: 1,
+            url: "http://localhost".to_string(),
+            secret: "Jefe".to_string(),
+            events: serde_json::json!([]),
+            active: true,
+        };
+        assert_eq!(
+            webhook.sign(1_700_000_000, b"what do ya want for nothing?"),
+            "sha256=1cdd0650c8be1cb0974b1788d458b1e781206cfef59b85faafc582d2e182c57e"
+        );
+    }
+
+    #[test]
+    fn test_private_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.100.100.200",
+            "0.0.0.0",
+            "::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_url_refuses_private_hosts() {
+        let url = |url: &str| reqwest::Url::parse(url).unwrap();
+        assert!(resolve_url(&url("http://127.0.0.1:8080/hook"), false)
+            .await
+            .is_err());
+        assert!(resolve_url(&url("http://[::1]/hook"), false).await.is_err());
+        assert!(
+            resolve_url(&url("http://169.254.169.254/latest/meta-data"), false)
+                .await
+                .is_err()
+        );
+        assert!(resolve_url(&url("ftp://1.1.1.1/hook"), false)
+            .await
+            .is_err());
+        assert_eq!(
+            resolve_url(&url("http://127.0.0.1:8080/hook"), true)
+                .await
+                .unwrap(),
+            ["127.0.0.1:8080".parse().unwrap()]
+        );
+        assert!(resolve_url(&url("https://1.1.1.1/hook"), false)
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_generated_secrets_differ() {
+        assert_eq!(generate_secret().len(), 64);
+        assert_ne!(generate_secret(), generate_secret());
+    }
+}
@@ -2,14 +2,17 @@
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
 use crate::{
-    common::dlcdevkit,
+    common::{
+        bitcoin_price::{normalize_currency, DEFAULT_CURRENCY},
+        dlcdevkit, pnl,
+    },
     models::{_entities::balances, users},
     sol::SonsOfLiberty,
-    views::balances::BalanceHistoryRequest,
+    views::balances::{BalanceHistoryRequest, HistoryAggregate, PnlQuery},
 };
-use axum::{debug_handler, extract::Query, Extension};
+use axum::{debug_handler, extract::Query, http::StatusCode, Extension};
 use bitcoin::SignedAmount;
-use loco_rs::prelude::*;
+use loco_rs::{controller::ErrorDetail, prelude::*};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -74,9 +77,49 @@ pub async fn history(
     req: Query<BalanceHistoryRequest>,
 ) -> Result<Response> {
     users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
-    let history =
-        balances::Model::get_history(&ctx.db, req.time_period, req.reference_date).await?;
-    format::json(history)
+    let (start, end) = req
+        .window()
+        .ok_or_else(|| bad_request("Give a time_period or a start".to_string()))?;
+    if start > end {
+        return Err(bad_request("start must be before end".to_string()));
+    }
+    let currency = req
+        .currency
+        .as_deref()
+        .map(normalize_currency)
+        .transpose()
+        .map_err(|e| bad_request(e.to_string()))?;
+
+    match (req.aggregate, req.bucket) {
+        (HistoryAggregate::Ohlc, Some(bucket)) => {
+            let currency = currency.as_deref().unwrap_or(DEFAULT_CURRENCY);
+            format::json(balances::Model::candles(&ctx.db, start, end, bucket, currency).await?)
+        }
+        (HistoryAggregate::Ohlc, None) => {
+            Err(bad_request("OHLC history needs a bucket".to_string()))
+        }
+        (HistoryAggregate::Last, bucket) => match currency {
+            Some(currency) => format::json(
+                balances::Model::get_range_in(&ctx.db, start, end, bucket, &currency).await?,
+            ),
+            None => format::json(balances::Model::get_range(&ctx.db, start, end, bucket).await?),
+        },
+    }
+}
+
+fn bad_request(reason: String) -> Error {
+    Error::CustomError(StatusCode::BAD_REQUEST, ErrorDetail::with_reason(reason))
+}
+
+#[debug_handler]
+pub async fn pnl_report(
+    cookie: CookieAuth,
+    Extension(ddk): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<PnlQuery>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    format::json(pnl::pnl_report(&ctx.db, &ddk, &query).await?)
 }
 
 pub fn routes() -> Routes {
@@ -84,4 +127,5 @@ pub fn routes() -> Routes {
         .prefix("api/balance/")
         .add("/", get(index))
         .add("/history", get(history))
+        .add("/pnl", get(pnl_report))
 }
@@ -0,0 +1,537 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use std::sync::Arc;
+
+use crate::{
+    common::{
+        close::{self, CloseTerms},
+        wallet::{self, broadcast},
+    },
+    models::{close_proposals, users},
+    sol::SonsOfLiberty,
+    views::close::CloseMessage,
+};
+use axum::{debug_handler, http::StatusCode, Extension};
+use bitcoin::{secp256k1::ecdsa::Signature, Transaction};
+use chrono::{Duration, Utc};
+use ddk_manager::{
+    contract::{signed_contract::SignedContract, ClosedContract, Contract},
+    ContractId, ContractSigner, ContractSignerProvider, Storage,
+};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use super::auth::CookieAuth;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProposeCloseBody {
+    contract_id: String,
+    /// What we receive from the funding output, in sats. The counterparty receives the rest.
+    payout: u64,
+    fee_rate: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AcceptCloseBody {
+    contract_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WithdrawCloseBody {
+    contract_id: String,
+}
+
+/// Lists close proposals after picking up new messages from the counterparties.
+#[debug_handler]
+pub async fn index(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    sync_close_messages(&ctx.db, &sol).await?;
+
+    format::json(close_proposals::Model::list(&ctx.db).await?)
+}
+
+#[debug_handler]
+pub async fn propose(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<ProposeCloseBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let contract = get_confirmed_contract(&sol, &body.contract_id).await?;
+    let offered = &contract.accepted_contract.offered_contract;
+    if body.payout > offered.total_collateral {
+        return Err(bad_request(format!(
+            "Payout of {} sats is more than the total collateral of {} sats",
+            body.payout, offered.total_collateral
+        )));
+    }
+    let counter_payout = offered.total_collateral - body.payout;
+    let terms = if offered.is_offer_party {
+        CloseTerms {
+            offer_payout: body.payout,
+            accept_payout: counter_payout,
+            fee_rate: body.fee_rate,
+        }
+    } else {
+        CloseTerms {
+            offer_payout: counter_payout,
+            accept_payout: body.payout,
+            fee_rate: body.fee_rate,
+        }
+    };
+
+    let tx = close::build_close_transaction(&contract, &terms).map_err(close_error)?;
+    let signature =
+        close::sign_close_transaction(&contract, &tx, &funding_secret_key(&sol, &contract)?)
+            .map_err(close_error)?;
+    let signature = hex::encode(signature.serialize_compact());
+
+    let counterparty = nostr_counterparty(&contract)?;
+    sol.nostr
+        .send_close_message(
+            counterparty,
+            &CloseMessage::Propose {
+                contract_id: body.contract_id.clone(),
+                offer_payout: terms.offer_payout,
+                accept_payout: terms.accept_payout,
+                fee_rate: terms.fee_rate,
+                signature: signature.clone(),
+            },
+        )
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    let proposal = close_proposals::ActiveModel::create_proposal(
+        &ctx.db,
+        &body.contract_id,
+        &counterparty.to_hex(),
+        true,
+        &terms,
+        &signature,
+    )
+    .await?;
+
+    tracing::info!(
+        "Proposed cooperative close of {} with txid {}",
+        body.contract_id,
+        tx.compute_txid()
+    );
+
+    format::json(proposal)
+}
+
+#[debug_handler]
+pub async fn accept(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<AcceptCloseBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    sync_close_messages(&ctx.db, &sol).await?;
+
+    let proposal = close_proposals::Model::find_open(&ctx.db, &body.contract_id, false)
+        .await?
+        .ok_or_else(|| {
+            Error::CustomError(
+                StatusCode::NOT_FOUND,
+                ErrorDetail::with_reason("No close proposal received for this contract"),
+            )
+        })?;
+
+    let contract = get_confirmed_contract(&sol, &body.contract_id).await?;
+    let offered = &contract.accepted_contract.offered_contract;
+    let terms = proposal.terms();
+    let tx = close::build_close_transaction(&contract, &terms).map_err(close_error)?;
+
+    let counter_signature = hex::decode(&proposal.signature)
+        .ok()
+        .and_then(|bytes| Signature::from_compact(&bytes).ok())
+        .ok_or_else(|| bad_request("Close proposal has an invalid signature".to_string()))?;
+    let counter_fund_pubkey = if offered.is_offer_party {
+        contract.accepted_contract.accept_params.fund_pubkey
+    } else {
+        offered.offer_params.fund_pubkey
+    };
+    close::verify_close_signature(&contract, &tx, &counter_signature, &counter_fund_pubkey)
+        .map_err(close_error)?;
+
+    let signature =
+        close::sign_close_transaction(&contract, &tx, &funding_secret_key(&sol, &contract)?)
+            .map_err(close_error)?;
+    let (offer_signature, accept_signature) = if offered.is_offer_party {
+        (signature, counter_signature)
+    } else {
+        (counter_signature, signature)
+    };
+    let tx = close::finalize_close_transaction(&contract, tx, offer_signature, accept_signature);
+
+    broadcast(&sol, &tx).await?;
+    let txid = tx.compute_txid().to_string();
+
+    sol.nostr
+        .send_close_message(
+            nostr_counterparty(&contract)?,
+            &CloseMessage::Accept {
+                contract_id: body.contract_id.clone(),
+                txid: txid.clone(),
+            },
+        )
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    let pnl = mark_contract_closed(&sol, &contract, &terms, Some(tx)).await?;
+    let proposal = close_proposals::ActiveModel::mark_closed(&ctx.db, proposal, &txid).await?;
+
+    format::json(serde_json::json!({
+        "proposal": proposal,
+        "txid": txid,
+        "pnl": pnl,
+    }))
+}
+
+/// Withdraws our open proposals for a contract and tells the counterparty to drop them. The
+/// counterparty keeps our signatures, so a withdrawn proposal can still close the contract if it
+/// was broadcast before the withdrawal arrived.
+#[debug_handler]
+pub async fn withdraw(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<WithdrawCloseBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let proposals = close_proposals::Model::all_open(&ctx.db, &body.contract_id, true).await?;
+    if proposals.is_empty() {
+        return Err(Error::CustomError(
+            StatusCode::NOT_FOUND,
+            ErrorDetail::with_reason("No open close proposal sent for this contract"),
+        ));
+    }
+
+    let mut withdrawn = Vec::new();
+    for proposal in proposals {
+        let counterparty = nostr::PublicKey::from_hex(&proposal.counter_party).map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+        sol.nostr
+            .send_close_message(
+                counterparty,
+                &CloseMessage::Withdraw {
+                    contract_id: body.contract_id.clone(),
+                    signature: proposal.signature.clone(),
+                },
+            )
+            .await
+            .map_err(|e| {
+                Error::CustomError(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ErrorDetail::with_reason(e.to_string()),
+                )
+            })?;
+        withdrawn.push(close_proposals::ActiveModel::mark_withdrawn(&ctx.db, proposal).await?);
+    }
+
+    tracing::info!(
+        "Withdrew {} close proposals for {}",
+        withdrawn.len(),
+        body.contract_id
+    );
+
+    format::json(withdrawn)
+}
+
+/// Picks up the close messages sent to us over the transport.
+async fn sync_close_messages(db: &DatabaseConnection, sol: &SonsOfLiberty) -> Result<()> {
+    let since = Utc::now() - Duration::days(7);
+    let messages = sol
+        .nostr
+        .get_close_messages(nostr::Timestamp::from(since.timestamp() as u64))
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    // A proposal must be stored before a withdrawal of it is handled.
+    let mut messages = messages;
+    messages.sort_by_key(|(_, message)| !matches!(message, CloseMessage::Propose { .. }));
+    for (sender, message) in messages {
+        handle_close_message(db, sol, sender, message).await?;
+    }
+    Ok(())
+}
+
+/// Stores a proposal sent by the counterparty of a confirmed contract, drops the ones it withdrew,
+/// and closes one of our own proposals once the counterparty accepted it and its close
+/// transaction spends the funding output. Messages from anyone but the contract's counterparty
+/// are dropped.
+pub async fn handle_close_message(
+    db: &DatabaseConnection,
+    sol: &SonsOfLiberty,
+    sender: nostr::PublicKey,
+    message: CloseMessage,
+) -> Result<()> {
+    match message {
+        CloseMessage::Propose {
+            contract_id,
+            offer_payout,
+            accept_payout,
+            fee_rate,
+            signature,
+        } => {
+            if close_proposals::Model::find_by_signature(db, &signature)
+                .await?
+                .is_some()
+            {
+                return Ok(());
+            }
+            let Ok(contract) = get_confirmed_contract(sol, &contract_id).await else {
+                return Ok(());
+            };
+            if nostr_counterparty(&contract)? != sender {
+                tracing::warn!(
+                    "Dropped close proposal for {} from {}, who is not its counterparty",
+                    contract_id,
+                    sender.to_hex()
+                );
+                return Ok(());
+            }
+            let terms = CloseTerms {
+                offer_payout,
+                accept_payout,
+                fee_rate,
+            };
+            close_proposals::ActiveModel::create_proposal(
+                db,
+                &contract_id,
+                &sender.to_hex(),
+                false,
+                &terms,
+                &signature,
+            )
+            .await?;
+        }
+        CloseMessage::Accept { contract_id, txid } => {
+            // Our proposals were sent to the contract's counterparty.
+            let proposals = close_proposals::Model::signed_unclosed(db, &contract_id)
+                .await?
+                .into_iter()
+                .filter(|proposal| proposal.counter_party == sender.to_hex())
+                .collect::<Vec<_>>();
+            if proposals.is_empty() {
+                tracing::warn!(
+                    "Ignored acceptance of the close of {} from {}, who was sent no proposal",
+                    contract_id,
+                    sender.to_hex()
+                );
+                return Ok(());
+            }
+            let Ok(contract) = get_confirmed_contract(sol, &contract_id).await else {
+                return Ok(());
+            };
+            // The witness is not part of the txid, so the unsigned copy of the accepted
+            // proposal must match.
+            let accepted = proposals.into_iter().find_map(|proposal| {
+                let terms = proposal.terms();
+                let tx = close::build_close_transaction(&contract, &terms).ok()?;
+                (tx.compute_txid().to_string() == txid).then_some((proposal, terms, tx))
+            });
+            let Some((proposal, terms, tx)) = accepted else {
+                tracing::warn!(
+                    "Close of {} was accepted with unexpected txid {}",
+                    contract_id,
+                    txid
+                );
+                return Ok(());
+            };
+
+            let funding_outpoint = tx.input[0].previous_output;
+            let close_tx = match wallet::spending_transaction(sol, &funding_outpoint).await? {
+                Some(spending) if spending.compute_txid() == tx.compute_txid() => spending,
+                Some(spending) => {
+                    tracing::warn!(
+                        "Funding output of {} was spent by {} instead of the close {}",
+                        contract_id,
+                        spending.compute_txid(),
+                        txid
+                    );
+                    return Ok(());
+                }
+                // Checked again on the next sync, the message is fetched for a week.
+                None => return Ok(()),
+            };
+            mark_contract_closed(sol, &contract, &terms, Some(close_tx)).await?;
+            close_proposals::ActiveModel::mark_closed(db, proposal, &txid).await?;
+            tracing::info!("Contract {} closed cooperatively in {}", contract_id, txid);
+        }
+        CloseMessage::Withdraw {
+            contract_id,
+            signature,
+        } => {
+            let Some(proposal) = close_proposals::Model::find_by_signature(db, &signature).await?
+            else {
+                return Ok(());
+            };
+            if proposal.is_proposer
+                || proposal.contract_id != contract_id
+                || proposal.status != close_proposals::STATUS_PROPOSED
+            {
+                return Ok(());
+            }
+            if proposal.counter_party != sender.to_hex() {
+                tracing::warn!(
+                    "Ignored withdrawal of a close proposal for {} from {}, who did not send it",
+                    contract_id,
+                    sender.to_hex()
+                );
+                return Ok(());
+            }
+            close_proposals::ActiveModel::mark_withdrawn(db, proposal).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_confirmed_contract(sol: &SonsOfLiberty, contract_id: &str) -> Result<SignedContract> {
+    let id = hex::decode(contract_id)
+        .ok()
+        .and_then(|bytes| ContractId::try_from(bytes).ok())
+        .ok_or_else(|| bad_request("Contract id must be 32 hex encoded bytes".to_string()))?;
+
+    let contract = sol.dlcdevkit.storage.get_contract(&id).await.map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(e.to_string()),
+        )
+    })?;
+
+    match contract {
+        Some(Contract::Confirmed(signed)) => Ok(signed),
+        Some(_) => Err(bad_request(
+            "Only confirmed contracts can be closed cooperatively".to_string(),
+        )),
+        None => Err(Error::CustomError(
+            StatusCode::NOT_FOUND,
+            ErrorDetail::with_reason("Contract not found"),
+        )),
+    }
+}
+
+fn funding_secret_key(
+    sol: &SonsOfLiberty,
+    contract: &SignedContract,
+) -> Result<bitcoin::secp256k1::SecretKey> {
+    sol.dlcdevkit
+        .wallet
+        .derive_contract_signer(contract.accepted_contract.offered_contract.keys_id)
+        .and_then(|signer| signer.get_secret_key())
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })
+}
+
+/// The transport identifies peers by the bitcoin form of their nostr key.
+fn nostr_counterparty(contract: &SignedContract) -> Result<nostr::PublicKey> {
+    let (x_only, _) = contract
+        .accepted_contract
+        .offered_contract
+        .counter_party
+        .x_only_public_key();
+    nostr::PublicKey::from_slice(&x_only.serialize()).map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(e.to_string()),
+        )
+    })
+}
+
+/// Moves the contract to `closed` with the PnL realized by the close.
+async fn mark_contract_closed(
+    sol: &SonsOfLiberty,
+    contract: &SignedContract,
+    terms: &CloseTerms,
+    close_tx: Option<Transaction>,
+) -> Result<i64> {
+    let offered = &contract.accepted_contract.offered_contract;
+    let collateral = if offered.is_offer_party {
+        offered.offer_params.collateral
+    } else {
+        contract.accepted_contract.accept_params.collateral
+    };
+    let pnl = terms
+        .pnl(
+            offered.is_offer_party,
+            collateral,
+            close::fund_value(contract),
+        )
+        .map_err(close_error)?;
+
+    let closed = Contract::Closed(ClosedContract {
+        attestations: None,
+        signed_cet: close_tx,
+        contract_id: contract.accepted_contract.get_contract_id(),
+        temporary_contract_id: offered.id,
+        counter_party_id: offered.counter_party,
+        pnl,
+    });
+    sol.dlcdevkit
+        .storage
+        .update_contract(&closed)
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?;
+
+    Ok(pnl)
+}
+
+fn bad_request(reason: String) -> Error {
+    Error::CustomError(StatusCode::BAD_REQUEST, ErrorDetail::with_reason(reason))
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn close_error(e: close::CloseError) -> Error {
+    bad_request(e.to_string())
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("api/contracts/close/")
+        .add("/", get(index))
+        .add("/propose", post(propose))
+        .add("/accept", post(accept))
+        .add("/withdraw", post(withdraw))
+}
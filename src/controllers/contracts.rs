@@ -3,8 +3,21 @@
 #![allow(clippy::unused_async)]
 use std::sync::Arc;
 
-use crate::{common::dlcdevkit, models::users, sol::SonsOfLiberty};
+use crate::{
+    common::{dlcdevkit, preview::preview_descriptor},
+    models::{
+        contract_events,
+        contracts::{self, ContractState},
+        users,
+    },
+    sol::SonsOfLiberty,
+    views::contracts::{
+        AnnouncementDetail, AttestationDetail, ContractDetail, ContractEvent, ContractInfoDetail,
+        ContractListQuery, ContractPage, ContractTransactions,
+    },
+};
 use axum::{debug_handler, extract::Query, http::StatusCode, Extension};
+use ddk_manager::{contract::Contract, ContractId, Storage};
 use loco_rs::{controller::ErrorDetail, prelude::*};
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +32,27 @@ pub enum ContractFilter {
     Failed,
 }
 
+impl ContractFilter {
+    fn states(&self) -> Vec<ContractState> {
+        match self {
+            Self::All => Vec::new(),
+            Self::Active => vec![
+                ContractState::Accepted,
+                ContractState::Signed,
+                ContractState::Confirmed,
+                ContractState::PreClosed,
+            ],
+            Self::Closed => vec![ContractState::Closed],
+            Self::Failed => vec![
+                ContractState::Refunded,
+                ContractState::FailedAccept,
+                ContractState::FailedSign,
+                ContractState::Rejected,
+            ],
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GetContractByIdQuery {
     id: Option<String>,
@@ -29,19 +63,15 @@ pub struct GetContractByIdQuery {
 pub async fn index(
     cookie: CookieAuth,
     Query(query): Query<GetContractByIdQuery>,
-    Extension(ddk): Extension<Arc<SonsOfLiberty>>,
+    Query(list): Query<ContractListQuery>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
 
-    let contracts =
-        dlcdevkit::get_filtered_contracts(ddk.dlcdevkit.storage.clone(), query.filter).await?;
-
     if let Some(id) = query.id {
         let contract =
-            contracts
-                .iter()
-                .find(|contract| contract.id == id)
+            contracts::Model::find_row(&ctx.db, &id)
+                .await?
                 .ok_or(Error::CustomError(
                     StatusCode::NOT_FOUND,
                     ErrorDetail::with_reason("Contract not found"),
@@ -49,9 +79,201 @@ pub async fn index(
         return format::json(contract);
     }
 
-    format::json(contracts)
+    let states = match &list.state {
+        Some(states) => parse_states(states)?,
+        None => query.filter.unwrap_or(ContractFilter::All).states(),
+    };
+    let (items, next_cursor) = contracts::Model::page(&ctx.db, &list, &states)
+        .await
+        .map_err(invalid_list_query)?;
+
+    format::json(ContractPage { items, next_cursor })
+}
+
+/// Parses a comma separated list of contract states.
+pub(crate) fn parse_states(states: &str) -> Result<Vec<ContractState>> {
+    states
+        .split(',')
+        .map(str::trim)
+        .filter(|state| !state.is_empty())
+        .map(|state| {
+            ContractState::from_name(state).ok_or_else(|| {
+                Error::CustomError(
+                    StatusCode::BAD_REQUEST,
+                    ErrorDetail::with_reason(format!("Unknown contract state '{state}'")),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Invalid cursors surface as database errors from the query builder.
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn invalid_list_query(e: sea_orm::DbErr) -> Error {
+    match e {
+        sea_orm::DbErr::Custom(reason) => {
+            Error::CustomError(StatusCode::BAD_REQUEST, ErrorDetail::with_reason(reason))
+        }
+        e => Error::DB(e),
+    }
+}
+
+#[debug_handler]
+pub async fn show(
+    cookie: CookieAuth,
+    Path(id): Path<String>,
+    Extension(ddk): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let contract_id = hex::decode(&id)
+        .ok()
+        .and_then(|bytes| ContractId::try_from(bytes).ok())
+        .ok_or(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason("Contract id must be 32 hex encoded bytes"),
+        ))?;
+
+    let contract = ddk
+        .dlcdevkit
+        .storage
+        .get_contract(&contract_id)
+        .await
+        .map_err(|e| {
+            Error::CustomError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorDetail::with_reason(e.to_string()),
+            )
+        })?
+        .ok_or(Error::CustomError(
+            StatusCode::NOT_FOUND,
+            ErrorDetail::with_reason("Contract not found"),
+        ))?;
+
+    // The timeline is recorded by the sync loop, reading it never writes.
+    let timeline = contract_events::Model::timeline(&ctx.db, &id).await?;
+
+    format::json(contract_detail(&contract, timeline)?)
+}
+
+fn contract_detail(contract: &Contract, timeline: Vec<ContractEvent>) -> Result<ContractDetail> {
+    let offered = dlcdevkit::offered_contract(contract);
+    let accepted = match contract {
+        Contract::Accepted(accepted) => Some(accepted),
+        Contract::Signed(signed) | Contract::Confirmed(signed) | Contract::Refunded(signed) => {
+            Some(&signed.accepted_contract)
+        }
+        Contract::PreClosed(pre_closed) => Some(&pre_closed.signed_contract.accepted_contract),
+        Contract::FailedSign(failed) => Some(&failed.accepted_contract),
+        _ => None,
+    };
+    let (attestations, closing_tx, pnl) = match contract {
+        Contract::PreClosed(pre_closed) => (
+            pre_closed.attestations.clone().unwrap_or_default(),
+            Some(&pre_closed.signed_cet),
+            None,
+        ),
+        Contract::Closed(closed) => (
+            closed.attestations.clone().unwrap_or_default(),
+            closed.signed_cet.as_ref(),
+            Some(closed.pnl),
+        ),
+        _ => (Vec::new(), None, None),
+    };
+
+    let offer_collateral = offered.map(|offered| offered.offer_params.collateral);
+    let total_collateral = offered.map(|offered| offered.total_collateral);
+    let accept_collateral =
+        offered.map(|offered| offered.total_collateral - offered.offer_params.collateral);
+
+    let contract_infos = offered
+        .map(|offered| {
+            offered
+                .contract_info
+                .iter()
+                .map(|info| {
+                    let payouts = preview_descriptor(
+                        &info.contract_descriptor,
+                        offered.offer_params.collateral,
+                        offered.total_collateral - offered.offer_params.collateral,
+                    )?
+                    .payouts;
+                    Ok(ContractInfoDetail {
+                        descriptor: info.contract_descriptor.clone(),
+                        payouts,
+                        threshold: info.threshold,
+                        announcements: info
+                            .oracle_announcements
+                            .iter()
+                            .map(|announcement| AnnouncementDetail {
+                                event_id: announcement.oracle_event.event_id.clone(),
+                                oracle_public_key: announcement.oracle_public_key.to_string(),
+                                maturity: announcement.oracle_event.event_maturity_epoch,
+                                announcement: announcement.clone(),
+                            })
+                            .collect(),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let transactions = ContractTransactions {
+        funding_txid: accepted
+            .map(|accepted| accepted.dlc_transactions.fund.compute_txid().to_string()),
+        cet_txids: accepted
+            .map(|accepted| {
+                accepted
+                    .dlc_transactions
+                    .cets
+                    .iter()
+                    .map(|cet| cet.compute_txid().to_string())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        refund_txid: accepted
+            .map(|accepted| accepted.dlc_transactions.refund.compute_txid().to_string()),
+        closing_txid: closing_tx.map(|tx| tx.compute_txid().to_string()),
+    };
+
+    let counter_party = match contract {
+        Contract::Closed(closed) => closed.counter_party_id.to_string(),
+        _ => offered
+            .map(|offered| offered.counter_party.to_string())
+            .unwrap_or_default(),
+    };
+
+    Ok(ContractDetail {
+        id: hex::encode(contract.get_id()),
+        temporary_id: hex::encode(contract.get_temporary_id()),
+        state: dlcdevkit::contract_state(contract).to_string(),
+        counter_party,
+        is_offer_party: offered.map(|offered| offered.is_offer_party),
+        offer_collateral,
+        accept_collateral,
+        total_collateral,
+        fee_rate_per_vb: offered.map(|offered| offered.fee_rate_per_vb),
+        cet_locktime: offered.map(|offered| offered.cet_locktime),
+        refund_locktime: offered.map(|offered| offered.refund_locktime),
+        pnl,
+        contract_infos,
+        transactions,
+        attestations: attestations
+            .iter()
+            .map(|attestation| AttestationDetail {
+                oracle_public_key: attestation.oracle_public_key.to_string(),
+                outcomes: attestation.outcomes.clone(),
+            })
+            .collect(),
+        timeline,
+    })
 }
 
 pub fn routes() -> Routes {
-    Routes::new().prefix("api/contracts/").add("/", get(index))
+    Routes::new()
+        .prefix("api/contracts/")
+        .add("/", get(index))
+        .add("/{id}", get(show))
 }
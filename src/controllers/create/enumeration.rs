@@ -1,21 +1,27 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashSet, str::FromStr, sync::Arc};
 
 use crate::controllers::auth::CookieAuth;
-use crate::{models::users, sol::SonsOfLiberty};
+use crate::{
+    common::{coin_control, preview::preview_descriptor},
+    models::users,
+    sol::SonsOfLiberty,
+};
 use axum::{debug_handler, http::StatusCode, Extension, Json};
-use bitcoin::secp256k1::PublicKey;
+use bitcoin::{secp256k1::PublicKey, OutPoint};
 use ddk_manager::contract::{
-    contract_input::{ContractInput, ContractInputInfo, OracleInput},
+    contract_input::{ContractInput, ContractInputInfo},
     enum_descriptor::EnumDescriptor,
     ContractDescriptor,
 };
-use ernest_oracle::{events::EventType, routes::CreateEvent};
+use ernest_oracle::routes::CreateEvent;
 use loco_rs::{controller::ErrorDetail, prelude::*};
 use serde::{Deserialize, Serialize};
 
+use super::{create_announcements, event_ids, oracle_input};
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateEnumContract {
     counterparty: String,
@@ -24,6 +30,14 @@ pub struct CreateEnumContract {
     fee_rate: u64,
     descriptor: EnumDescriptor,
     maturity: u32,
+    /// Attestations required to close the contract. Defaults to the configured threshold.
+    oracle_threshold: Option<u16>,
+    /// Coins to fund the contract with, all of them are spent. Any spendable coin may be used
+    /// when unset, frozen coins never are.
+    funding_outpoints: Option<Vec<OutPoint>>,
+    /// Only preview the contract terms, nothing is sent to the oracle or the counterparty.
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
 }
 
 #[debug_handler]
@@ -45,41 +59,32 @@ pub async fn enum_create(
         )
     })?;
 
-    let _outcomes = body
-        .descriptor
-        .outcome_payouts
-        .iter()
-        .map(|outcome| outcome.outcome.clone())
-        .collect::<Vec<_>>();
-
-    let announcement = sol
-        .dlcdevkit
-        .oracle
-        // .create_enum_event(outcomes, body.maturity)
-        .create_event(CreateEvent::Single {
-            event_type: EventType::Hashrate,
-            maturity: body.maturity,
-        })
-        .await
-        .map_err(|e| {
-            Error::CustomError(
-                StatusCode::BAD_REQUEST,
-                ErrorDetail {
-                    error: Some(e.to_string()),
-                    description: Some("Failed to create enum event".to_string()),
-                },
-            )
-        })?;
-
-    let contract_descriptor = ContractDescriptor::Enum(body.descriptor);
+    let total_collateral = body
+        .offer_collateral
+        .checked_add(body.accept_collateral)
+        .ok_or_else(|| bad_outcome("Total collateral overflows"))?;
+    let outcomes = validate_enum_descriptor(&body.descriptor, total_collateral)?;
+
+    if body.dry_run {
+        let contract_descriptor = ContractDescriptor::Enum(body.descriptor);
+        return format::json(preview_descriptor(
+            &contract_descriptor,
+            body.offer_collateral,
+            body.accept_collateral,
+        )?);
+    }
+
+    coin_control::check_selected_coins(&ctx.db, &sol, body.funding_outpoints.as_deref()).await?;
+
+    let announcements = create_announcements(&sol, || CreateEvent::Enum {
+        outcomes: outcomes.clone(),
+        maturity: body.maturity,
+    })
+    .await?;
 
     let contract_input_info = ContractInputInfo {
-        contract_descriptor,
-        oracles: OracleInput {
-            public_keys: vec![announcement.oracle_public_key],
-            event_id: announcement.oracle_event.event_id.clone(),
-            threshold: 1,
-        },
+        contract_descriptor: ContractDescriptor::Enum(body.descriptor),
+        oracles: oracle_input(&sol, &announcements, body.oracle_threshold)?,
     };
 
     let contract_input = ContractInput {
@@ -89,22 +94,117 @@ pub async fn enum_create(
         contract_infos: vec![contract_input_info],
     };
 
-    let offer = sol
-        .dlcdevkit
-        .send_dlc_offer(&contract_input, counterparty, vec![])
-        .await
-        .map_err(|e| {
-            Error::CustomError(
-                StatusCode::BAD_REQUEST,
-                ErrorDetail {
-                    error: Some(e.to_string()),
-                    description: Some("Invalid counterparty public key".to_string()),
-                },
-            )
-        })?;
+    let offer = coin_control::send_offer(
+        &ctx.db,
+        &sol,
+        &contract_input,
+        counterparty,
+        vec![announcements.clone()],
+        body.funding_outpoints.as_deref(),
+    )
+    .await?;
 
     format::json(serde_json::json!({
         "id": hex::encode(offer.temporary_contract_id),
-        "oracle_event_id": announcement.oracle_event.event_id,
+        "oracle_event_id": announcements[0].oracle_event.event_id,
+        "oracle_event_ids": event_ids(&announcements),
     }))
 }
+
+/// Checks that every outcome of the descriptor is non-empty and unique and that each payout
+/// distributes exactly the total collateral. Returns the outcomes in descriptor order so the
+/// oracle announcement matches the contract.
+fn validate_enum_descriptor(
+    descriptor: &EnumDescriptor,
+    total_collateral: u64,
+) -> Result<Vec<String>> {
+    if descriptor.outcome_payouts.is_empty() {
+        return Err(bad_outcome(
+            "Enum descriptor must contain at least one outcome",
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut outcomes = Vec::with_capacity(descriptor.outcome_payouts.len());
+    for (index, outcome_payout) in descriptor.outcome_payouts.iter().enumerate() {
+        let outcome = &outcome_payout.outcome;
+        if outcome.trim().is_empty() {
+            return Err(bad_outcome(&format!("Outcome at index {index} is empty")));
+        }
+        if !seen.insert(outcome.as_str()) {
+            return Err(bad_outcome(&format!("Duplicate enum outcome '{outcome}'")));
+        }
+        let Some(payout_total) = outcome_payout
+            .payout
+            .offer
+            .checked_add(outcome_payout.payout.accept)
+        else {
+            return Err(bad_outcome(&format!(
+                "Payout for outcome '{outcome}' overflows"
+            )));
+        };
+        if payout_total != total_collateral {
+            return Err(bad_outcome(&format!(
+                "Payout for outcome '{outcome}' is {payout_total} sats but total collateral is {total_collateral} sats"
+            )));
+        }
+        outcomes.push(outcome.clone());
+    }
+
+    Ok(outcomes)
+}
+
+fn bad_outcome(description: &str) -> Error {
+    Error::CustomError(
+        StatusCode::BAD_REQUEST,
+        ErrorDetail {
+            error: Some("Invalid enum descriptor".to_string()),
+            description: Some(description.to_string()),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(payouts: &[(&str, u64, u64)]) -> EnumDescriptor {
+        let outcome_payouts = payouts
+            .iter()
+            .map(|(outcome, offer, accept)| {
+                serde_json::json!({
+                    "outcome": outcome,
+                    "payout": { "offer": offer, "accept": accept },
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({ "outcomePayouts": outcome_payouts })).unwrap()
+    }
+
+    #[test]
+    fn test_validate_enum_descriptor_returns_outcomes_in_order() {
+        let descriptor = descriptor(&[("yes", 100, 0), ("no", 0, 100)]);
+        let outcomes = validate_enum_descriptor(&descriptor, 100).unwrap();
+        assert_eq!(outcomes, vec!["yes".to_string(), "no".to_string()]);
+    }
+
+    #[test]
+    fn test_validate_enum_descriptor_rejects_bad_outcomes() {
+        assert!(validate_enum_descriptor(&descriptor(&[]), 100).is_err());
+        assert!(
+            validate_enum_descriptor(&descriptor(&[("yes", 100, 0), (" ", 0, 100)]), 100).is_err()
+        );
+        assert!(
+            validate_enum_descriptor(&descriptor(&[("yes", 100, 0), ("yes", 0, 100)]), 100)
+                .is_err()
+        );
+        assert!(
+            validate_enum_descriptor(&descriptor(&[("yes", 60, 0), ("no", 0, 100)]), 100).is_err()
+        );
+        assert!(validate_enum_descriptor(
+            &descriptor(&[("yes", u64::MAX, 1), ("no", 0, 100)]),
+            100
+        )
+        .is_err());
+    }
+}
@@ -1,12 +1,118 @@
-use axum::routing::post;
-use loco_rs::prelude::Routes;
+use std::str::FromStr;
+
+use axum::{http::StatusCode, routing::post};
+use bitcoin::secp256k1::PublicKey;
+use ddk::nostr::nostr_to_bitcoin_pubkey;
+use ddk_manager::contract::contract_input::OracleInput;
+use dlc_messages::oracle_msgs::OracleAnnouncement;
+use ernest_oracle::routes::CreateEvent;
+use loco_rs::{controller::ErrorDetail, prelude::*};
+
+use crate::sol::SonsOfLiberty;
 
 pub mod enumeration;
+pub mod numeric;
+pub mod options;
 pub mod parlay;
 
 pub fn routes() -> Routes {
     Routes::new()
         .prefix("api/create/")
         .add("/enum", post(enumeration::enum_create))
+        .add("/numeric", post(numeric::create_numeric_contract))
+        .add("/option", post(options::create_option_contract))
         .add("/parlay", post(parlay::create_parlay_event))
 }
+
+/// Parses a counterparty's nostr public key into the bitcoin public key used by the transport.
+pub(crate) fn parse_nostr_counterparty(counterparty: &str) -> Result<PublicKey> {
+    let nostr_pubkey = nostr::PublicKey::from_str(counterparty).map_err(|e| {
+        Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail {
+                error: Some(e.to_string()),
+                description: Some("Invalid counterparty public key".to_string()),
+            },
+        )
+    })?;
+    Ok(nostr_to_bitcoin_pubkey(&nostr_pubkey))
+}
+
+/// Creates the event on every configured oracle so the contract can be closed by any threshold of
+/// their attestations. `event` is called once per oracle.
+pub(crate) async fn create_announcements(
+    sol: &SonsOfLiberty,
+    event: impl Fn() -> CreateEvent,
+) -> Result<Vec<OracleAnnouncement>> {
+    let mut announcements = Vec::with_capacity(sol.oracles.len());
+    for oracle in &sol.oracles {
+        let event = event();
+        tracing::info!("Creating oracle event: {:?}", event);
+        let announcement = oracle.create_event(event).await.map_err(|e| {
+            Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail {
+                    error: Some(e.to_string()),
+                    description: Some("Failed to create event".to_string()),
+                },
+            )
+        })?;
+        tracing::info!(
+            "Created announcement for event: {}",
+            announcement.oracle_event.event_id
+        );
+        announcements.push(announcement);
+    }
+
+    // Every oracle has to attest at the same time for the threshold to be reachable at maturity.
+    let mut maturities = announcements
+        .iter()
+        .map(|announcement| announcement.oracle_event.event_maturity_epoch);
+    if let Some(first) = maturities.next() {
+        if maturities.any(|maturity| maturity != first) {
+            return Err(Error::CustomError(
+                StatusCode::BAD_GATEWAY,
+                ErrorDetail::with_reason("Oracles announced the event with different maturities"),
+            ));
+        }
+    }
+    Ok(announcements)
+}
+
+/// Builds the oracle input for the announcements. The threshold defaults to the configured
+/// `oracle_threshold` and must not exceed the number of oracles. Contracts with more than one
+/// announcement are closed by the sync rather than DDK, using the configured oracles only, so
+/// every oracle counted towards the threshold must stay configured until the contract closes.
+pub(crate) fn oracle_input(
+    sol: &SonsOfLiberty,
+    announcements: &[OracleAnnouncement],
+    threshold: Option<u16>,
+) -> Result<OracleInput> {
+    let threshold = threshold.unwrap_or(sol.settings.oracle_threshold);
+    if threshold == 0 || threshold as usize > announcements.len() {
+        return Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason(format!(
+                "Oracle threshold {threshold} must be between 1 and the {} oracles",
+                announcements.len()
+            )),
+        ));
+    }
+
+    Ok(OracleInput {
+        public_keys: announcements
+            .iter()
+            .map(|announcement| announcement.oracle_public_key)
+            .collect(),
+        event_id: announcements[0].oracle_event.event_id.clone(),
+        threshold,
+    })
+}
+
+/// Event ids of the announcements, in oracle order.
+pub(crate) fn event_ids(announcements: &[OracleAnnouncement]) -> Vec<String> {
+    announcements
+        .iter()
+        .map(|announcement| announcement.oracle_event.event_id.clone())
+        .collect()
+}
@@ -0,0 +1,549 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use std::sync::Arc;
+
+use crate::controllers::auth::CookieAuth;
+use crate::{
+    common::{
+        coin_control,
+        dlcdevkit::offered_contract,
+        preview::{preview_descriptor, PREVIEW_NB_DIGITS},
+    },
+    models::users,
+    sol::SonsOfLiberty,
+};
+use axum::{http::StatusCode, Extension, Json};
+use bitcoin::OutPoint;
+use ddk_manager::{
+    contract::{
+        contract_input::{ContractInput, ContractInputInfo},
+        numerical_descriptor::{DifferenceParams, NumericalDescriptor},
+        ContractDescriptor,
+    },
+    payout_curve::{
+        HyperbolicPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece, PayoutPoint,
+        PolynomialPayoutCurvePiece, RoundingInterval, RoundingIntervals,
+    },
+    Oracle, Storage,
+};
+use dlc_messages::oracle_msgs::{EventDescriptor, OracleAnnouncement};
+use dlc_trie::OracleNumericInfo;
+use ernest_oracle::{events::EventType, routes::CreateEvent};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use super::{create_announcements, event_ids, oracle_input, parse_nostr_counterparty};
+
+/// Side of the price exposure taken by the offering party.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Long,
+    Short,
+}
+
+/// Shape of the payout section between the liquidation price and the cap.
+///
+/// `Linear` pays the offer party a fixed fraction of its collateral per dollar of price move.
+/// `Hyperbolic` settles an inverse (BTC margined) contract where the position is sized in USD.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveShape {
+    Linear,
+    #[default]
+    Hyperbolic,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateNumericContract {
+    counterparty: String,
+    /// Direction of the offer party (us).
+    direction: Direction,
+    leverage: f64,
+    /// Price in USD the position is opened at.
+    entry_price: u64,
+    offer_collateral: u64,
+    accept_collateral: u64,
+    fee_rate: u64,
+    maturity: u32,
+    /// Price event the oracle attests to.
+    event_type: EventType,
+    #[serde(default)]
+    curve: CurveShape,
+    /// Payouts are rounded to a multiple of this many sats. Defaults to 0.1% of total collateral.
+    rounding_mod: Option<u64>,
+    /// Attestations required to close the contract. Defaults to the configured threshold.
+    oracle_threshold: Option<u16>,
+    /// Allowed difference between the outcomes attested by the oracles.
+    difference_params: Option<DifferenceParams>,
+    /// Coins to fund the contract with, all of them are spent. Any spendable coin may be used
+    /// when unset, frozen coins never are.
+    funding_outpoints: Option<Vec<OutPoint>>,
+    /// Only preview the contract terms, nothing is sent to the oracle or the counterparty.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub async fn create_numeric_contract(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    Json(body): Json<CreateNumericContract>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let counterparty = parse_nostr_counterparty(&body.counterparty)?;
+
+    let total_collateral = body
+        .offer_collateral
+        .checked_add(body.accept_collateral)
+        .ok_or_else(|| invalid_contract("Total collateral overflows".to_string()))?;
+    let curve = PriceCurve {
+        direction: body.direction,
+        shape: body.curve,
+        leverage: body.leverage,
+        entry_price: body.entry_price as f64,
+        offer_collateral: body.offer_collateral,
+        total_collateral,
+    };
+    curve.validate().map_err(invalid_contract)?;
+
+    let rounding_mod = body
+        .rounding_mod
+        .unwrap_or_else(|| (curve.total_collateral / 1_000).max(1));
+
+    if body.dry_run {
+        let (oracle_numeric_infos, max_outcome) =
+            preview_numeric_infos(&sol, &body.event_type).await?;
+        let contract_descriptor = price_descriptor(
+            curve
+                .payout_function(max_outcome)
+                .map_err(invalid_contract)?,
+            rounding_mod,
+            oracle_numeric_infos,
+            body.difference_params,
+        );
+        return format::json(preview_descriptor(
+            &contract_descriptor,
+            body.offer_collateral,
+            body.accept_collateral,
+        )?);
+    }
+
+    tracing::info!("Creating numeric contract offer to {}", body.counterparty);
+
+    coin_control::check_selected_coins(&ctx.db, &sol, body.funding_outpoints.as_deref()).await?;
+
+    let announcements = create_announcements(&sol, || CreateEvent::Single {
+        event_type: body.event_type.clone(),
+        maturity: body.maturity,
+    })
+    .await?;
+    let oracles = oracle_input(&sol, &announcements, body.oracle_threshold)?;
+    let (oracle_numeric_infos, max_outcome) = oracle_numeric_infos(&announcements)?;
+
+    let contract_descriptor = price_descriptor(
+        curve
+            .payout_function(max_outcome)
+            .map_err(invalid_contract)?,
+        rounding_mod,
+        oracle_numeric_infos,
+        body.difference_params,
+    );
+
+    tracing::info!("Created contract descriptor: {:?}", contract_descriptor);
+
+    let contract_input = ContractInput {
+        offer_collateral: body.offer_collateral,
+        accept_collateral: body.accept_collateral,
+        fee_rate: body.fee_rate,
+        contract_infos: vec![ContractInputInfo {
+            contract_descriptor,
+            oracles,
+        }],
+    };
+
+    let offer = coin_control::send_offer(
+        &ctx.db,
+        &sol,
+        &contract_input,
+        counterparty,
+        vec![announcements.clone()],
+        body.funding_outpoints.as_deref(),
+    )
+    .await?;
+
+    tracing::info!(
+        "Created numeric contract offer to {}: {}",
+        body.counterparty,
+        hex::encode(offer.temporary_contract_id)
+    );
+
+    format::json(serde_json::json!({
+        "id": hex::encode(offer.temporary_contract_id),
+        "oracle_event_id": announcements[0].oracle_event.event_id,
+        "oracle_event_ids": event_ids(&announcements),
+    }))
+}
+
+/// Reads the digit decomposition parameters of numeric oracle announcements. Returns the numeric
+/// infos and the largest outcome every oracle can attest to.
+pub(crate) fn oracle_numeric_infos(
+    announcements: &[OracleAnnouncement],
+) -> Result<(OracleNumericInfo, u64)> {
+    let mut base = None;
+    let mut nb_digits = Vec::with_capacity(announcements.len());
+    for announcement in announcements {
+        let EventDescriptor::DigitDecompositionEvent(descriptor) =
+            &announcement.oracle_event.event_descriptor
+        else {
+            return Err(Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail::with_reason("Oracle created an enum event for a numeric contract"),
+            ));
+        };
+        if *base.get_or_insert(descriptor.base as usize) != descriptor.base as usize {
+            return Err(Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail::with_reason("Oracles attest numeric events in different bases"),
+            ));
+        }
+        nb_digits.push(descriptor.nb_digits as usize);
+    }
+
+    let base = base.unwrap_or(2);
+    let min_nb_digits = nb_digits.iter().copied().min().unwrap_or_default();
+    Ok((
+        OracleNumericInfo { base, nb_digits },
+        max_outcome(base, min_nb_digits),
+    ))
+}
+
+/// Digit decomposition of the newest `event_type` announcement of every oracle, found in stored
+/// contracts, so previews use the digits real offers get. Assumes a single binary oracle with
+/// `PREVIEW_NB_DIGITS` digits until every oracle has announced such an event.
+pub(crate) async fn preview_numeric_infos(
+    sol: &SonsOfLiberty,
+    event_type: &EventType,
+) -> Result<(OracleNumericInfo, u64)> {
+    let fallback = (
+        OracleNumericInfo {
+            base: 2,
+            nb_digits: vec![PREVIEW_NB_DIGITS],
+        },
+        max_outcome(2, PREVIEW_NB_DIGITS),
+    );
+    // Oracle event ids start with the event type they attest to.
+    let Some(event_type) = serde_json::to_value(event_type)
+        .ok()
+        .and_then(|name| name.as_str().map(str::to_lowercase))
+    else {
+        return Ok(fallback);
+    };
+
+    let contracts = sol.dlcdevkit.storage.get_contracts().await.map_err(|e| {
+        Error::CustomError(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorDetail::with_reason(format!("Failed to get contracts: {e}")),
+        )
+    })?;
+    let announced = contracts
+        .iter()
+        .filter_map(offered_contract)
+        .flat_map(|offered| &offered.contract_info)
+        .flat_map(|info| &info.oracle_announcements)
+        .filter(|announcement| {
+            matches!(
+                announcement.oracle_event.event_descriptor,
+                EventDescriptor::DigitDecompositionEvent(_)
+            ) && announcement
+                .oracle_event
+                .event_id
+                .to_lowercase()
+                .starts_with(&event_type)
+        })
+        .collect::<Vec<_>>();
+
+    let mut newest = Vec::with_capacity(sol.oracles.len());
+    for oracle in &sol.oracles {
+        let Some(announcement) = announced
+            .iter()
+            .filter(|announcement| announcement.oracle_public_key == oracle.get_public_key())
+            .max_by_key(|announcement| announcement.oracle_event.event_maturity_epoch)
+        else {
+            return Ok(fallback);
+        };
+        newest.push((*announcement).clone());
+    }
+    if newest.is_empty() {
+        return Ok(fallback);
+    }
+    oracle_numeric_infos(&newest)
+}
+
+/// Wraps a price payout function into a numeric descriptor.
+pub(crate) fn price_descriptor(
+    payout_function: PayoutFunction,
+    rounding_mod: u64,
+    oracle_numeric_infos: OracleNumericInfo,
+    difference_params: Option<DifferenceParams>,
+) -> ContractDescriptor {
+    ContractDescriptor::Numerical(NumericalDescriptor {
+        payout_function,
+        rounding_intervals: RoundingIntervals {
+            intervals: vec![RoundingInterval {
+                begin_interval: 0,
+                rounding_mod,
+            }],
+        },
+        difference_params,
+        oracle_numeric_infos,
+    })
+}
+
+/// Largest outcome an oracle can attest to with `nb_digits` digits in `base`.
+pub(crate) fn max_outcome(base: usize, nb_digits: usize) -> u64 {
+    (base as u64).saturating_pow(nb_digits as u32) - 1
+}
+
+pub(crate) fn invalid_contract(e: String) -> Error {
+    Error::CustomError(
+        StatusCode::BAD_REQUEST,
+        ErrorDetail {
+            error: Some(e),
+            description: Some("Invalid numeric contract parameters".to_string()),
+        },
+    )
+}
+
+/// Payout curve of a leveraged price position, expressed as the offer party's payout in sats.
+///
+/// The curve is flat at the liquidation payout until the liquidation price, follows the linear
+/// or hyperbolic section, and is flat again once the whole collateral is won (the cap).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PriceCurve {
+    pub direction: Direction,
+    pub shape: CurveShape,
+    pub leverage: f64,
+    pub entry_price: f64,
+    pub offer_collateral: u64,
+    pub total_collateral: u64,
+}
+
+impl PriceCurve {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !self.leverage.is_finite() || self.leverage <= 0.0 {
+            return Err(format!("Leverage must be positive, got {}", self.leverage));
+        }
+        if self.entry_price <= 0.0 {
+            return Err("Entry price must be positive".to_string());
+        }
+        if self.offer_collateral == 0 || self.offer_collateral > self.total_collateral {
+            return Err("Offer collateral must be positive and at most the total".to_string());
+        }
+        Ok(())
+    }
+
+    /// Offer party payout at `price` before clamping to the collateral bounds.
+    pub fn payout_at(&self, price: f64) -> f64 {
+        let c = self.offer_collateral as f64;
+        let (l, e) = (self.leverage, self.entry_price);
+        match (self.shape, self.direction) {
+            (CurveShape::Linear, Direction::Long) => c * (1.0 + l * (price - e) / e),
+            (CurveShape::Linear, Direction::Short) => c * (1.0 - l * (price - e) / e),
+            (CurveShape::Hyperbolic, Direction::Long) => c * (1.0 + l) - c * l * e / price,
+            (CurveShape::Hyperbolic, Direction::Short) => c * (1.0 - l) + c * l * e / price,
+        }
+    }
+
+    /// Prices at which the offer party's payout reaches zero and the total collateral.
+    /// `f64::INFINITY` means the bound is never reached.
+    fn bound_prices(&self) -> (f64, f64) {
+        let c = self.offer_collateral as f64;
+        let t = self.total_collateral as f64;
+        let (l, e) = (self.leverage, self.entry_price);
+        match (self.shape, self.direction) {
+            (CurveShape::Linear, Direction::Long) => {
+                (e * (1.0 - 1.0 / l), e * (1.0 + (t / c - 1.0) / l))
+            }
+            (CurveShape::Linear, Direction::Short) => {
+                (e * (1.0 + 1.0 / l), e * (1.0 - (t / c - 1.0) / l))
+            }
+            (CurveShape::Hyperbolic, Direction::Long) => {
+                let denominator = c * (1.0 + l) - t;
+                let cap = if denominator > 0.0 {
+                    c * l * e / denominator
+                } else {
+                    f64::INFINITY
+                };
+                (l * e / (1.0 + l), cap)
+            }
+            (CurveShape::Hyperbolic, Direction::Short) => {
+                let liquidation = if l > 1.0 {
+                    l * e / (l - 1.0)
+                } else {
+                    f64::INFINITY
+                };
+                (liquidation, c * l * e / (t - c + c * l))
+            }
+        }
+    }
+
+    /// Outcome range covered by the linear or hyperbolic section, rounded inwards so the section
+    /// never pays less than zero or more than the total collateral.
+    pub fn section_bounds(&self, max_outcome: u64) -> (u64, u64) {
+        let (zero, total) = self.bound_prices();
+        let (low, high) = match self.direction {
+            Direction::Long => (zero, total),
+            Direction::Short => (total, zero),
+        };
+        // Hyperbolic sections are undefined at an outcome of zero.
+        let min_outcome = match self.shape {
+            CurveShape::Linear => 0.0,
+            CurveShape::Hyperbolic => 1.0,
+        };
+        let clamp = |price: f64| price.max(min_outcome).min(max_outcome as f64);
+        (clamp(low.ceil()) as u64, clamp(high.floor()) as u64)
+    }
+
+    fn payout_point(&self, outcome: u64) -> PayoutPoint {
+        let payout = self
+            .payout_at(outcome as f64)
+            .round()
+            .clamp(0.0, self.total_collateral as f64);
+        PayoutPoint {
+            event_outcome: outcome,
+            outcome_payout: payout as u64,
+            extra_precision: 0,
+        }
+    }
+
+    /// Builds the payout function over `[0, max_outcome]`.
+    pub fn payout_function(&self, max_outcome: u64) -> std::result::Result<PayoutFunction, String> {
+        let (low, high) = self.section_bounds(max_outcome);
+        if low >= high {
+            return Err(format!(
+                "Payout section collapses between outcomes {low} and {high}, check leverage and collateral"
+            ));
+        }
+
+        let low_point = self.payout_point(low);
+        let high_point = self.payout_point(high);
+        let (low_payout, high_payout) = (low_point.outcome_payout, high_point.outcome_payout);
+        let mut pieces = Vec::with_capacity(3);
+
+        if low > 0 {
+            pieces.push(flat_piece(0, low, low_payout)?);
+        }
+
+        let section = match self.shape {
+            CurveShape::Linear => PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+                PolynomialPayoutCurvePiece::new(vec![low_point, high_point])
+                    .map_err(|e| e.to_string())?,
+            ),
+            CurveShape::Hyperbolic => {
+                let c = self.offer_collateral as f64;
+                let scale = c * self.leverage * self.entry_price;
+                let (d, translate_payout) = match self.direction {
+                    Direction::Long => (-scale, c * (1.0 + self.leverage)),
+                    Direction::Short => (scale, c * (1.0 - self.leverage)),
+                };
+                // With a = 1, b = 0, c = 0 the piece evaluates to `d / outcome + translate_payout`.
+                PayoutFunctionPiece::HyperbolicPayoutCurvePiece(
+                    HyperbolicPayoutCurvePiece::new(
+                        low_point,
+                        high_point,
+                        true,
+                        0.0,
+                        translate_payout,
+                        1.0,
+                        0.0,
+                        0.0,
+                        d,
+                    )
+                    .map_err(|e| e.to_string())?,
+                )
+            }
+        };
+        pieces.push(section);
+
+        if high < max_outcome {
+            pieces.push(flat_piece(high, max_outcome, high_payout)?);
+        }
+
+        PayoutFunction::new(pieces).map_err(|e| e.to_string())
+    }
+}
+
+pub(crate) fn flat_piece(
+    start: u64,
+    end: u64,
+    payout: u64,
+) -> std::result::Result<PayoutFunctionPiece, String> {
+    Ok(PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+        PolynomialPayoutCurvePiece::new(vec![
+            PayoutPoint {
+                event_outcome: start,
+                outcome_payout: payout,
+                extra_precision: 0,
+            },
+            PayoutPoint {
+                event_outcome: end,
+                outcome_payout: payout,
+                extra_precision: 0,
+            },
+        ])
+        .map_err(|e| e.to_string())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(direction: Direction, shape: CurveShape, leverage: f64) -> PriceCurve {
+        PriceCurve {
+            direction,
+            shape,
+            leverage,
+            entry_price: 50_000.0,
+            offer_collateral: 100_000,
+            total_collateral: 200_000,
+        }
+    }
+
+    #[test]
+    fn test_payout_at_entry_is_offer_collateral() {
+        for direction in [Direction::Long, Direction::Short] {
+            for shape in [CurveShape::Linear, CurveShape::Hyperbolic] {
+                let payout = curve(direction, shape, 2.0).payout_at(50_000.0);
+                assert!((payout - 100_000.0).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_section_bounds_follow_direction() {
+        let long = curve(Direction::Long, CurveShape::Linear, 2.0);
+        assert_eq!(long.section_bounds(1 << 20), (25_000, 75_000));
+
+        let short = curve(Direction::Short, CurveShape::Linear, 2.0);
+        assert_eq!(short.section_bounds(1 << 20), (25_000, 75_000));
+
+        let hyperbolic_long = curve(Direction::Long, CurveShape::Hyperbolic, 2.0);
+        let (low, high) = hyperbolic_long.section_bounds(1 << 20);
+        assert!(hyperbolic_long.payout_at(low as f64) >= 0.0);
+        assert!(hyperbolic_long.payout_at(high as f64) <= 200_000.0);
+        assert!(low < 50_000 && high > 50_000);
+    }
+
+    #[test]
+    fn test_unreachable_bound_is_clamped_to_max_outcome() {
+        // A 1x short is never liquidated, the section runs to the largest outcome.
+        let short = curve(Direction::Short, CurveShape::Hyperbolic, 1.0);
+        let (_, high) = short.section_bounds(1_048_575);
+        assert_eq!(high, 1_048_575);
+    }
+}
@@ -0,0 +1,359 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use std::sync::Arc;
+
+use crate::controllers::auth::CookieAuth;
+use crate::{
+    common::{coin_control, preview::preview_descriptor},
+    models::users,
+    sol::SonsOfLiberty,
+};
+use axum::{Extension, Json};
+use bitcoin::OutPoint;
+use ddk_manager::{
+    contract::{
+        contract_input::{ContractInput, ContractInputInfo},
+        numerical_descriptor::DifferenceParams,
+    },
+    payout_curve::{HyperbolicPayoutCurvePiece, PayoutFunction, PayoutFunctionPiece, PayoutPoint},
+};
+use ernest_oracle::{events::EventType, routes::CreateEvent};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    create_announcements, event_ids,
+    numeric::{
+        flat_piece, invalid_contract, oracle_numeric_infos, preview_numeric_infos, price_descriptor,
+    },
+    oracle_input, parse_nostr_counterparty,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
+/// An option written by the offer party and bought by the accept party.
+///
+/// The writer locks `notional` sats, the buyer locks the `premium`. Payouts are settled in BTC,
+/// so a call pays the buyer `notional * (price - strike) / price` above the strike and a put pays
+/// `notional * (strike - price) / price` below it, capped at the writer's collateral.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOptionContract {
+    counterparty: String,
+    kind: OptionKind,
+    /// Strike price in USD.
+    strike: u64,
+    /// Premium in sats paid by the buyer.
+    premium: u64,
+    /// Notional in sats covered by the writer.
+    notional: u64,
+    /// Expiry of the option as a unix timestamp.
+    expiry: u32,
+    fee_rate: u64,
+    /// Price event the oracle attests to at expiry.
+    event_type: EventType,
+    /// Payouts are rounded to a multiple of this many sats. Defaults to 0.1% of total collateral.
+    rounding_mod: Option<u64>,
+    /// Attestations required to close the contract. Defaults to the configured threshold.
+    oracle_threshold: Option<u16>,
+    /// Allowed difference between the outcomes attested by the oracles.
+    difference_params: Option<DifferenceParams>,
+    /// Coins to fund the contract with, all of them are spent. Any spendable coin may be used
+    /// when unset, frozen coins never are.
+    funding_outpoints: Option<Vec<OutPoint>>,
+    /// Only preview the contract terms, nothing is sent to the oracle or the counterparty.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayoutPreview {
+    pub price: u64,
+    pub offer_payout: u64,
+    pub accept_payout: u64,
+}
+
+pub async fn create_option_contract(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    Json(body): Json<CreateOptionContract>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let counterparty = parse_nostr_counterparty(&body.counterparty)?;
+
+    let option = OptionContract {
+        kind: body.kind,
+        strike: body.strike,
+        premium: body.premium,
+        notional: body.notional,
+    };
+    option.validate().map_err(invalid_contract)?;
+
+    let rounding_mod = body
+        .rounding_mod
+        .unwrap_or_else(|| (option.total_collateral() / 1_000).max(1));
+
+    if body.dry_run {
+        let (oracle_numeric_infos, max_outcome) =
+            preview_numeric_infos(&sol, &body.event_type).await?;
+        let contract_descriptor = price_descriptor(
+            option
+                .payout_function(max_outcome)
+                .map_err(invalid_contract)?,
+            rounding_mod,
+            oracle_numeric_infos,
+            body.difference_params,
+        );
+        return format::json(preview_descriptor(
+            &contract_descriptor,
+            body.notional,
+            body.premium,
+        )?);
+    }
+
+    tracing::info!(
+        "Creating {:?} option offer to {}",
+        body.kind,
+        body.counterparty
+    );
+
+    coin_control::check_selected_coins(&ctx.db, &sol, body.funding_outpoints.as_deref()).await?;
+
+    let announcements = create_announcements(&sol, || CreateEvent::Single {
+        event_type: body.event_type.clone(),
+        maturity: body.expiry,
+    })
+    .await?;
+    let oracles = oracle_input(&sol, &announcements, body.oracle_threshold)?;
+    let (oracle_numeric_infos, max_outcome) = oracle_numeric_infos(&announcements)?;
+
+    let contract_descriptor = price_descriptor(
+        option
+            .payout_function(max_outcome)
+            .map_err(invalid_contract)?,
+        rounding_mod,
+        oracle_numeric_infos,
+        body.difference_params,
+    );
+
+    let contract_input = ContractInput {
+        offer_collateral: body.notional,
+        accept_collateral: body.premium,
+        fee_rate: body.fee_rate,
+        contract_infos: vec![ContractInputInfo {
+            contract_descriptor,
+            oracles,
+        }],
+    };
+
+    let offer = coin_control::send_offer(
+        &ctx.db,
+        &sol,
+        &contract_input,
+        counterparty,
+        vec![announcements.clone()],
+        body.funding_outpoints.as_deref(),
+    )
+    .await?;
+
+    tracing::info!(
+        "Created {:?} option offer to {}: {}",
+        body.kind,
+        body.counterparty,
+        hex::encode(offer.temporary_contract_id)
+    );
+
+    format::json(serde_json::json!({
+        "id": hex::encode(offer.temporary_contract_id),
+        "oracle_event_id": announcements[0].oracle_event.event_id,
+        "oracle_event_ids": event_ids(&announcements),
+        "preview": option.preview(max_outcome),
+    }))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OptionContract {
+    pub kind: OptionKind,
+    pub strike: u64,
+    pub premium: u64,
+    pub notional: u64,
+}
+
+impl OptionContract {
+    pub fn total_collateral(&self) -> u64 {
+        self.notional + self.premium
+    }
+
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.notional == 0 {
+            return Err("Notional must be positive".to_string());
+        }
+        // The put payout is capped at half the strike, which needs a strike of at least 2.
+        if self.strike < 2 {
+            return Err(format!("Strike must be at least 2, got {}", self.strike));
+        }
+        Ok(())
+    }
+
+    /// Price below which a put pays out the writer's whole collateral.
+    fn put_floor(&self) -> u64 {
+        self.strike.div_ceil(2)
+    }
+
+    /// Writer (offer party) payout at `price`, clamped to the collateral bounds.
+    pub fn writer_payout_at(&self, price: u64) -> u64 {
+        let total = self.total_collateral() as f64;
+        let notional = self.notional as f64;
+        let strike = self.strike as f64;
+        let price = price as f64;
+        let payout = match self.kind {
+            OptionKind::Call if price <= strike => total,
+            OptionKind::Call => self.premium as f64 + notional * strike / price,
+            OptionKind::Put if price >= strike => total,
+            OptionKind::Put if price <= 0.0 => self.premium as f64,
+            OptionKind::Put => total + notional - notional * strike / price,
+        };
+        payout.round().clamp(self.premium as f64, total) as u64
+    }
+
+    fn payout_point(&self, outcome: u64) -> PayoutPoint {
+        PayoutPoint {
+            event_outcome: outcome,
+            outcome_payout: self.writer_payout_at(outcome),
+            extra_precision: 0,
+        }
+    }
+
+    /// Builds the writer's payout function over `[0, max_outcome]`.
+    pub fn payout_function(&self, max_outcome: u64) -> std::result::Result<PayoutFunction, String> {
+        if self.strike >= max_outcome {
+            return Err(format!(
+                "Strike {} is outside the oracle range of 0 to {max_outcome}",
+                self.strike
+            ));
+        }
+
+        let total = self.total_collateral();
+        let notional = self.notional as f64;
+        let strike = self.strike as f64;
+
+        // With a = 1, b = 0, c = 0 the hyperbolic piece evaluates to `d / outcome + translate_payout`.
+        let pieces = match self.kind {
+            OptionKind::Call => vec![
+                flat_piece(0, self.strike, total)?,
+                PayoutFunctionPiece::HyperbolicPayoutCurvePiece(
+                    HyperbolicPayoutCurvePiece::new(
+                        self.payout_point(self.strike),
+                        self.payout_point(max_outcome),
+                        true,
+                        0.0,
+                        self.premium as f64,
+                        1.0,
+                        0.0,
+                        0.0,
+                        notional * strike,
+                    )
+                    .map_err(|e| e.to_string())?,
+                ),
+            ],
+            OptionKind::Put => {
+                let floor = self.put_floor();
+                vec![
+                    flat_piece(0, floor, self.writer_payout_at(floor))?,
+                    PayoutFunctionPiece::HyperbolicPayoutCurvePiece(
+                        HyperbolicPayoutCurvePiece::new(
+                            self.payout_point(floor),
+                            self.payout_point(self.strike),
+                            true,
+                            0.0,
+                            total as f64 + notional,
+                            1.0,
+                            0.0,
+                            0.0,
+                            -notional * strike,
+                        )
+                        .map_err(|e| e.to_string())?,
+                    ),
+                    flat_piece(self.strike, max_outcome, total)?,
+                ]
+            }
+        };
+
+        PayoutFunction::new(pieces).map_err(|e| e.to_string())
+    }
+
+    /// Payouts at zero, around the strike and at the top of the oracle range.
+    pub fn preview(&self, max_outcome: u64) -> Vec<PayoutPreview> {
+        let total = self.total_collateral();
+        let mut prices = [0, 50, 75, 90, 100, 110, 125, 150, 200]
+            .iter()
+            .map(|percent| self.strike * percent / 100)
+            .chain(std::iter::once(max_outcome))
+            .filter(|price| *price <= max_outcome)
+            .collect::<Vec<_>>();
+        prices.dedup();
+
+        prices
+            .into_iter()
+            .map(|price| {
+                let offer_payout = self.writer_payout_at(price);
+                PayoutPreview {
+                    price,
+                    offer_payout,
+                    accept_payout: total - offer_payout,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(kind: OptionKind) -> OptionContract {
+        OptionContract {
+            kind,
+            strike: 100_000,
+            premium: 1_000,
+            notional: 100_000,
+        }
+    }
+
+    #[test]
+    fn test_call_payouts() {
+        let call = option(OptionKind::Call);
+        assert_eq!(call.writer_payout_at(0), 101_000);
+        assert_eq!(call.writer_payout_at(100_000), 101_000);
+        // At twice the strike the buyer receives half the notional.
+        assert_eq!(call.writer_payout_at(200_000), 51_000);
+    }
+
+    #[test]
+    fn test_put_payouts() {
+        let put = option(OptionKind::Put);
+        assert_eq!(put.writer_payout_at(200_000), 101_000);
+        assert_eq!(put.writer_payout_at(100_000), 101_000);
+        // Below half the strike the buyer receives the whole notional.
+        assert_eq!(put.writer_payout_at(50_000), 1_000);
+        assert_eq!(put.writer_payout_at(10_000), 1_000);
+    }
+
+    #[test]
+    fn test_preview_splits_total_collateral() {
+        let preview = option(OptionKind::Call).preview(1_048_575);
+        assert_eq!(preview.first().unwrap().price, 0);
+        assert_eq!(preview.last().unwrap().price, 1_048_575);
+        assert!(preview
+            .iter()
+            .all(|point| point.offer_payout + point.accept_payout == 101_000));
+    }
+}
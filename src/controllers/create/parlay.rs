@@ -1,16 +1,20 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
-use std::{str::FromStr, sync::Arc};
+use std::sync::Arc;
 
 use crate::controllers::auth::CookieAuth;
-use crate::{models::users, sol::SonsOfLiberty};
+use crate::{
+    common::{coin_control, preview::preview_descriptor},
+    models::users,
+    sol::SonsOfLiberty,
+};
 use axum::{http::StatusCode, Extension, Json};
-use ddk::nostr::nostr_to_bitcoin_pubkey;
+use bitcoin::OutPoint;
 use ddk_manager::{
     contract::{
-        contract_input::{ContractInput, ContractInputInfo, OracleInput},
-        numerical_descriptor::NumericalDescriptor,
+        contract_input::{ContractInput, ContractInputInfo},
+        numerical_descriptor::{DifferenceParams, NumericalDescriptor},
         ContractDescriptor,
     },
     payout_curve::{
@@ -24,6 +28,8 @@ use ernest_oracle::{parlay::ParlayParameter, routes::CreateEvent};
 use loco_rs::{controller::ErrorDetail, prelude::*};
 use serde::{Deserialize, Serialize};
 
+use super::{create_announcements, event_ids, oracle_input, parse_nostr_counterparty};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateParlayEvent {
@@ -36,6 +42,16 @@ pub struct CreateParlayEvent {
     offer_collateral: u64,
     accept_collateral: u64,
     fee_rate: u64,
+    /// Attestations required to close the contract. Defaults to the configured threshold.
+    oracle_threshold: Option<u16>,
+    /// Allowed difference between the outcomes attested by the oracles.
+    difference_params: Option<DifferenceParams>,
+    /// Coins to fund the contract with, all of them are spent. Any spendable coin may be used
+    /// when unset, frozen coins never are.
+    funding_outpoints: Option<Vec<OutPoint>>,
+    /// Only preview the contract terms, nothing is sent to the oracle or the counterparty.
+    #[serde(default)]
+    dry_run: bool,
 }
 
 pub async fn create_parlay_event(
@@ -46,41 +62,7 @@ pub async fn create_parlay_event(
 ) -> Result<Response> {
     users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
 
-    let counterparty =
-        nostr_to_bitcoin_pubkey(&nostr::PublicKey::from_str(&body.counterparty).unwrap());
-
-    let event = CreateEvent::Parlay {
-        parameters: body.parlay_parameters,
-        combination_method: body.combination_method,
-        max_normalized_value: body.max_normalized_value,
-        event_maturity_epoch: body.event_maturity_epoch,
-    };
-
-    tracing::info!(
-        "Creating contract offer to {}: {:?}",
-        body.counterparty.to_string(),
-        event
-    );
-
-    let announcement = sol
-        .dlcdevkit
-        .oracle
-        .create_event(event)
-        .await
-        .map_err(|e| {
-            Error::CustomError(
-                StatusCode::BAD_REQUEST,
-                ErrorDetail {
-                    error: Some(e.to_string()),
-                    description: Some("Failed to create event".to_string()),
-                },
-            )
-        })?;
-
-    tracing::info!(
-        "Created announcement for event: {}",
-        announcement.oracle_event.event_id
-    );
+    let counterparty = parse_nostr_counterparty(&body.counterparty)?;
 
     let max_normalized_value = body.max_normalized_value.unwrap_or(10_000);
     let (nb_digits, max_oracle_value) =
@@ -138,32 +120,55 @@ pub async fn create_parlay_event(
 
     tracing::info!("Created payout function");
 
-    let oracle_numeric_infos = OracleNumericInfo {
-        base: 2,
-        nb_digits: vec![nb_digits as usize],
+    let payout_function = PayoutFunction::new(vec![payout_curve, upper_bound]).unwrap();
+    let descriptor = |nb_oracles: usize| {
+        ContractDescriptor::Numerical(NumericalDescriptor {
+            payout_function: payout_function.clone(),
+            rounding_intervals: RoundingIntervals {
+                intervals: vec![RoundingInterval {
+                    begin_interval: 0,
+                    rounding_mod: 1,
+                }],
+            },
+            difference_params: body.difference_params.clone(),
+            oracle_numeric_infos: OracleNumericInfo {
+                base: 2,
+                nb_digits: vec![nb_digits as usize; nb_oracles],
+            },
+        })
     };
 
-    let contract_descriptor = ContractDescriptor::Numerical(NumericalDescriptor {
-        payout_function: PayoutFunction::new(vec![payout_curve, upper_bound]).unwrap(),
-        rounding_intervals: RoundingIntervals {
-            intervals: vec![RoundingInterval {
-                begin_interval: 0,
-                rounding_mod: 1,
-            }],
-        },
-        difference_params: None,
-        oracle_numeric_infos,
-    });
+    if body.dry_run {
+        return format::json(preview_descriptor(
+            &descriptor(1),
+            body.offer_collateral,
+            body.accept_collateral,
+        )?);
+    }
+
+    tracing::info!(
+        "Creating contract offer to {}: {:?}",
+        body.counterparty.to_string(),
+        body.parlay_parameters
+    );
+
+    coin_control::check_selected_coins(&ctx.db, &sol, body.funding_outpoints.as_deref()).await?;
+
+    let announcements = create_announcements(&sol, || CreateEvent::Parlay {
+        parameters: body.parlay_parameters.clone(),
+        combination_method: body.combination_method.clone(),
+        max_normalized_value: body.max_normalized_value,
+        event_maturity_epoch: body.event_maturity_epoch,
+    })
+    .await?;
+
+    let contract_descriptor = descriptor(announcements.len());
 
     tracing::info!("Created contract descriptor: {:?}", contract_descriptor);
 
     let contract_input_info = ContractInputInfo {
         contract_descriptor,
-        oracles: OracleInput {
-            public_keys: vec![announcement.oracle_public_key],
-            event_id: announcement.oracle_event.event_id.clone(),
-            threshold: 1,
-        },
+        oracles: oracle_input(&sol, &announcements, body.oracle_threshold)?,
     };
 
     let contract_input = ContractInput {
@@ -178,24 +183,15 @@ pub async fn create_parlay_event(
         body.counterparty.to_string()
     );
 
-    let offer = sol
-        .dlcdevkit
-        .manager
-        .send_offer_with_announcements(
-            &contract_input,
-            counterparty,
-            vec![vec![announcement.clone()]],
-        )
-        .await
-        .map_err(|e| {
-            Error::CustomError(
-                StatusCode::BAD_REQUEST,
-                ErrorDetail {
-                    error: Some(e.to_string()),
-                    description: Some("Invalid counterparty public key".to_string()),
-                },
-            )
-        })?;
+    let offer = coin_control::send_offer(
+        &ctx.db,
+        &sol,
+        &contract_input,
+        counterparty,
+        vec![announcements.clone()],
+        body.funding_outpoints.as_deref(),
+    )
+    .await?;
 
     tracing::info!(
         "Created contract offer to {}: {}",
@@ -205,6 +201,7 @@ pub async fn create_parlay_event(
 
     format::json(serde_json::json!({
         "id": hex::encode(offer.temporary_contract_id),
-        "oracle_event_id": announcement.oracle_event.event_id,
+        "oracle_event_id": announcements[0].oracle_event.event_id,
+        "oracle_event_ids": event_ids(&announcements),
     }))
 }
@@ -0,0 +1,44 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+use std::sync::Arc;
+
+use axum::{
+    debug_handler,
+    response::sse::{Event, KeepAlive, Sse},
+    Extension,
+};
+use loco_rs::prelude::*;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt,
+};
+
+use crate::{models::users, sol::SonsOfLiberty};
+
+use super::auth::CookieAuth;
+
+/// Streams node events as server-sent events, named after their `type`. Events are found by
+/// comparing storage after each sync. A `lagged` event with the number of events missed tells a
+/// slow client to refetch what it shows.
+#[debug_handler]
+pub async fn index(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let stream = BroadcastStream::new(sol.events.subscribe()).map(|event| match event {
+        Ok(event) => Event::default().event(event.name()).json_data(&event),
+        Err(BroadcastStreamRecvError::Lagged(missed)) => {
+            Ok(Event::default().event("lagged").data(missed.to_string()))
+        }
+    });
+    Ok(Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+pub fn routes() -> Routes {
+    Routes::new().prefix("api/events/").add("/", get(index))
+}
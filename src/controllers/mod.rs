@@ -1,11 +1,15 @@
 pub mod auth;
 
 pub mod balance;
+pub mod close;
 pub mod contracts;
+pub mod events;
 pub mod info;
 pub mod offers;
 pub mod peers;
+pub mod seed;
 pub mod wallet;
+pub mod webhooks;
 
 // This is an example of how to nest routes.
 // fn routes() -> Vec<Routes> {
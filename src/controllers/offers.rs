@@ -3,15 +3,31 @@
 #![allow(clippy::unused_async)]
 use std::{str::FromStr, sync::Arc};
 
-use crate::{common::dlcdevkit, models::users, sol::SonsOfLiberty};
+use crate::{
+    common::{coin_control, dlcdevkit, offer_expiry, preview::preview_descriptor},
+    models::{
+        contracts::{self, ContractState},
+        users,
+    },
+    sol::SonsOfLiberty,
+    views::contracts::{ContractListQuery, ContractPage},
+};
 use axum::{debug_handler, extract::Query, http::StatusCode, Extension};
-use bitcoin::secp256k1::PublicKey;
-use ddk_manager::contract::contract_input::ContractInput;
+use bitcoin::{secp256k1::PublicKey, OutPoint};
+use ddk_manager::{
+    contract::{
+        contract_input::{ContractInput, ContractInputInfo, OracleInput},
+        numerical_descriptor::NumericalDescriptor,
+        ContractDescriptor,
+    },
+    payout_curve::{PayoutFunction, PayoutFunctionPiece, PayoutPoint, PolynomialPayoutCurvePiece},
+    ContractId,
+};
 use dlc_messages::{oracle_msgs::OracleAnnouncement, AcceptDlc};
 use loco_rs::{controller::ErrorDetail, prelude::*};
 use serde::{Deserialize, Serialize};
 
-use super::auth::CookieAuth;
+use super::{auth::CookieAuth, contracts::invalid_list_query};
 
 #[derive(Debug, Deserialize)]
 pub struct GetOfferByIdQuery {
@@ -22,25 +38,27 @@ pub struct GetOfferByIdQuery {
 pub async fn index(
     cookie: CookieAuth,
     Query(query): Query<GetOfferByIdQuery>,
-    Extension(ddk): Extension<Arc<SonsOfLiberty>>,
+    Query(list): Query<ContractListQuery>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
 
-    let offers = dlcdevkit::get_offers(ddk.dlcdevkit.storage.clone()).await?;
-
     if let Some(id) = query.id {
-        let offer = offers
-            .iter()
-            .find(|offer| offer.id == id)
+        let offer = contracts::Model::find_row(&ctx.db, &id)
+            .await?
+            .filter(|offer| offer.state == ContractState::Offered.name())
             .ok_or(Error::CustomError(
                 StatusCode::NOT_FOUND,
                 ErrorDetail::with_reason("Offer not found"),
             ))?;
-        format::json(offer)
-    } else {
-        format::json(offers)
+        return format::json(offer);
     }
+
+    let (items, next_cursor) = contracts::Model::page(&ctx.db, &list, &[ContractState::Offered])
+        .await
+        .map_err(invalid_list_query)?;
+
+    format::json(ContractPage { items, next_cursor })
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -48,6 +66,12 @@ pub struct SendOfferBody {
     contract_input: ContractInput,
     counter_party: String,
     oracle_announcements: Vec<OracleAnnouncement>,
+    /// Coins to fund the contract with, all of them are spent. Any spendable coin may be used
+    /// when unset, frozen coins never are.
+    funding_outpoints: Option<Vec<OutPoint>>,
+    /// Only preview the contract terms, nothing is sent to the counterparty.
+    #[serde(default, rename = "dryRun")]
+    dry_run: bool,
 }
 
 #[debug_handler]
@@ -66,20 +90,34 @@ pub async fn send_offer(
         )
     })?;
 
-    let offer = ddk
-        .dlcdevkit
-        .send_dlc_offer(
-            &body.contract_input,
-            counter_party,
-            body.oracle_announcements,
-        )
-        .await
-        .map_err(|e| {
-            Error::CustomError(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                ErrorDetail::with_reason(e.to_string()),
-            )
-        })?;
+    if body.dry_run {
+        let previews = body
+            .contract_input
+            .contract_infos
+            .iter()
+            .map(|info| {
+                preview_descriptor(
+                    &info.contract_descriptor,
+                    body.contract_input.offer_collateral,
+                    body.contract_input.accept_collateral,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return format::json(previews);
+    }
+
+    let selected = body.funding_outpoints.as_deref();
+    coin_control::check_selected_coins(&ctx.db, &ddk, selected).await?;
+
+    let offer = coin_control::send_offer(
+        &ctx.db,
+        &ddk,
+        &body.contract_input,
+        counter_party,
+        vec![body.oracle_announcements],
+        selected,
+    )
+    .await?;
 
     format::json(offer)
 }
@@ -87,6 +125,9 @@ pub async fn send_offer(
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AcceptOfferBody {
     offer_id: String,
+    /// Coins to fund the contract with, all of them are spent. Any spendable coin may be used
+    /// when unset, frozen coins never are.
+    funding_outpoints: Option<Vec<OutPoint>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -105,32 +146,233 @@ pub async fn accept_offer(
 ) -> Result<Response> {
     users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
 
-    let offer_id_bytes = hex::decode(&body.offer_id).map_err(|e| {
+    let offer_id = parse_offer_id(&body.offer_id)?;
+    let offered = dlcdevkit::get_received_offer(&ddk, &offer_id).await?;
+    offer_expiry::ensure_offer_acceptable(&ctx.db, &ddk, &offered).await?;
+    let selected = body.funding_outpoints.as_deref();
+    coin_control::check_selected_coins(&ctx.db, &ddk, selected).await?;
+
+    let (contract_id, accept_dlc) =
+        coin_control::accept_offer(&ctx.db, &ddk, &offered, selected).await?;
+
+    format::json(AcceptOfferResponse {
+        contract_id: hex::encode(contract_id),
+        counter_party: offered.counter_party.to_string(),
+        accept_dlc,
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RejectOfferBody {
+    offer_id: String,
+}
+
+#[debug_handler]
+pub async fn reject_offer(
+    cookie: CookieAuth,
+    Extension(ddk): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<RejectOfferBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let offer_id = parse_offer_id(&body.offer_id)?;
+    let offered = dlcdevkit::get_received_offer(&ddk, &offer_id).await?;
+    dlcdevkit::reject_offer(&ddk, offered).await?;
+
+    format::json(serde_json::json!({
+        "offer_id": body.offer_id,
+        "rejected": true,
+    }))
+}
+
+/// New terms for a received offer. Unset fields keep the original terms, seen from our side.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CounterOfferBody {
+    offer_id: String,
+    /// Our collateral. Defaults to the collateral the offer asked us to lock.
+    offer_collateral: Option<u64>,
+    /// The original offerer's collateral. Defaults to what they offered to lock.
+    accept_collateral: Option<u64>,
+    fee_rate: Option<u64>,
+    /// Coins to fund the contract with, all of them are spent. Any spendable coin may be used
+    /// when unset, frozen coins never are.
+    funding_outpoints: Option<Vec<OutPoint>>,
+}
+
+#[debug_handler]
+pub async fn counter_offer(
+    cookie: CookieAuth,
+    Extension(ddk): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<CounterOfferBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let offer_id = parse_offer_id(&body.offer_id)?;
+    let offered = dlcdevkit::get_received_offer(&ddk, &offer_id).await?;
+
+    let original_offer_collateral = offered.offer_params.collateral;
+    let original_accept_collateral = offered.total_collateral - original_offer_collateral;
+    let offer_collateral = body.offer_collateral.unwrap_or(original_accept_collateral);
+    let accept_collateral = body.accept_collateral.unwrap_or(original_offer_collateral);
+    let fee_rate = body.fee_rate.unwrap_or(offered.fee_rate_per_vb);
+
+    let [contract_info] = offered.contract_info.as_slice() else {
+        return Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason("Only offers with a single contract can be countered"),
+        ));
+    };
+
+    let total_collateral = offer_collateral
+        .checked_add(accept_collateral)
+        .ok_or_else(|| {
+            Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail {
+                    error: Some("Total collateral overflows".to_string()),
+                    description: Some("Failed to build counter offer".to_string()),
+                },
+            )
+        })?;
+    let contract_descriptor = counter_descriptor(
+        &contract_info.contract_descriptor,
+        offered.total_collateral,
+        total_collateral,
+    )
+    .map_err(|e| {
         Error::CustomError(
             StatusCode::BAD_REQUEST,
-            ErrorDetail::with_reason(e.to_string()),
+            ErrorDetail {
+                error: Some(e),
+                description: Some("Failed to build counter offer".to_string()),
+            },
         )
     })?;
 
-    let mut offer_id = [0u8; 32];
-    offer_id.copy_from_slice(&offer_id_bytes);
+    let selected = body.funding_outpoints.as_deref();
+    coin_control::check_selected_coins(&ctx.db, &ddk, selected).await?;
 
-    let accept = ddk
-        .dlcdevkit
-        .accept_dlc_offer(offer_id)
-        .await
-        .map_err(|e| {
+    let announcements = contract_info.oracle_announcements.clone();
+    let contract_input = ContractInput {
+        offer_collateral,
+        accept_collateral,
+        fee_rate,
+        contract_infos: vec![ContractInputInfo {
+            contract_descriptor,
+            oracles: OracleInput {
+                public_keys: announcements
+                    .iter()
+                    .map(|announcement| announcement.oracle_public_key)
+                    .collect(),
+                event_id: announcements[0].oracle_event.event_id.clone(),
+                threshold: contract_info.threshold as u16,
+            },
+        }],
+    };
+
+    let offer = coin_control::send_offer(
+        &ctx.db,
+        &ddk,
+        &contract_input,
+        offered.counter_party,
+        vec![announcements],
+        selected,
+    )
+    .await?;
+
+    dlcdevkit::reject_offer(&ddk, offered).await?;
+
+    format::json(serde_json::json!({
+        "id": hex::encode(offer.temporary_contract_id),
+        "rejected_offer_id": body.offer_id,
+    }))
+}
+
+fn parse_offer_id(offer_id: &str) -> Result<ContractId> {
+    hex::decode(offer_id)
+        .ok()
+        .and_then(|bytes| ContractId::try_from(bytes).ok())
+        .ok_or_else(|| {
             Error::CustomError(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                ErrorDetail::with_reason(e.to_string()),
+                StatusCode::BAD_REQUEST,
+                ErrorDetail::with_reason("Offer id must be 32 hex encoded bytes"),
             )
-        })?;
+        })
+}
 
-    format::json(AcceptOfferResponse {
-        contract_id: accept.0,
-        counter_party: accept.1,
-        accept_dlc: accept.2,
-    })
+/// Copies a received contract descriptor with the roles swapped, so our payouts as the new offer
+/// party are what we would have received as the accept party. Payouts are scaled from the
+/// original total collateral to `total_collateral`.
+fn counter_descriptor(
+    descriptor: &ContractDescriptor,
+    original_total: u64,
+    total_collateral: u64,
+) -> std::result::Result<ContractDescriptor, String> {
+    if original_total == 0 || total_collateral == 0 {
+        return Err("Total collateral must be positive".to_string());
+    }
+    let scale = |payout: u64| {
+        u64::try_from(
+            u128::from(payout) * u128::from(total_collateral) / u128::from(original_total),
+        )
+        .unwrap_or(total_collateral)
+    };
+
+    match descriptor {
+        ContractDescriptor::Enum(enum_descriptor) => {
+            let mut enum_descriptor = enum_descriptor.clone();
+            for outcome_payout in &mut enum_descriptor.outcome_payouts {
+                let offer = scale(outcome_payout.payout.accept);
+                outcome_payout.payout.offer = offer;
+                outcome_payout.payout.accept = total_collateral - offer;
+            }
+            Ok(ContractDescriptor::Enum(enum_descriptor))
+        }
+        ContractDescriptor::Numerical(numerical) => {
+            let range_payouts = numerical
+                .payout_function
+                .to_range_payouts(original_total, &numerical.rounding_intervals)
+                .map_err(|e| e.to_string())?;
+
+            // Each range is flat, consecutive ranges are joined by a one outcome step.
+            let mut points: Vec<PayoutPoint> = Vec::new();
+            for range in &range_payouts {
+                let payout = scale(range.payout.accept);
+                let start = range.start as u64;
+                let end = (range.start + range.count - 1) as u64;
+                for outcome in [start, end] {
+                    if points.last().map(|point| point.event_outcome) != Some(outcome) {
+                        points.push(PayoutPoint {
+                            event_outcome: outcome,
+                            outcome_payout: payout,
+                            extra_precision: 0,
+                        });
+                    }
+                }
+            }
+            if points.len() < 2 {
+                return Err("Numeric contract must cover at least two outcomes".to_string());
+            }
+
+            let pieces = points
+                .windows(2)
+                .map(|pair| {
+                    PolynomialPayoutCurvePiece::new(pair.to_vec())
+                        .map(PayoutFunctionPiece::PolynomialPayoutCurvePiece)
+                        .map_err(|e| e.to_string())
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            Ok(ContractDescriptor::Numerical(NumericalDescriptor {
+                payout_function: PayoutFunction::new(pieces).map_err(|e| e.to_string())?,
+                rounding_intervals: numerical.rounding_intervals.clone(),
+                difference_params: numerical.difference_params.clone(),
+                oracle_numeric_infos: numerical.oracle_numeric_infos.clone(),
+            }))
+        }
+    }
 }
 
 pub fn routes() -> Routes {
@@ -139,4 +381,137 @@ pub fn routes() -> Routes {
         .add("/", get(index))
         .add("/", post(send_offer))
         .add("/accept", post(accept_offer))
+        .add("/reject", post(reject_offer))
+        .add("/counter", post(counter_offer))
+}
+
+#[cfg(test)]
+mod tests {
+    use ddk_manager::payout_curve::{RoundingInterval, RoundingIntervals};
+    use dlc_trie::OracleNumericInfo;
+
+    use super::*;
+
+    fn enum_descriptor() -> ContractDescriptor {
+        ContractDescriptor::Enum(
+            serde_json::from_value(serde_json::json!({
+                "outcomePayouts": [
+                    { "outcome": "yes", "payout": { "offer": 1_000, "accept": 0 } },
+                    { "outcome": "no", "payout": { "offer": 250, "accept": 750 } },
+                ]
+            }))
+            .unwrap(),
+        )
+    }
+
+    /// Pays the offer party nothing up to outcome 511 and the whole 1000 sats from 512 on.
+    fn numeric_descriptor() -> ContractDescriptor {
+        let piece = |points: [(u64, u64); 2]| {
+            let points = points
+                .iter()
+                .map(|(event_outcome, outcome_payout)| PayoutPoint {
+                    event_outcome: *event_outcome,
+                    outcome_payout: *outcome_payout,
+                    extra_precision: 0,
+                })
+                .collect();
+            PayoutFunctionPiece::PolynomialPayoutCurvePiece(
+                PolynomialPayoutCurvePiece::new(points).unwrap(),
+            )
+        };
+        ContractDescriptor::Numerical(NumericalDescriptor {
+            payout_function: PayoutFunction::new(vec![
+                piece([(0, 0), (511, 0)]),
+                piece([(511, 0), (512, 1_000)]),
+                piece([(512, 1_000), (1_023, 1_000)]),
+            ])
+            .unwrap(),
+            rounding_intervals: RoundingIntervals {
+                intervals: vec![RoundingInterval {
+                    begin_interval: 0,
+                    rounding_mod: 1,
+                }],
+            },
+            difference_params: None,
+            oracle_numeric_infos: OracleNumericInfo {
+                base: 2,
+                nb_digits: vec![10],
+            },
+        })
+    }
+
+    /// Offer and accept payouts at `outcome`.
+    fn payouts_at(descriptor: &ContractDescriptor, total: u64, outcome: usize) -> (u64, u64) {
+        let ContractDescriptor::Numerical(numerical) = descriptor else {
+            panic!("expected numeric descriptor");
+        };
+        let ranges = numerical
+            .payout_function
+            .to_range_payouts(total, &numerical.rounding_intervals)
+            .unwrap();
+        let range = ranges
+            .iter()
+            .find(|range| range.start <= outcome && outcome < range.start + range.count)
+            .unwrap();
+        (range.payout.offer, range.payout.accept)
+    }
+
+    #[test]
+    fn test_counter_numeric_swaps_roles() {
+        let counter = counter_descriptor(&numeric_descriptor(), 1_000, 1_000).unwrap();
+        assert_eq!(payouts_at(&counter, 1_000, 0), (1_000, 0));
+        assert_eq!(payouts_at(&counter, 1_000, 511), (1_000, 0));
+        assert_eq!(payouts_at(&counter, 1_000, 512), (0, 1_000));
+        assert_eq!(payouts_at(&counter, 1_000, 1_023), (0, 1_000));
+
+        let ContractDescriptor::Numerical(numerical) = &counter else {
+            panic!("expected numeric descriptor");
+        };
+        assert_eq!(numerical.oracle_numeric_infos.nb_digits, vec![10]);
+    }
+
+    #[test]
+    fn test_counter_numeric_scales_collateral() {
+        let counter = counter_descriptor(&numeric_descriptor(), 1_000, 3_000).unwrap();
+        assert_eq!(payouts_at(&counter, 3_000, 0), (3_000, 0));
+        assert_eq!(payouts_at(&counter, 3_000, 1_023), (0, 3_000));
+    }
+
+    #[test]
+    fn test_dry_run_rejects_overflowing_collateral() {
+        assert!(matches!(
+            preview_descriptor(&enum_descriptor(), u64::MAX, 1),
+            Err(Error::CustomError(StatusCode::BAD_REQUEST, _))
+        ));
+    }
+
+    #[test]
+    fn test_counter_rejects_zero_collateral() {
+        assert!(counter_descriptor(&numeric_descriptor(), 1_000, 0).is_err());
+        assert!(counter_descriptor(&enum_descriptor(), 0, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_counter_enum_swaps_roles() {
+        let ContractDescriptor::Enum(counter) =
+            counter_descriptor(&enum_descriptor(), 1_000, 1_000).unwrap()
+        else {
+            panic!("expected enum descriptor");
+        };
+        assert_eq!(counter.outcome_payouts[0].payout.offer, 0);
+        assert_eq!(counter.outcome_payouts[0].payout.accept, 1_000);
+        assert_eq!(counter.outcome_payouts[1].payout.offer, 750);
+        assert_eq!(counter.outcome_payouts[1].payout.accept, 250);
+    }
+
+    #[test]
+    fn test_counter_enum_scales_collateral() {
+        let ContractDescriptor::Enum(counter) =
+            counter_descriptor(&enum_descriptor(), 1_000, 2_000).unwrap()
+        else {
+            panic!("expected enum descriptor");
+        };
+        assert_eq!(counter.outcome_payouts[1].payout.offer, 1_500);
+        assert_eq!(counter.outcome_payouts[1].payout.accept, 500);
+    }
 }
@@ -0,0 +1,66 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unnecessary_struct_initialization)]
+#![allow(clippy::unused_async)]
+use std::sync::Arc;
+
+use axum::{debug_handler, http::header, Extension};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::seed_encryption::SeedKey,
+    models::{seeds, users},
+    sol::SonsOfLiberty,
+};
+
+use super::auth::CookieAuth;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BackupSeedBody {
+    /// The user's password, asked again before the seed is revealed.
+    password: String,
+}
+
+/// Tells restores of the backup where the words are valid.
+const MNEMONIC_NOTE: &str = "These words encode the node seed entropy and only restore this \
+node. They are not a BIP39 seed, other wallets derive different keys from them.";
+
+/// Reveals the node seed as 24 words after the user re-enters their password. The response is
+/// never cached.
+#[debug_handler]
+pub async fn backup(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<BackupSeedBody>,
+) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    if !user.verify_password(&body.password) {
+        return unauthorized("unauthorized!");
+    }
+
+    let seed = seeds::Model::find_by_name(&ctx.db, &sol.settings.name)
+        .await?
+        .ok_or_else(|| Error::NotFound)?;
+    let key = SeedKey::configured(&sol.settings)?;
+    let mnemonic = seed.mnemonic(key.as_ref())?;
+
+    tracing::warn!("Seed mnemonic revealed to {}", user.email);
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({
+            "name": seed.name,
+            "mnemonic": mnemonic.to_string(),
+            "words": mnemonic.word_count(),
+            "bip39_compatible": false,
+            "note": MNEMONIC_NOTE,
+        })),
+    )
+        .into_response())
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("api/seed/")
+        .add("/backup", post(backup))
+}
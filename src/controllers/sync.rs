@@ -3,14 +3,14 @@
 #![allow(clippy::unused_async)]
 use std::sync::Arc;
 
-use crate::{models::users, sol::SonsOfLiberty};
-use axum::http::StatusCode;
+use crate::{common::sync, models::users, sol::SonsOfLiberty};
 use axum::{debug_handler, Extension};
-use loco_rs::controller::ErrorDetail;
 use loco_rs::prelude::*;
 
 use super::auth::CookieAuth;
 
+/// Syncs the manager and wallet now. Answers with a conflict while another sync, manual or from
+/// the background loop, is running.
 #[debug_handler]
 pub async fn index(
     cookie: CookieAuth,
@@ -19,26 +19,27 @@ pub async fn index(
 ) -> Result<Response> {
     users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
     tracing::info!("Syncing manager and wallet.");
-    if let Err(e) = sol.dlcdevkit.manager.periodic_check(false).await {
-        tracing::error!("Error syncing manager: {:?}", e);
-        return Err(Error::CustomError(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            ErrorDetail::with_reason(e.to_string()),
-        ));
-    };
-
-    if let Err(e) = sol.dlcdevkit.wallet.sync().await {
-        tracing::error!("Error syncing wallet: {:?}", e);
-        return Err(Error::CustomError(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            ErrorDetail::with_reason(e.to_string()),
-        ));
-    };
+    let closed = sync::sync_now(&ctx.db, &sol).await?;
     format::json(serde_json::json!({
         "success": true,
+        "closed": closed,
     }))
 }
 
+/// When the last sync succeeded and failed, the chain tip it reached and the errors it saw.
+#[debug_handler]
+pub async fn status(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    format::json(sol.sync.status())
+}
+
 pub fn routes() -> Routes {
-    Routes::new().prefix("api/sync/").add("/", get(index))
+    Routes::new()
+        .prefix("api/sync/")
+        .add("/", get(index))
+        .add("/status", get(status))
 }
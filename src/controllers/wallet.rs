@@ -1,12 +1,30 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
-use axum::{debug_handler, Extension};
-use loco_rs::prelude::*;
+use axum::{
+    debug_handler,
+    extract::Query,
+    http::{header, StatusCode},
+    Extension,
+};
+use bitcoin::{OutPoint, Psbt, Txid};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use serde::{Deserialize, Serialize};
 
-use crate::{common::dlcdevkit, models::users, sol::SonsOfLiberty};
+use crate::{
+    common::{
+        bitcoin_price, coin_control, dlcdevkit, history, ledger,
+        wallet::{self, FeePreview, SendAmount},
+    },
+    models::{psbt_reservations, users, utxo_labels},
+    sol::SonsOfLiberty,
+    views::{
+        ledger::{LedgerFormat, LedgerQuery},
+        wallet::{LabeledUtxo, UtxoLabelBody},
+    },
+};
 
 use super::auth::CookieAuth;
 
@@ -32,6 +50,52 @@ pub async fn get_wallet_transactions(
     format::json(transactions)
 }
 
+/// Wallet transactions with their direction, fee, confirmation and the contract they belong to.
+#[debug_handler]
+pub async fn history(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    format::json(history::wallet_history(&ctx.db, &sol).await?)
+}
+
+/// Confirmed wallet activity of a tax year with fiat values and FIFO realized gains, as JSON or
+/// CSV.
+#[debug_handler]
+pub async fn ledger(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Query(query): Query<LedgerQuery>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let currency = query
+        .currency
+        .as_deref()
+        .unwrap_or(bitcoin_price::DEFAULT_CURRENCY);
+    let ledger = ledger::build_ledger(&ctx.db, &sol, query.year, currency).await?;
+    match query.format {
+        LedgerFormat::Json => format::json(ledger),
+        LedgerFormat::Csv => {
+            let disposition = format!(
+                "attachment; filename=\"ledger-{}-{}.csv\"",
+                ledger.year, ledger.currency
+            );
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "text/csv".to_string()),
+                    (header::CONTENT_DISPOSITION, disposition),
+                ],
+                ledger.to_csv(),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Lists wallet UTXOs with their labels, frozen flags and contract reservations.
 #[debug_handler]
 pub async fn get_utxos(
     cookie: CookieAuth,
@@ -40,13 +104,374 @@ pub async fn get_utxos(
 ) -> Result<Response> {
     users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
     let utxos = dlcdevkit::get_utxos(&ddk)?;
+    let mut labels = utxo_labels::Model::by_outpoint(&ctx.db).await?;
+    let reserved = wallet::reserved_outpoints(&ddk).await?;
+
+    let utxos = utxos
+        .into_iter()
+        .map(|utxo| {
+            let label = labels.remove(&utxo.outpoint);
+            LabeledUtxo {
+                reserved: reserved.contains(&utxo.outpoint),
+                frozen: label.as_ref().is_some_and(|label| label.frozen),
+                label: label.and_then(|label| label.label),
+                utxo,
+            }
+        })
+        .collect::<Vec<_>>();
     format::json(utxos)
 }
 
+#[debug_handler]
+pub async fn label_utxo(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Path(outpoint): Path<String>,
+    Json(body): Json<UtxoLabelBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let outpoint = parse_outpoint(&outpoint)?;
+    let label = body
+        .label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    format::json(utxo_labels::ActiveModel::set_label(&ctx.db, &outpoint, label).await?)
+}
+
+#[debug_handler]
+pub async fn freeze_utxo(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Path(outpoint): Path<String>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let outpoint = parse_outpoint(&outpoint)?;
+    format::json(utxo_labels::ActiveModel::set_frozen(&ctx.db, &outpoint, true).await?)
+}
+
+#[debug_handler]
+pub async fn unfreeze_utxo(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Path(outpoint): Path<String>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let outpoint = parse_outpoint(&outpoint)?;
+    format::json(utxo_labels::ActiveModel::set_frozen(&ctx.db, &outpoint, false).await?)
+}
+
+fn parse_outpoint(outpoint: &str) -> Result<OutPoint> {
+    OutPoint::from_str(outpoint).map_err(|e| {
+        Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail {
+                error: Some(e.to_string()),
+                description: Some("Outpoint must be formatted as txid:vout".to_string()),
+            },
+        )
+    })
+}
+
+/// Confirmation target used when neither a fee rate nor a target is given.
+const DEFAULT_CONF_TARGET: u16 = 6;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SendBody {
+    address: String,
+    /// Amount in sats. Required unless `send_max` is set.
+    amount: Option<u64>,
+    #[serde(default)]
+    send_max: bool,
+    /// Fee rate in sat/vB. Takes precedence over `conf_target`.
+    fee_rate: Option<u64>,
+    /// Number of blocks the transaction should confirm in.
+    conf_target: Option<u16>,
+    /// Coins to spend. Every spendable coin is a candidate when unset.
+    outpoints: Option<Vec<OutPoint>>,
+    /// Only return the fee preview, without signing or broadcasting.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SendResponse {
+    #[serde(flatten)]
+    preview: FeePreview,
+    txid: Option<String>,
+}
+
+/// Sends on-chain funds from the DDK wallet. Frozen coins and coins reserved as funding inputs
+/// of pending contracts are never spent.
+#[debug_handler]
+pub async fn send(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<SendBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let address = wallet::parse_address(&body.address, &sol.settings.network)?;
+    let amount = send_amount(body.amount, body.send_max)?;
+    let fee_rate = fee_rate(&sol, body.fee_rate, body.conf_target).await?;
+
+    let coins = coin_control::selected_coins(&ctx.db, &sol, body.outpoints.as_deref()).await?;
+    // Planned with the next change script, which is only revealed once the plan is valid and
+    // the transaction is about to be signed.
+    let change_script_pubkey = wallet::change_script_pubkey(&ctx.db, &sol).await?;
+    let mut plan = wallet::plan_send(
+        coins,
+        &address.script_pubkey(),
+        amount,
+        fee_rate,
+        &change_script_pubkey,
+    )
+    .map_err(wallet::send_error)?;
+    let preview = plan.preview(fee_rate);
+
+    if body.dry_run {
+        return format::json(SendResponse {
+            preview,
+            txid: None,
+        });
+    }
+
+    if plan.change.is_some() {
+        plan.set_change_script_pubkey(wallet::reveal_change_script_pubkey(&sol).await?);
+    }
+    let tx = wallet::sign_psbt(&sol, wallet::build_psbt(&plan, &[])?).await?;
+    wallet::broadcast(&sol, &tx).await?;
+
+    format::json(SendResponse {
+        preview,
+        txid: Some(tx.compute_txid().to_string()),
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExportPsbtBody {
+    /// Destination. Consolidations default to a fresh address of the wallet.
+    address: Option<String>,
+    /// Amount in sats. Required unless `send_max` or `consolidate` is set.
+    amount: Option<u64>,
+    #[serde(default)]
+    send_max: bool,
+    /// Merge the selected coins, or every spendable coin, into a single output.
+    #[serde(default)]
+    consolidate: bool,
+    /// Fee rate in sat/vB. Takes precedence over `conf_target`.
+    fee_rate: Option<u64>,
+    /// Number of blocks the transaction should confirm in.
+    conf_target: Option<u16>,
+    /// Coins to spend. Every spendable coin is a candidate when unset.
+    outpoints: Option<Vec<OutPoint>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportPsbtResponse {
+    #[serde(flatten)]
+    preview: FeePreview,
+    /// Txid of the unsigned transaction, used to abandon the PSBT.
+    txid: String,
+    /// Unsigned PSBT, base64 encoded.
+    psbt: String,
+}
+
+/// Builds an unsigned PSBT from the wallet's coins, to be reviewed and signed outside of the
+/// server. Its coins stay reserved until it is broadcast or abandoned.
+#[debug_handler]
+pub async fn export_psbt(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<ExportPsbtBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let coins = coin_control::selected_coins(&ctx.db, &sol, body.outpoints.as_deref()).await?;
+    let address = body
+        .address
+        .as_deref()
+        .map(|address| wallet::parse_address(address, &sol.settings.network))
+        .transpose()?;
+    let change_script_pubkey = wallet::change_script_pubkey(&ctx.db, &sol).await?;
+
+    let (script_pubkey, amount) = if body.consolidate {
+        if coins.len() < 2 {
+            return Err(Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail::with_reason("Consolidation needs at least two coins"),
+            ));
+        }
+        let script_pubkey = address.map_or_else(
+            || change_script_pubkey.clone(),
+            |address| address.script_pubkey(),
+        );
+        (script_pubkey, SendAmount::Max)
+    } else {
+        let Some(address) = address else {
+            return Err(Error::CustomError(
+                StatusCode::BAD_REQUEST,
+                ErrorDetail::with_reason("Address is required unless consolidating"),
+            ));
+        };
+        (
+            address.script_pubkey(),
+            send_amount(body.amount, body.send_max)?,
+        )
+    };
+    let fee_rate = fee_rate(&sol, body.fee_rate, body.conf_target).await?;
+
+    let plan = wallet::plan_send(
+        coins,
+        &script_pubkey,
+        amount,
+        fee_rate,
+        &change_script_pubkey,
+    )
+    .map_err(wallet::send_error)?;
+    let descriptors = wallet::wallet_descriptors(&ctx.db, &sol).await?;
+    let psbt = wallet::build_psbt(&plan, &descriptors)?;
+    let txid = psbt.unsigned_tx.compute_txid();
+    let inputs = plan
+        .inputs
+        .iter()
+        .map(|coin| coin.outpoint)
+        .collect::<Vec<_>>();
+    psbt_reservations::ActiveModel::reserve(&ctx.db, &txid, &inputs).await?;
+
+    format::json(ExportPsbtResponse {
+        preview: plan.preview(fee_rate),
+        txid: txid.to_string(),
+        psbt: psbt.to_string(),
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BroadcastPsbtBody {
+    /// Signed PSBT, base64 encoded.
+    psbt: String,
+}
+
+/// Finalizes a PSBT signed by an external signer and broadcasts it.
+#[debug_handler]
+pub async fn broadcast_psbt(
+    cookie: CookieAuth,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    State(ctx): State<AppContext>,
+    Json(body): Json<BroadcastPsbtBody>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let psbt = Psbt::from_str(body.psbt.trim()).map_err(|e| {
+        Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail {
+                error: Some(e.to_string()),
+                description: Some("Invalid base64 PSBT".to_string()),
+            },
+        )
+    })?;
+
+    // The PSBT may have been built long ago, coins could be frozen or funding a contract by now.
+    let mut locked = wallet::reserved_outpoints(&sol).await?;
+    locked.extend(utxo_labels::Model::frozen_outpoints(&ctx.db).await?);
+    let locked_inputs = psbt
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .filter(|outpoint| locked.contains(outpoint))
+        .map(|outpoint| outpoint.to_string())
+        .collect::<Vec<_>>();
+    if !locked_inputs.is_empty() {
+        return Err(Error::CustomError(
+            StatusCode::CONFLICT,
+            ErrorDetail {
+                error: Some(locked_inputs.join(", ")),
+                description: Some("PSBT spends frozen or reserved coins".to_string()),
+            },
+        ));
+    }
+
+    let fee = wallet::psbt_fee(&psbt);
+    let tx = wallet::finalize_psbt(psbt).map_err(wallet::send_error)?;
+    wallet::broadcast(&sol, &tx).await?;
+    psbt_reservations::ActiveModel::release(&ctx.db, &tx.compute_txid()).await?;
+
+    format::json(serde_json::json!({
+        "txid": tx.compute_txid().to_string(),
+        "fee": fee,
+    }))
+}
+
+/// Abandons an exported PSBT that will not be signed, so its coins can be spent again.
+#[debug_handler]
+pub async fn abandon_psbt(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Path(txid): Path<String>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let txid = Txid::from_str(&txid).map_err(|e| {
+        Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail {
+                error: Some(e.to_string()),
+                description: Some("Invalid txid".to_string()),
+            },
+        )
+    })?;
+    let released = psbt_reservations::ActiveModel::release(&ctx.db, &txid).await?;
+    if released == 0 {
+        return Err(Error::NotFound);
+    }
+    format::json(serde_json::json!({
+        "txid": txid.to_string(),
+        "released": released,
+    }))
+}
+
+fn send_amount(amount: Option<u64>, send_max: bool) -> Result<SendAmount> {
+    match (amount, send_max) {
+        (Some(amount), false) => Ok(SendAmount::Sats(amount)),
+        (None, true) => Ok(SendAmount::Max),
+        _ => Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason("Provide either an amount or send_max"),
+        )),
+    }
+}
+
+/// Fee rate in sat/vB, estimated from the confirmation target when no rate is given.
+async fn fee_rate(
+    sol: &SonsOfLiberty,
+    fee_rate: Option<u64>,
+    conf_target: Option<u16>,
+) -> Result<u64> {
+    match fee_rate {
+        Some(0) => Err(Error::CustomError(
+            StatusCode::BAD_REQUEST,
+            ErrorDetail::with_reason("Fee rate must be at least 1 sat/vB"),
+        )),
+        Some(fee_rate) => Ok(fee_rate),
+        None => wallet::estimate_fee_rate(sol, conf_target.unwrap_or(DEFAULT_CONF_TARGET)).await,
+    }
+}
+
 pub fn routes() -> Routes {
     Routes::new()
         .prefix("api/wallet/")
         .add("/address", post(index))
         .add("/transactions", get(get_wallet_transactions))
+        .add("/history", get(history))
+        .add("/ledger", get(ledger))
         .add("/utxos", get(get_utxos))
+        .add("/utxos/{outpoint}/label", put(label_utxo))
+        .add("/utxos/{outpoint}/freeze", post(freeze_utxo))
+        .add("/utxos/{outpoint}/unfreeze", post(unfreeze_utxo))
+        .add("/send", post(send))
+        .add("/psbt", post(export_psbt))
+        .add("/psbt/broadcast", post(broadcast_psbt))
+        .add("/psbt/{txid}", delete(abandon_psbt))
 }
@@ -0,0 +1,130 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+use std::sync::Arc;
+
+use axum::{debug_handler, http::StatusCode, Extension};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+
+use crate::{
+    common::webhooks::{generate_secret, resolve_url},
+    models::{users, webhook_deliveries, webhooks},
+    sol::SonsOfLiberty,
+    views::{
+        events::NodeEvent,
+        webhooks::{RegisterWebhook, WebhookResponse},
+    },
+};
+
+use super::auth::CookieAuth;
+
+fn bad_request(reason: impl Into<String>) -> Error {
+    Error::CustomError(StatusCode::BAD_REQUEST, ErrorDetail::with_reason(reason))
+}
+
+#[debug_handler]
+pub async fn list(cookie: CookieAuth, State(ctx): State<AppContext>) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let webhooks = webhooks::Model::list(&ctx.db)
+        .await?
+        .into_iter()
+        .map(WebhookResponse::from)
+        .collect::<Vec<_>>();
+    format::json(webhooks)
+}
+
+/// Registers a URL that node events are posted to, signed with the returned secret. Hosts that
+/// are not public are refused unless `webhooks.allow_private_hosts` is set.
+#[debug_handler]
+pub async fn register(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Extension(sol): Extension<Arc<SonsOfLiberty>>,
+    Json(body): Json<RegisterWebhook>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+
+    let url = reqwest::Url::parse(body.url.trim())
+        .map_err(|e| bad_request(format!("Invalid webhook url: {e}")))?;
+    resolve_url(&url, sol.settings.webhooks.allow_private_hosts)
+        .await
+        .map_err(bad_request)?;
+    let unknown = body
+        .events
+        .iter()
+        .filter(|event| !NodeEvent::NAMES.contains(&event.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(bad_request(format!(
+            "Unknown events {}, expected some of {}",
+            unknown.join(", "),
+            NodeEvent::NAMES.join(", ")
+        )));
+    }
+    let secret = match body.secret.map(|secret| secret.trim().to_string()) {
+        Some(secret) if secret.is_empty() => return Err(bad_request("Secret must not be empty")),
+        Some(secret) => secret,
+        None => generate_secret(),
+    };
+
+    let webhook =
+        webhooks::ActiveModel::register(&ctx.db, url.as_str(), &secret, &body.events).await?;
+    format::json(WebhookResponse {
+        secret: Some(webhook.secret.clone()),
+        ..WebhookResponse::from(webhook)
+    })
+}
+
+#[debug_handler]
+pub async fn remove(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Path(id): Path<i32>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let deleted = webhooks::Entity::delete_by_id(id).exec(&ctx.db).await?;
+    if deleted.rows_affected == 0 {
+        return Err(Error::NotFound);
+    }
+    format::empty_json()
+}
+
+/// Delivery log of a webhook, newest first.
+#[debug_handler]
+pub async fn deliveries(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Path(id): Path<i32>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    webhooks::Entity::find_by_id(id)
+        .one(&ctx.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+    format::json(webhook_deliveries::Model::for_webhook(&ctx.db, id).await?)
+}
+
+/// Sends the payload of a past delivery again, logged as a new delivery that is due right away.
+#[debug_handler]
+pub async fn replay(
+    cookie: CookieAuth,
+    State(ctx): State<AppContext>,
+    Path(id): Path<i32>,
+) -> Result<Response> {
+    users::Model::find_by_pid(&ctx.db, &cookie.user.pid).await?;
+    let delivery = webhook_deliveries::Entity::find_by_id(id)
+        .one(&ctx.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+    format::json(delivery.replay(&ctx.db).await?)
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("api/webhooks/")
+        .add("/", get(list))
+        .add("/", post(register))
+        .add("/{id}", delete(remove))
+        .add("/{id}/deliveries", get(deliveries))
+        .add("/deliveries/{id}/replay", post(replay))
+}
@@ -23,4 +23,13 @@ pub struct Model {
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::balance_valuations::Entity")]
+    BalanceValuations,
+}
+
+impl Related<super::balance_valuations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BalanceValuations.def()
+    }
+}
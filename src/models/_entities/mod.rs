@@ -3,13 +3,22 @@
 pub mod prelude;
 
 pub mod anchor_tx;
+pub mod balance_valuations;
 pub mod balances;
 pub mod block;
+pub mod close_proposals;
+pub mod contract_events;
 pub mod contracts;
 pub mod keychain;
 pub mod network;
+pub mod offer_expiries;
+pub mod prices;
+pub mod psbt_reservations;
 pub mod seeds;
 pub mod tx;
 pub mod txout;
 pub mod users;
+pub mod utxo_labels;
 pub mod version;
+pub mod webhook_deliveries;
+pub mod webhooks;
@@ -1,13 +1,22 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.4
 
 pub use super::anchor_tx::Entity as AnchorTx;
+pub use super::balance_valuations::Entity as BalanceValuations;
 pub use super::balances::Entity as Balances;
 pub use super::block::Entity as Block;
+pub use super::close_proposals::Entity as CloseProposals;
+pub use super::contract_events::Entity as ContractEvents;
 pub use super::contracts::Entity as Contracts;
 pub use super::keychain::Entity as Keychain;
 pub use super::network::Entity as Network;
+pub use super::offer_expiries::Entity as OfferExpiries;
+pub use super::prices::Entity as Prices;
+pub use super::psbt_reservations::Entity as PsbtReservations;
 pub use super::seeds::Entity as Seeds;
 pub use super::tx::Entity as Tx;
 pub use super::txout::Entity as Txout;
 pub use super::users::Entity as Users;
+pub use super::utxo_labels::Entity as UtxoLabels;
 pub use super::version::Entity as Version;
+pub use super::webhook_deliveries::Entity as WebhookDeliveries;
+pub use super::webhooks::Entity as Webhooks;
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use super::_entities::balance_valuations::Column;
+pub use super::_entities::balance_valuations::{ActiveModel, Entity, Model};
+use crate::common::bitcoin_price::SpotPrice;
+use bitcoin::SignedAmount;
+use ddk::Balance;
+use sea_orm::{entity::prelude::*, ActiveValue};
+pub type BalanceValuations = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Valuations in `currency` of the given balance snapshots, keyed by snapshot id.
+    pub async fn for_balances(
+        db: &DatabaseConnection,
+        balance_ids: impl IntoIterator<Item = i32>,
+        currency: &str,
+    ) -> Result<HashMap<i32, Self>, DbErr> {
+        Ok(Entity::find()
+            .filter(Column::BalanceId.is_in(balance_ids))
+            .filter(Column::Currency.eq(currency))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|valuation| (valuation.balance_id, valuation))
+            .collect())
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// Values a balance snapshot at `price`.
+    pub fn valuation(balance_id: i32, balance: &Balance, price: &SpotPrice) -> Self {
+        let value =
+            |btc: f64| Decimal::from_f64_retain(btc * price.amount).unwrap_or(Decimal::ZERO);
+        Self {
+            balance_id: ActiveValue::Set(balance_id),
+            currency: ActiveValue::Set(price.currency.clone()),
+            bitcoin_price: ActiveValue::Set(
+                Decimal::from_f64_retain(price.amount).unwrap_or(Decimal::ZERO),
+            ),
+            bitcoin_balance: ActiveValue::Set(value(balance.confirmed.to_btc())),
+            contract_balance: ActiveValue::Set(value(balance.contract.to_btc())),
+            pnl: ActiveValue::Set(value(SignedAmount::from_sat(balance.contract_pnl).to_btc())),
+            ..Default::default()
+        }
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -1,10 +1,16 @@
-use crate::{common::settings::Settings, views::balances::TimePeriod};
+use crate::{
+    common::{bitcoin_price::SpotPrice, settings::Settings},
+    views::balances::{BalanceCandle, Bucket, FiatBalance, Ohlc, TimePeriod},
+};
 
-use super::_entities::balances::Column;
 pub use super::_entities::balances::{ActiveModel, Entity, Model};
-use chrono::{DateTime, Duration, Utc};
+use super::_entities::{balance_valuations, balances::Column};
+use chrono::{DateTime, Utc};
 use ddk::Balance;
-use sea_orm::{entity::prelude::*, ActiveValue, Condition, QueryOrder, TransactionTrait};
+use sea_orm::{
+    entity::prelude::*, ActiveValue, Condition, DbBackend, FromQueryResult, QueryOrder, Statement,
+    TransactionTrait,
+};
 pub type Balances = Entity;
 
 #[async_trait::async_trait]
@@ -23,6 +29,42 @@ impl ActiveModelBehavior for ActiveModel {
     }
 }
 
+#[derive(Debug, FromQueryResult)]
+struct CandleRow {
+    bucket: DateTimeWithTimeZone,
+    samples: i64,
+    price_open: Decimal,
+    price_high: Decimal,
+    price_low: Decimal,
+    price_close: Decimal,
+    balance_sats_open: i64,
+    balance_sats_high: i64,
+    balance_sats_low: i64,
+    balance_sats_close: i64,
+    balance_open: Decimal,
+    balance_high: Decimal,
+    balance_low: Decimal,
+    balance_close: Decimal,
+    pnl_sats: i64,
+    num_contracts: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+pub struct PricePoint {
+    pub created_at: DateTimeWithTimeZone,
+    pub price: Decimal,
+}
+
+/// Start of the bucket a snapshot falls in, for a bucket width bound as `$1` seconds.
+const BUCKET: &str =
+    "to_timestamp((floor(extract(epoch FROM b.created_at) / $1) * $1)::double precision)";
+
+/// Price and total balance of a snapshot in the currency bound as `$4`. Snapshots without a
+/// valuation in it fall back to their own USD columns when the currency is USD.
+const FIAT_PRICE: &str = "COALESCE(v.bitcoin_price, CASE WHEN $4 = 'USD' THEN b.bitcoin_price END)";
+const FIAT_BALANCE: &str = "COALESCE(v.bitcoin_balance + v.contract_balance, \
+     CASE WHEN $4 = 'USD' THEN b.bitcoin_balance_usd + b.contract_balance_usd END)";
+
 // implement your read-oriented logic here
 impl Model {
     pub async fn get_history(
@@ -30,25 +72,178 @@ impl Model {
         time_period: TimePeriod,
         reference_date: DateTime<Utc>,
     ) -> Result<Vec<Self>, DbErr> {
-        let start_date = match time_period {
-            TimePeriod::Day => reference_date - Duration::hours(24),
-            TimePeriod::Week => reference_date - Duration::days(7),
-            TimePeriod::Month => reference_date - Duration::days(30),
-            TimePeriod::Year => reference_date - Duration::days(365),
+        Self::get_range(db, time_period.start(reference_date), reference_date, None).await
+    }
+
+    /// Snapshots between `start` and `end`. With a bucket, only the last snapshot of every bucket
+    /// is kept.
+    pub async fn get_range(
+        db: &DatabaseConnection,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket: Option<Bucket>,
+    ) -> Result<Vec<Self>, DbErr> {
+        let Some(bucket) = bucket else {
+            return Balances::find()
+                .filter(
+                    Condition::all()
+                        .add(Column::CreatedAt.gte(start))
+                        .add(Column::CreatedAt.lte(end)),
+                )
+                .order_by_asc(Column::CreatedAt)
+                .all(db)
+                .await;
         };
 
-        // Query balances within the time range
-        let balances = Balances::find()
-            .filter(
-                Condition::all()
-                    .add(Column::CreatedAt.gte(start_date))
-                    .add(Column::CreatedAt.lte(reference_date)),
-            )
-            .order_by_asc(Column::CreatedAt)
-            .all(db)
-            .await?;
-
-        Ok(balances)
+        let sql = format!(
+            "SELECT DISTINCT ON ({BUCKET}) b.* FROM balances b \
+             WHERE b.created_at >= $2 AND b.created_at <= $3 \
+             ORDER BY {BUCKET} ASC, b.created_at DESC, b.id DESC"
+        );
+        Self::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            sql,
+            [bucket.seconds.into(), start.into(), end.into()],
+        ))
+        .all(db)
+        .await
+    }
+
+    /// Same as [`Model::get_history`], valued in `currency`. Snapshots taken before `currency`
+    /// was configured have no valuation in it and are left out, except for USD which every
+    /// snapshot has.
+    pub async fn get_history_in(
+        db: &DatabaseConnection,
+        time_period: TimePeriod,
+        reference_date: DateTime<Utc>,
+        currency: &str,
+    ) -> Result<Vec<FiatBalance>, DbErr> {
+        Self::get_range_in(
+            db,
+            time_period.start(reference_date),
+            reference_date,
+            None,
+            currency,
+        )
+        .await
+    }
+
+    /// Same as [`Model::get_range`], valued in `currency`.
+    pub async fn get_range_in(
+        db: &DatabaseConnection,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket: Option<Bucket>,
+        currency: &str,
+    ) -> Result<Vec<FiatBalance>, DbErr> {
+        let balances = Self::get_range(db, start, end, bucket).await?;
+        let mut valuations = balance_valuations::Model::for_balances(
+            db,
+            balances.iter().map(|balance| balance.id),
+            currency,
+        )
+        .await?;
+
+        Ok(balances
+            .into_iter()
+            .filter_map(|balance| {
+                let valuation = valuations.remove(&balance.id);
+                FiatBalance::new(balance, valuation, currency)
+            })
+            .collect())
+    }
+
+    /// BTC price in `currency` of every snapshot taken before `end`, oldest first.
+    pub async fn price_history(
+        db: &DatabaseConnection,
+        currency: &str,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<PricePoint>, DbErr> {
+        let price = "COALESCE(v.bitcoin_price, CASE WHEN $2 = 'USD' THEN b.bitcoin_price END)";
+        let sql = format!(
+            "SELECT b.created_at, {price} AS price FROM balances b \
+             LEFT JOIN balance_valuations v ON v.balance_id = b.id AND v.currency = $2 \
+             WHERE b.created_at < $1 AND {price} IS NOT NULL \
+             ORDER BY b.created_at ASC"
+        );
+        PricePoint::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            sql,
+            [end.into(), currency.into()],
+        ))
+        .all(db)
+        .await
+    }
+
+    /// Open, high, low and close of the price and total balance in every bucket between `start`
+    /// and `end`, valued in `currency`. Aggregation happens in postgres.
+    pub async fn candles(
+        db: &DatabaseConnection,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        bucket: Bucket,
+        currency: &str,
+    ) -> Result<Vec<BalanceCandle>, DbErr> {
+        let sats = "b.bitcoin_balance_sats + b.contract_balance_sats";
+        let sql = format!(
+            "SELECT {BUCKET} AS bucket, COUNT(*) AS samples, \
+                (array_agg({FIAT_PRICE} ORDER BY b.created_at ASC, b.id ASC))[1] AS price_open, \
+                MAX({FIAT_PRICE}) AS price_high, MIN({FIAT_PRICE}) AS price_low, \
+                (array_agg({FIAT_PRICE} ORDER BY b.created_at DESC, b.id DESC))[1] AS price_close, \
+                (array_agg({sats} ORDER BY b.created_at ASC, b.id ASC))[1] AS balance_sats_open, \
+                MAX({sats}) AS balance_sats_high, MIN({sats}) AS balance_sats_low, \
+                (array_agg({sats} ORDER BY b.created_at DESC, b.id DESC))[1] AS balance_sats_close, \
+                (array_agg({FIAT_BALANCE} ORDER BY b.created_at ASC, b.id ASC))[1] AS balance_open, \
+                MAX({FIAT_BALANCE}) AS balance_high, MIN({FIAT_BALANCE}) AS balance_low, \
+                (array_agg({FIAT_BALANCE} ORDER BY b.created_at DESC, b.id DESC))[1] AS balance_close, \
+                (array_agg(b.pnl_sats ORDER BY b.created_at DESC, b.id DESC))[1] AS pnl_sats, \
+                (array_agg(b.num_contracts ORDER BY b.created_at DESC, b.id DESC))[1] AS num_contracts \
+             FROM balances b \
+             LEFT JOIN balance_valuations v ON v.balance_id = b.id AND v.currency = $4 \
+             WHERE b.created_at >= $2 AND b.created_at <= $3 AND {FIAT_PRICE} IS NOT NULL \
+             GROUP BY 1 ORDER BY 1"
+        );
+        let rows = CandleRow::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            sql,
+            [
+                bucket.seconds.into(),
+                start.into(),
+                end.into(),
+                currency.into(),
+            ],
+        ))
+        .all(db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| BalanceCandle {
+                bucket: row.bucket,
+                samples: row.samples,
+                currency: currency.to_string(),
+                bitcoin_price: Ohlc {
+                    open: row.price_open,
+                    high: row.price_high,
+                    low: row.price_low,
+                    close: row.price_close,
+                },
+                balance_sats: Ohlc {
+                    open: row.balance_sats_open,
+                    high: row.balance_sats_high,
+                    low: row.balance_sats_low,
+                    close: row.balance_sats_close,
+                },
+                balance: Ohlc {
+                    open: row.balance_open,
+                    high: row.balance_high,
+                    low: row.balance_low,
+                    close: row.balance_close,
+                },
+                pnl_sats: row.pnl_sats,
+                num_contracts: row.num_contracts,
+            })
+            .collect())
     }
 }
 
@@ -59,6 +254,7 @@ impl ActiveModel {
         settings: &Settings,
         balance: Balance,
         price: f64,
+        valuations: &[SpotPrice],
         num_contracts: usize,
     ) -> Result<(), DbErr> {
         let txn = db.begin().await?;
@@ -86,7 +282,12 @@ impl ActiveModel {
             network: ActiveValue::Set(settings.network.clone()),
             ..Default::default()
         };
-        balance_update.insert(&txn).await?;
+        let balance_update = balance_update.insert(&txn).await?;
+        for price in valuations {
+            balance_valuations::ActiveModel::valuation(balance_update.id, &balance, price)
+                .insert(&txn)
+                .await?;
+        }
         txn.commit().await?;
         Ok(())
     }
@@ -1,5 +1,5 @@
 pub use super::_entities::block::{ActiveModel, Entity, Model};
-use sea_orm::entity::prelude::*;
+use sea_orm::{entity::prelude::*, QueryOrder};
 pub type Block = Entity;
 
 #[async_trait::async_trait]
@@ -19,4 +19,22 @@ impl Model {}
 impl ActiveModel {}
 
 // implement your custom finders, selectors oriented logic here
-impl Entity {}
+impl Entity {
+    /// Height of the highest block in the local chain of the DDK wallet named `wallet_name`.
+    pub async fn tip_height(
+        db: &DatabaseConnection,
+        wallet_name: &str,
+    ) -> Result<Option<u32>, DbErr> {
+        Ok(Self::find()
+            .filter(super::_entities::block::Column::WalletName.eq(wallet_name))
+            .order_by_desc(super::_entities::block::Column::Height)
+            .one(db)
+            .await?
+            .and_then(|block| u32::try_from(block.height).ok()))
+    }
+}
+
+impl super::wallet_table::WalletTable for Entity {
+    const WALLET_NAME: super::_entities::block::Column =
+        super::_entities::block::Column::WalletName;
+}
@@ -0,0 +1,146 @@
+use super::_entities::close_proposals::Column;
+pub use super::_entities::close_proposals::{ActiveModel, Entity, Model};
+use crate::common::close::CloseTerms;
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder};
+pub type CloseProposals = Entity;
+
+pub const STATUS_PROPOSED: &str = "proposed";
+pub const STATUS_CLOSED: &str = "closed";
+/// Withdrawn by the proposer. Its signature stays valid, so the counterparty can still close
+/// with it, but it can no longer be accepted here.
+pub const STATUS_WITHDRAWN: &str = "withdrawn";
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    pub fn terms(&self) -> CloseTerms {
+        CloseTerms {
+            offer_payout: self.offer_payout as u64,
+            accept_payout: self.accept_payout as u64,
+            fee_rate: self.fee_rate as u64,
+        }
+    }
+
+    /// Latest open proposal for a contract, sent by us or by the counterparty.
+    pub async fn find_open(
+        db: &DatabaseConnection,
+        contract_id: &str,
+        is_proposer: bool,
+    ) -> Result<Option<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::ContractId.eq(contract_id))
+            .filter(Column::IsProposer.eq(is_proposer))
+            .filter(Column::Status.eq(STATUS_PROPOSED))
+            .order_by_desc(Column::CreatedAt)
+            .one(db)
+            .await
+    }
+
+    /// Every open proposal for a contract, sent by us or by the counterparty.
+    pub async fn all_open(
+        db: &DatabaseConnection,
+        contract_id: &str,
+        is_proposer: bool,
+    ) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::ContractId.eq(contract_id))
+            .filter(Column::IsProposer.eq(is_proposer))
+            .filter(Column::Status.eq(STATUS_PROPOSED))
+            .order_by_desc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    /// Proposals we signed for a contract that did not close it yet. Withdrawn proposals are
+    /// included, as the counterparty still holds our signature.
+    pub async fn signed_unclosed(
+        db: &DatabaseConnection,
+        contract_id: &str,
+    ) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::ContractId.eq(contract_id))
+            .filter(Column::IsProposer.eq(true))
+            .filter(Column::Status.is_in([STATUS_PROPOSED, STATUS_WITHDRAWN]))
+            .order_by_desc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn list(db: &DatabaseConnection) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .order_by_desc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_signature(
+        db: &DatabaseConnection,
+        signature: &str,
+    ) -> Result<Option<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::Signature.eq(signature))
+            .one(db)
+            .await
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    pub async fn create_proposal(
+        db: &DatabaseConnection,
+        contract_id: &str,
+        counter_party: &str,
+        is_proposer: bool,
+        terms: &CloseTerms,
+        signature: &str,
+    ) -> Result<Model, DbErr> {
+        ActiveModel {
+            contract_id: ActiveValue::Set(contract_id.to_string()),
+            counter_party: ActiveValue::Set(counter_party.to_string()),
+            is_proposer: ActiveValue::Set(is_proposer),
+            offer_payout: ActiveValue::Set(terms.offer_payout as i64),
+            accept_payout: ActiveValue::Set(terms.accept_payout as i64),
+            fee_rate: ActiveValue::Set(terms.fee_rate as i64),
+            signature: ActiveValue::Set(signature.to_string()),
+            status: ActiveValue::Set(STATUS_PROPOSED.to_string()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn mark_closed(
+        db: &DatabaseConnection,
+        proposal: Model,
+        txid: &str,
+    ) -> Result<Model, DbErr> {
+        let mut proposal: Self = proposal.into();
+        proposal.status = ActiveValue::Set(STATUS_CLOSED.to_string());
+        proposal.txid = ActiveValue::Set(Some(txid.to_string()));
+        proposal.update(db).await
+    }
+
+    pub async fn mark_withdrawn(db: &DatabaseConnection, proposal: Model) -> Result<Model, DbErr> {
+        let mut proposal: Self = proposal.into();
+        proposal.status = ActiveValue::Set(STATUS_WITHDRAWN.to_string());
+        proposal.update(db).await
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use super::_entities::contract_events::Column;
+pub use super::_entities::contract_events::{ActiveModel, Entity, Model};
+use sea_orm::{entity::prelude::*, sea_query::Expr, ActiveValue, Condition, QueryOrder};
+pub type ContractEvents = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// State transitions of a contract, oldest first. Accepts both the temporary id used while
+    /// the contract is offered and the final contract id.
+    pub async fn timeline(db: &DatabaseConnection, contract_id: &str) -> Result<Vec<Self>, DbErr> {
+        let Some(event) = Entity::find()
+            .filter(
+                Condition::any()
+                    .add(Column::ContractId.eq(contract_id))
+                    .add(Column::TemporaryContractId.eq(contract_id)),
+            )
+            .one(db)
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+
+        Entity::find()
+            .filter(Column::TemporaryContractId.eq(event.temporary_contract_id))
+            .order_by_asc(Column::CreatedAt)
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// Events recorded under each of `contract_ids`, oldest first, keyed by contract id.
+    pub async fn by_contract(
+        db: &DatabaseConnection,
+        contract_ids: &[String],
+    ) -> Result<HashMap<String, Vec<Self>>, DbErr> {
+        let mut by_contract = HashMap::<String, Vec<Self>>::new();
+        for event in Entity::find()
+            .filter(Column::ContractId.is_in(contract_ids.iter().map(String::as_str)))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?
+        {
+            by_contract
+                .entry(event.contract_id.clone())
+                .or_default()
+                .push(event);
+        }
+        Ok(by_contract)
+    }
+
+    /// When each contract was first seen, keyed by both its temporary and its final id.
+    pub async fn first_seen(
+        db: &DatabaseConnection,
+    ) -> Result<HashMap<String, DateTimeWithTimeZone>, DbErr> {
+        let events = Entity::find().order_by_asc(Column::Id).all(db).await?;
+        let mut by_temporary_id = HashMap::new();
+        for event in &events {
+            by_temporary_id
+                .entry(event.temporary_contract_id.clone())
+                .or_insert(event.created_at);
+        }
+        Ok(events
+            .into_iter()
+            .flat_map(|event| {
+                let seen = by_temporary_id[&event.temporary_contract_id];
+                [
+                    (event.contract_id, seen),
+                    (event.temporary_contract_id, seen),
+                ]
+            })
+            .collect())
+    }
+
+    /// When each contract first reached one of `states`, keyed by contract id.
+    pub async fn reached(
+        db: &DatabaseConnection,
+        states: &[&str],
+    ) -> Result<HashMap<String, DateTimeWithTimeZone>, DbErr> {
+        let mut reached = HashMap::new();
+        for event in Entity::find()
+            .filter(Column::State.is_in(states.iter().copied()))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?
+        {
+            reached.entry(event.contract_id).or_insert(event.created_at);
+        }
+        Ok(reached)
+    }
+
+    /// Last recorded state of every contract, keyed by temporary contract id.
+    pub async fn latest_states(db: &DatabaseConnection) -> Result<HashMap<String, String>, DbErr> {
+        Ok(Entity::find()
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|event| (event.temporary_contract_id, event.state))
+            .collect())
+    }
+
+    /// Events not yet published as node events and webhook deliveries, oldest first.
+    pub async fn unpublished(db: &DatabaseConnection) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::PublishedAt.is_null())
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    pub async fn record(
+        db: &DatabaseConnection,
+        contract_id: &str,
+        temporary_contract_id: &str,
+        state: &str,
+        oracle_event_id: Option<String>,
+    ) -> Result<Model, DbErr> {
+        ActiveModel {
+            contract_id: ActiveValue::Set(contract_id.to_string()),
+            temporary_contract_id: ActiveValue::Set(temporary_contract_id.to_string()),
+            state: ActiveValue::Set(state.to_string()),
+            oracle_event_id: ActiveValue::Set(oracle_event_id),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    /// Marks events as published, so they are not delivered again.
+    pub async fn mark_published(db: &DatabaseConnection, ids: &[i32]) -> Result<u64, DbErr> {
+        Ok(Entity::update_many()
+            .col_expr(
+                Column::PublishedAt,
+                Expr::value(chrono::Utc::now().fixed_offset()),
+            )
+            .filter(Column::Id.is_in(ids.iter().copied()))
+            .exec(db)
+            .await?
+            .rows_affected)
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -1,5 +1,10 @@
 pub use super::_entities::contracts::{ActiveModel, Entity, Model};
-use sea_orm::entity::prelude::*;
+use chrono::DateTime;
+use ddk_manager::contract::Contract;
+use sea_orm::{entity::prelude::*, DbBackend, FromQueryResult, Statement, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::views::contracts::{ContractListItem, ContractListQuery, ContractSort, SortOrder};
 pub type Contracts = Entity;
 
 #[async_trait::async_trait]
@@ -12,11 +17,334 @@ impl ActiveModelBehavior for ActiveModel {
     }
 }
 
+/// States of the `state` column of ddk's contract rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContractState {
+    Offered,
+    Accepted,
+    Signed,
+    Confirmed,
+    PreClosed,
+    Closed,
+    FailedAccept,
+    FailedSign,
+    Refunded,
+    Rejected,
+}
+
+impl ContractState {
+    pub const ALL: [Self; 10] = [
+        Self::Offered,
+        Self::Accepted,
+        Self::Signed,
+        Self::Confirmed,
+        Self::PreClosed,
+        Self::Closed,
+        Self::FailedAccept,
+        Self::FailedSign,
+        Self::Refunded,
+        Self::Rejected,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Offered => "offered",
+            Self::Accepted => "accepted",
+            Self::Signed => "signed",
+            Self::Confirmed => "confirmed",
+            Self::PreClosed => "pre-closed",
+            Self::Closed => "closed",
+            Self::FailedAccept => "failed-accept",
+            Self::FailedSign => "failed-sign",
+            Self::Refunded => "refunded",
+            Self::Rejected => "rejected",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|state| state.name() == name)
+    }
+
+    /// Value stored in the `state` column, following the contract prefixes of ddk-manager.
+    pub fn as_i16(self) -> i16 {
+        match self {
+            Self::Offered => 1,
+            Self::Accepted => 2,
+            Self::Signed => 3,
+            Self::Confirmed => 4,
+            Self::PreClosed => 5,
+            Self::Closed => 6,
+            Self::FailedAccept => 7,
+            Self::FailedSign => 8,
+            Self::Refunded => 9,
+            Self::Rejected => 10,
+        }
+    }
+
+    pub fn from_i16(value: i16) -> Option<Self> {
+        Self::ALL.into_iter().find(|state| state.as_i16() == value)
+    }
+}
+
+impl From<&Contract> for ContractState {
+    fn from(contract: &Contract) -> Self {
+        match contract {
+            Contract::Offered(_) => Self::Offered,
+            Contract::Accepted(_) => Self::Accepted,
+            Contract::Signed(_) => Self::Signed,
+            Contract::Confirmed(_) => Self::Confirmed,
+            Contract::PreClosed(_) => Self::PreClosed,
+            Contract::Closed(_) => Self::Closed,
+            Contract::Refunded(_) => Self::Refunded,
+            Contract::FailedAccept(_) => Self::FailedAccept,
+            Contract::FailedSign(_) => Self::FailedSign,
+            Contract::Rejected(_) => Self::Rejected,
+        }
+    }
+}
+
+/// Position after the last row of a page, ordered by the sort column then the contract id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractCursor {
+    pub value: String,
+    pub id: String,
+}
+
+impl ContractCursor {
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}|{}", self.value, self.id))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let decoded = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+        let (value, id) = decoded.split_once('|')?;
+        Some(Self {
+            value: value.to_string(),
+            id: id.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ContractListRow {
+    id: String,
+    state: i16,
+    is_offer_party: bool,
+    counter_party: String,
+    offer_collateral: i64,
+    accept_collateral: i64,
+    total_collateral: i64,
+    fee_rate_per_vb: i64,
+    cet_locktime: i32,
+    refund_locktime: i32,
+    pnl: Option<i64>,
+    created_at: DateTimeWithTimeZone,
+    oracle_event_id: Option<String>,
+}
+
+impl From<ContractListRow> for ContractListItem {
+    fn from(row: ContractListRow) -> Self {
+        Self {
+            id: row.id,
+            state: ContractState::from_i16(row.state)
+                .map_or_else(|| row.state.to_string(), |state| state.name().to_string()),
+            is_offer_party: row.is_offer_party,
+            counter_party: row.counter_party,
+            offer_collateral: row.offer_collateral,
+            accept_collateral: row.accept_collateral,
+            total_collateral: row.total_collateral,
+            fee_rate_per_vb: row.fee_rate_per_vb,
+            cet_locktime: row.cet_locktime,
+            refund_locktime: row.refund_locktime,
+            pnl: row.pnl,
+            created_at: row.created_at,
+            oracle_event_id: row.oracle_event_id,
+        }
+    }
+}
+
+/// Contract rows with the oracle event recorded in their timeline. ddk owns the `contracts`
+/// table and has no creation time, so a contract is dated by the first timeline event of its
+/// offer, which follows the contract from its temporary id to its final one. Contracts the sync
+/// has not recorded yet count as created now.
+const CONTRACT_ROWS: &str = r"
+    SELECT * FROM (
+        SELECT c.id, c.state, c.is_offer_party, c.counter_party, c.offer_collateral,
+               c.accept_collateral, c.total_collateral, c.fee_rate_per_vb, c.cet_locktime,
+               c.refund_locktime, c.pnl,
+               COALESCE(
+                   (SELECT MIN(seen.created_at)
+                    FROM contract_events e
+                    JOIN contract_events seen
+                        ON seen.temporary_contract_id = e.temporary_contract_id
+                    WHERE e.contract_id = c.id),
+                   now()) AS created_at,
+               (SELECT MAX(e.oracle_event_id) FROM contract_events e WHERE e.contract_id = c.id)
+                   AS oracle_event_id
+        FROM contracts c
+    ) c";
+
+/// Adds a query parameter and returns its placeholder.
+fn bind(values: &mut Vec<Value>, value: Value) -> String {
+    values.push(value);
+    format!("${}", values.len())
+}
+
 // implement your read-oriented logic here
-impl Model {}
+impl Model {
+    /// A page of contract rows matching the query, plus the cursor of the next page if there is
+    /// one. Filtering, sorting and pagination all happen in postgres.
+    pub async fn page(
+        db: &DatabaseConnection,
+        query: &ContractListQuery,
+        states: &[ContractState],
+    ) -> Result<(Vec<ContractListItem>, Option<String>), DbErr> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        if !states.is_empty() {
+            let placeholders = states
+                .iter()
+                .map(|state| bind(&mut values, state.as_i16().into()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            conditions.push(format!("c.state IN ({placeholders})"));
+        }
+        if let Some(counterparty) = &query.counterparty {
+            conditions.push(format!(
+                "c.counter_party = {}",
+                bind(&mut values, counterparty.clone().into())
+            ));
+        }
+        if let Some(from) = query.from {
+            conditions.push(format!(
+                "c.created_at >= {}",
+                bind(&mut values, from.into())
+            ));
+        }
+        if let Some(to) = query.to {
+            conditions.push(format!("c.created_at <= {}", bind(&mut values, to.into())));
+        }
+        if let Some(oracle_event_id) = &query.oracle_event_id {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM contract_events e WHERE e.contract_id = c.id AND e.oracle_event_id = {})",
+                bind(&mut values, oracle_event_id.clone().into())
+            ));
+        }
+
+        let sort_column = match query.sort {
+            ContractSort::Created => "c.created_at",
+            ContractSort::Collateral => "c.total_collateral",
+            ContractSort::State => "c.state::bigint",
+        };
+        let (comparison, direction) = match query.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+
+        if let Some(cursor) = query.cursor.as_deref().and_then(ContractCursor::decode) {
+            let cursor_value: Value = match query.sort {
+                ContractSort::Created => DateTime::parse_from_rfc3339(&cursor.value)
+                    .map_err(|e| DbErr::Custom(format!("Invalid cursor: {e}")))?
+                    .into(),
+                ContractSort::Collateral | ContractSort::State => cursor
+                    .value
+                    .parse::<i64>()
+                    .map_err(|e| DbErr::Custom(format!("Invalid cursor: {e}")))?
+                    .into(),
+            };
+            conditions.push(format!(
+                "({sort_column}, c.id) {comparison} ({}, {})",
+                bind(&mut values, cursor_value),
+                bind(&mut values, cursor.id.into())
+            ));
+        }
+
+        let limit = query.limit();
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let sql = format!(
+            "{CONTRACT_ROWS} {where_clause} ORDER BY {sort_column} {direction}, c.id {direction} LIMIT {}",
+            limit + 1
+        );
+        let mut rows = ContractListRow::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            sql,
+            values,
+        ))
+        .all(db)
+        .await?;
+
+        let next_cursor = if rows.len() > limit as usize {
+            rows.truncate(limit as usize);
+            rows.last().map(|row| {
+                let value = match query.sort {
+                    ContractSort::Created => row.created_at.to_rfc3339(),
+                    ContractSort::Collateral => row.total_collateral.to_string(),
+                    ContractSort::State => row.state.to_string(),
+                };
+                ContractCursor {
+                    value,
+                    id: row.id.clone(),
+                }
+                .encode()
+            })
+        } else {
+            None
+        };
+
+        Ok((rows.into_iter().map(Into::into).collect(), next_cursor))
+    }
+
+    /// A single contract row by id.
+    pub async fn find_row(
+        db: &DatabaseConnection,
+        id: &str,
+    ) -> Result<Option<ContractListItem>, DbErr> {
+        let sql = format!("{CONTRACT_ROWS} WHERE c.id = $1");
+        Ok(
+            ContractListRow::find_by_statement(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                [id.into()],
+            ))
+            .one(db)
+            .await?
+            .map(Into::into),
+        )
+    }
+}
 
 // implement your write-oriented logic here
 impl ActiveModel {}
 
 // implement your custom finders, selectors oriented logic here
 impl Entity {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_column_round_trip() {
+        for state in ContractState::ALL {
+            assert_eq!(ContractState::from_i16(state.as_i16()), Some(state));
+            assert_eq!(ContractState::from_name(state.name()), Some(state));
+        }
+        assert_eq!(ContractState::from_i16(0), None);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = ContractCursor {
+            value: "2025-06-01T00:00:00+00:00".to_string(),
+            id: "ab|cd".to_string(),
+        };
+        assert_eq!(ContractCursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(ContractCursor::decode("not hex"), None);
+    }
+}
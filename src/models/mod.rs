@@ -10,3 +10,13 @@ pub mod txout;
 pub mod users;
 pub mod version;
 pub mod balances;
+pub mod offer_expiries;
+pub mod close_proposals;
+pub mod contract_events;
+pub mod utxo_labels;
+pub mod wallet_table;
+pub mod prices;
+pub mod balance_valuations;
+pub mod webhooks;
+pub mod webhook_deliveries;
+pub mod psbt_reservations;
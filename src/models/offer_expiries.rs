@@ -0,0 +1,76 @@
+use super::_entities::offer_expiries::Column;
+pub use super::_entities::offer_expiries::{ActiveModel, Entity, Model};
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{entity::prelude::*, ActiveValue};
+pub type OfferExpiries = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    pub async fn find_by_contract_id(
+        db: &DatabaseConnection,
+        contract_id: &str,
+    ) -> Result<Option<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::ContractId.eq(contract_id))
+            .one(db)
+            .await
+    }
+
+    /// Returns the expiry of an offer, tracking it with a TTL counted from `offered_at` the first
+    /// time the offer is seen.
+    pub async fn find_or_track(
+        db: &DatabaseConnection,
+        contract_id: &str,
+        ttl_secs: u64,
+        offered_at: DateTime<Utc>,
+    ) -> Result<Self, DbErr> {
+        if let Some(expiry) = Self::find_by_contract_id(db, contract_id).await? {
+            return Ok(expiry);
+        }
+
+        let ttl = Duration::seconds(i64::try_from(ttl_secs).unwrap_or(i64::MAX / 1_000));
+        ActiveModel {
+            contract_id: ActiveValue::Set(contract_id.to_string()),
+            expires_at: ActiveValue::Set((offered_at + ttl).into()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expired_at.is_some() || self.expires_at <= now
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    pub async fn mark_expired(
+        db: &DatabaseConnection,
+        expiry: Model,
+        now: DateTime<Utc>,
+    ) -> Result<Model, DbErr> {
+        let mut expiry: Self = expiry.into();
+        expiry.expired_at = ActiveValue::Set(Some(now.into()));
+        expiry.update(db).await
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
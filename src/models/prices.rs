@@ -0,0 +1,79 @@
+use super::_entities::prices::Column;
+pub use super::_entities::prices::{ActiveModel, Entity, Model};
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder};
+pub type Prices = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// The most recently recorded price in a currency.
+    pub async fn latest(db: &DatabaseConnection, currency: &str) -> Result<Option<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::Currency.eq(currency))
+            .order_by_desc(Column::CreatedAt)
+            .order_by_desc(Column::Id)
+            .one(db)
+            .await
+    }
+
+    /// Every price recorded in a currency before `end`, oldest first.
+    pub async fn until(
+        db: &DatabaseConnection,
+        currency: &str,
+        end: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::Currency.eq(currency))
+            .filter(Column::CreatedAt.lt(end))
+            .order_by_asc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    pub fn amount_f64(&self) -> f64 {
+        f64::try_from(self.amount).unwrap_or_default()
+    }
+
+    /// Seconds since the price was recorded.
+    pub fn age_secs(&self) -> i64 {
+        (chrono::Utc::now() - self.created_at.to_utc()).num_seconds()
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// Records a price fetched from `source`.
+    pub async fn record(
+        db: &DatabaseConnection,
+        currency: &str,
+        amount: f64,
+        source: &str,
+    ) -> Result<Model, DbErr> {
+        Self {
+            currency: ActiveValue::Set(currency.to_string()),
+            amount: ActiveValue::Set(Decimal::from_f64_retain(amount).unwrap_or(Decimal::ZERO)),
+            source: ActiveValue::Set(source.to_string()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use super::_entities::psbt_reservations::Column;
+pub use super::_entities::psbt_reservations::{ActiveModel, Entity, Model};
+use bitcoin::{OutPoint, Txid};
+use sea_orm::{entity::prelude::*, ActiveValue, TransactionTrait};
+pub type PsbtReservations = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Outpoints spent by exported PSBTs that were neither broadcast nor abandoned yet.
+    pub async fn reserved_outpoints(db: &DatabaseConnection) -> Result<HashSet<OutPoint>, DbErr> {
+        Ok(Entity::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|reservation| reservation.outpoint.parse().ok())
+            .collect())
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// Reserves the inputs of an exported PSBT, keyed by the txid of its unsigned transaction.
+    pub async fn reserve(
+        db: &DatabaseConnection,
+        txid: &Txid,
+        outpoints: &[OutPoint],
+    ) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+        for outpoint in outpoints {
+            Self {
+                txid: ActiveValue::Set(txid.to_string()),
+                outpoint: ActiveValue::Set(outpoint.to_string()),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await
+    }
+
+    /// Releases the inputs of a PSBT once it was broadcast or abandoned. Returns how many coins
+    /// were released.
+    pub async fn release(db: &DatabaseConnection, txid: &Txid) -> Result<u64, DbErr> {
+        Ok(Entity::delete_many()
+            .filter(Column::Txid.eq(txid.to_string()))
+            .exec(db)
+            .await?
+            .rows_affected)
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
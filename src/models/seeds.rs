@@ -1,7 +1,8 @@
-use crate::models::_entities::seeds;
+use crate::common::seed_encryption::SeedKey;
 
 use super::_entities::seeds::Column;
 pub use super::_entities::seeds::{ActiveModel, Entity, Model};
+use bip39::Mnemonic;
 use bitcoin::{
     bip32::Xpriv,
     key::rand::{thread_rng, Fill},
@@ -28,15 +29,18 @@ impl ActiveModelBehavior for ActiveModel {
 
 // implement your read-oriented logic here
 impl Model {
-    /// Creates or loads the seed from the database with the given instance name.
+    /// Creates or loads the seed from the database with the given instance name. New seeds are
+    /// encrypted when a key is configured.
     ///
     /// # Errors
     ///
     /// - `DbErr` if the seed cannot be created or loaded.
+    /// - `loco_rs::Error` if the seed cannot be decrypted with `key`.
     pub async fn create_or_load_seed(
         db: &DatabaseConnection,
         name: &str,
         network: Network,
+        key: Option<&SeedKey>,
     ) -> Result<([u8; 32], Xpriv), loco_rs::Error> {
         let txn = db.begin().await?;
         let seed = Seeds::find()
@@ -44,39 +48,187 @@ impl Model {
             .one(&txn)
             .await?;
 
-        let (entropy, xprv) = if let Some(seed) = seed {
+        let entropy = if let Some(seed) = seed {
             tracing::info!("Loading seed from database for {}", name);
-            let mut entropy = [0u8; 32];
-            entropy.copy_from_slice(&seed.seed);
-            let xprv = Xpriv::new_master(network, &entropy).map_err(|e| {
-                loco_rs::Error::string(format!("Failed to create xprv from seed: {e}").as_str())
-            })?;
-            (entropy, xprv)
+            seed.entropy(key)?
         } else {
             tracing::info!("Creating new seed for {}", name);
             let mut entropy = [0; 32];
             entropy.try_fill(&mut thread_rng()).map_err(|e| {
                 loco_rs::Error::string(format!("Failed to fill entropy: {e}").as_str())
             })?;
-            let xprv = Xpriv::new_master(network, &entropy).map_err(|e| {
-                loco_rs::Error::string(format!("Failed to create xprv from seed: {e}").as_str())
-            })?;
-            let seed = seeds::ActiveModel {
-                name: ActiveValue::Set(name.to_string()),
-                seed: ActiveValue::Set(entropy.to_vec()),
-                ..Default::default()
-            };
-            seed.insert(&txn).await?;
-            (entropy, xprv)
+            ActiveModel::sealed(name, &entropy, key)?
+                .insert(&txn)
+                .await?;
+            entropy
         };
+        let xprv = Xpriv::new_master(network, &entropy).map_err(|e| {
+            loco_rs::Error::string(format!("Failed to create xprv from seed: {e}").as_str())
+        })?;
 
         txn.commit().await?;
         Ok((entropy, xprv))
     }
+
+    pub async fn find_by_name(db: &DatabaseConnection, name: &str) -> Result<Option<Self>, DbErr> {
+        Seeds::find().filter(Column::Name.eq(name)).one(db).await
+    }
+
+    /// The 32 bytes of entropy of the seed, decrypted with `key` if the seed is encrypted.
+    ///
+    /// # Errors
+    ///
+    /// - `loco_rs::Error` if the seed is encrypted and `key` is missing or wrong.
+    pub fn entropy(&self, key: Option<&SeedKey>) -> Result<[u8; 32], loco_rs::Error> {
+        if self.encrypted {
+            let key = key.ok_or_else(|| {
+                loco_rs::Error::string(
+                    format!(
+                        "Seed for {} is encrypted, set seed_passphrase or seed_key_file",
+                        self.name
+                    )
+                    .as_str(),
+                )
+            })?;
+            return key
+                .decrypt(&self.seed)
+                .map_err(|e| loco_rs::Error::string(e.to_string().as_str()));
+        }
+
+        if key.is_some() {
+            tracing::warn!(
+                "Seed for {} is stored unencrypted, run the encrypt_seeds task",
+                self.name
+            );
+        }
+        self.seed
+            .as_slice()
+            .try_into()
+            .map_err(|_| loco_rs::Error::string("Stored seed is not 32 bytes long"))
+    }
+
+    /// The seed entropy encoded as 24 words of the BIP39 word list. The node derives its keys
+    /// with `Xpriv::new_master` from the entropy itself, not from the BIP39 seed of the words, so
+    /// other wallets restoring them end up with different keys.
+    ///
+    /// # Errors
+    ///
+    /// - `loco_rs::Error` if the seed cannot be decrypted with `key`.
+    pub fn mnemonic(&self, key: Option<&SeedKey>) -> Result<Mnemonic, loco_rs::Error> {
+        Mnemonic::from_entropy(&self.entropy(key)?).map_err(|e| {
+            loco_rs::Error::string(format!("Failed to encode seed as mnemonic: {e}").as_str())
+        })
+    }
+}
+
+/// Parses a 24 word BIP39 mnemonic back into the 32 bytes of entropy the node seed is made of.
+///
+/// # Errors
+///
+/// - `loco_rs::Error` if the mnemonic is invalid or does not have 24 words.
+pub fn entropy_from_mnemonic(phrase: &str) -> Result<[u8; 32], loco_rs::Error> {
+    let mnemonic = Mnemonic::parse_normalized(phrase.trim())
+        .map_err(|e| loco_rs::Error::string(format!("Invalid mnemonic: {e}").as_str()))?;
+    mnemonic
+        .to_entropy()
+        .try_into()
+        .map_err(|_| loco_rs::Error::string("Mnemonic must have 24 words"))
 }
 
 // implement your write-oriented logic here
-impl ActiveModel {}
+impl ActiveModel {
+    /// A new seed row, encrypted when a key is given.
+    fn sealed(
+        name: &str,
+        entropy: &[u8; 32],
+        key: Option<&SeedKey>,
+    ) -> Result<Self, loco_rs::Error> {
+        let (seed, encrypted) = match key {
+            Some(key) => (
+                key.encrypt(entropy)
+                    .map_err(|e| loco_rs::Error::string(e.to_string().as_str()))?,
+                true,
+            ),
+            None => (entropy.to_vec(), false),
+        };
+        Ok(Self {
+            name: ActiveValue::Set(name.to_string()),
+            seed: ActiveValue::Set(seed),
+            encrypted: ActiveValue::Set(encrypted),
+            ..Default::default()
+        })
+    }
+
+    /// Restores the seed of `name` from a mnemonic. Only allowed while no seed exists for it, so
+    /// it must run before DDK initializes and generates a fresh one.
+    ///
+    /// # Errors
+    ///
+    /// - `loco_rs::Error` if the mnemonic is invalid or a seed already exists.
+    pub async fn restore_from_mnemonic(
+        db: &DatabaseConnection,
+        name: &str,
+        phrase: &str,
+        key: Option<&SeedKey>,
+    ) -> Result<Model, loco_rs::Error> {
+        let entropy = entropy_from_mnemonic(phrase)?;
+
+        let txn = db.begin().await?;
+        if Seeds::find()
+            .filter(Column::Name.eq(name))
+            .one(&txn)
+            .await?
+            .is_some()
+        {
+            return Err(loco_rs::Error::string(
+                format!("A seed already exists for {name}, refusing to overwrite it").as_str(),
+            ));
+        }
+        let seed = Self::sealed(name, &entropy, key)?.insert(&txn).await?;
+        txn.commit().await?;
+
+        tracing::info!("Restored seed for {} from mnemonic", name);
+        Ok(seed)
+    }
+
+    /// Encrypts every seed still stored in plain text. Returns the names of the encrypted seeds.
+    ///
+    /// # Errors
+    ///
+    /// - `loco_rs::Error` if a seed cannot be encrypted or saved.
+    pub async fn encrypt_unencrypted(
+        db: &DatabaseConnection,
+        key: &SeedKey,
+    ) -> Result<Vec<String>, loco_rs::Error> {
+        let txn = db.begin().await?;
+        let plain = Seeds::find()
+            .filter(Column::Encrypted.eq(false))
+            .all(&txn)
+            .await?;
+
+        let mut names = Vec::with_capacity(plain.len());
+        for seed in plain {
+            let entropy = seed.entropy(None)?;
+            let sealed = key
+                .encrypt(&entropy)
+                .map_err(|e| loco_rs::Error::string(e.to_string().as_str()))?;
+            // Never store a seed we could not read back.
+            if key.decrypt(&sealed).ok() != Some(entropy) {
+                return Err(loco_rs::Error::string(
+                    format!("Failed to verify encrypted seed for {}", seed.name).as_str(),
+                ));
+            }
+
+            names.push(seed.name.clone());
+            let mut seed: Self = seed.into();
+            seed.seed = ActiveValue::Set(sealed);
+            seed.encrypted = ActiveValue::Set(true);
+            seed.update(&txn).await?;
+        }
+        txn.commit().await?;
+        Ok(names)
+    }
+}
 
 // implement your custom finders, selectors oriented logic here
 impl Entity {}
@@ -20,3 +20,7 @@ impl ActiveModel {}
 
 // implement your custom finders, selectors oriented logic here
 impl Entity {}
+
+impl super::wallet_table::WalletTable for Entity {
+    const WALLET_NAME: super::_entities::tx::Column = super::_entities::tx::Column::WalletName;
+}
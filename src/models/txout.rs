@@ -20,3 +20,8 @@ impl ActiveModel {}
 
 // implement your custom finders, selectors oriented logic here
 impl Entity {}
+
+impl super::wallet_table::WalletTable for Entity {
+    const WALLET_NAME: super::_entities::txout::Column =
+        super::_entities::txout::Column::WalletName;
+}
@@ -0,0 +1,103 @@
+use std::collections::{HashMap, HashSet};
+
+use super::_entities::utxo_labels::Column;
+pub use super::_entities::utxo_labels::{ActiveModel, Entity, Model};
+use bitcoin::OutPoint;
+use sea_orm::{entity::prelude::*, ActiveValue};
+pub type UtxoLabels = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    pub async fn find_by_outpoint(
+        db: &DatabaseConnection,
+        outpoint: &OutPoint,
+    ) -> Result<Option<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::Outpoint.eq(outpoint.to_string()))
+            .one(db)
+            .await
+    }
+
+    /// Labels and flags of every UTXO that has any, keyed by outpoint.
+    pub async fn by_outpoint(db: &DatabaseConnection) -> Result<HashMap<OutPoint, Self>, DbErr> {
+        Ok(Entity::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|label| Some((label.outpoint.parse().ok()?, label)))
+            .collect())
+    }
+
+    /// Outpoints that must not be spent until they are unfrozen.
+    pub async fn frozen_outpoints(db: &DatabaseConnection) -> Result<HashSet<OutPoint>, DbErr> {
+        Ok(Entity::find()
+            .filter(Column::Frozen.eq(true))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|label| label.outpoint.parse().ok())
+            .collect())
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    async fn upsert(
+        db: &DatabaseConnection,
+        outpoint: &OutPoint,
+        update: impl FnOnce(&mut Self),
+    ) -> Result<Model, DbErr> {
+        let mut label = match Model::find_by_outpoint(db, outpoint).await? {
+            Some(existing) => existing.into(),
+            None => Self {
+                outpoint: ActiveValue::Set(outpoint.to_string()),
+                frozen: ActiveValue::Set(false),
+                ..Default::default()
+            },
+        };
+        update(&mut label);
+        label.save(db).await?.try_into_model()
+    }
+
+    /// Sets or clears the label of a UTXO.
+    pub async fn set_label(
+        db: &DatabaseConnection,
+        outpoint: &OutPoint,
+        label: Option<String>,
+    ) -> Result<Model, DbErr> {
+        Self::upsert(db, outpoint, |model| {
+            model.label = ActiveValue::Set(label);
+        })
+        .await
+    }
+
+    pub async fn set_frozen(
+        db: &DatabaseConnection,
+        outpoint: &OutPoint,
+        frozen: bool,
+    ) -> Result<Model, DbErr> {
+        Self::upsert(db, outpoint, |model| {
+            model.frozen = ActiveValue::Set(frozen);
+        })
+        .await
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
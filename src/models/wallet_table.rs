@@ -0,0 +1,17 @@
+use sea_orm::entity::prelude::*;
+
+/// Tables bdk persists for every DDK wallet, keyed by the wallet's name.
+pub trait WalletTable: EntityTrait {
+    const WALLET_NAME: Self::Column;
+}
+
+/// Rows persisted by the DDK wallet named `wallet_name`.
+pub async fn find_by_wallet<E: WalletTable>(
+    db: &DatabaseConnection,
+    wallet_name: &str,
+) -> Result<Vec<E::Model>, DbErr> {
+    E::find()
+        .filter(E::WALLET_NAME.eq(wallet_name))
+        .all(db)
+        .await
+}
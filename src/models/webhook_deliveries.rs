@@ -0,0 +1,132 @@
+use super::_entities::webhook_deliveries::Column;
+pub use super::_entities::webhook_deliveries::{ActiveModel, Entity, Model};
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder};
+use serde::{Deserialize, Serialize};
+pub type WebhookDeliveries = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// States of the `status` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Queued or being retried.
+    Pending,
+    Delivered,
+    /// Every attempt failed.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// Deliveries to a webhook, newest first.
+    pub async fn for_webhook(db: &DatabaseConnection, webhook_id: i32) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::WebhookId.eq(webhook_id))
+            .order_by_desc(Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// Pending deliveries whose next attempt is due at `now`, oldest first.
+    pub async fn due(
+        db: &DatabaseConnection,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::Status.eq(DeliveryStatus::Pending.name()))
+            .filter(Column::NextAttemptAt.lte(now))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// Sets when the delivery is next attempted.
+    pub async fn schedule(
+        self,
+        db: &DatabaseConnection,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Self, DbErr> {
+        let mut delivery: ActiveModel = self.into();
+        delivery.next_attempt_at = ActiveValue::Set(Some(at.into()));
+        delivery.update(db).await
+    }
+
+    /// Records the outcome of one more attempt. Only pending deliveries keep their next attempt.
+    pub async fn attempted(
+        self,
+        db: &DatabaseConnection,
+        status: DeliveryStatus,
+        response_status: Option<i32>,
+        error: Option<String>,
+    ) -> Result<Self, DbErr> {
+        let attempts = self.attempts + 1;
+        let mut delivery: ActiveModel = self.into();
+        delivery.attempts = ActiveValue::Set(attempts);
+        delivery.status = ActiveValue::Set(status.name().to_string());
+        delivery.response_status = ActiveValue::Set(response_status);
+        delivery.error = ActiveValue::Set(error);
+        if status != DeliveryStatus::Pending {
+            delivery.next_attempt_at = ActiveValue::Set(None);
+        }
+        if status == DeliveryStatus::Delivered {
+            delivery.delivered_at = ActiveValue::Set(Some(chrono::Utc::now().into()));
+        }
+        delivery.update(db).await
+    }
+
+    /// Queues the same payload again as a new delivery, keeping this one in the log.
+    pub async fn replay(&self, db: &DatabaseConnection) -> Result<Self, DbErr> {
+        ActiveModel::enqueue(db, self.webhook_id, &self.event, self.payload.clone()).await
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// Logs a delivery that is due right away.
+    pub async fn enqueue(
+        db: &DatabaseConnection,
+        webhook_id: i32,
+        event: &str,
+        payload: Json,
+    ) -> Result<Model, DbErr> {
+        Self {
+            webhook_id: ActiveValue::Set(webhook_id),
+            event: ActiveValue::Set(event.to_string()),
+            payload: ActiveValue::Set(payload),
+            status: ActiveValue::Set(DeliveryStatus::Pending.name().to_string()),
+            attempts: ActiveValue::Set(0),
+            next_attempt_at: ActiveValue::Set(Some(chrono::Utc::now().into())),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
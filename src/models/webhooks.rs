@@ -0,0 +1,84 @@
+use super::_entities::webhooks::Column;
+pub use super::_entities::webhooks::{ActiveModel, Entity, Model};
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder};
+pub type Webhooks = Entity;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    pub async fn list(db: &DatabaseConnection) -> Result<Vec<Self>, DbErr> {
+        Entity::find().order_by_asc(Column::Id).all(db).await
+    }
+
+    /// Active webhooks that receive `event`.
+    pub async fn subscribed(db: &DatabaseConnection, event: &str) -> Result<Vec<Self>, DbErr> {
+        Ok(Entity::find()
+            .filter(Column::Active.eq(true))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|webhook| webhook.wants(event))
+            .collect())
+    }
+
+    /// Event types the webhook receives, every type when empty.
+    pub fn events(&self) -> Vec<String> {
+        serde_json::from_value(self.events.clone()).unwrap_or_default()
+    }
+
+    pub fn wants(&self, event: &str) -> bool {
+        let events = self.events();
+        events.is_empty() || events.iter().any(|wanted| wanted == event)
+    }
+
+    /// `sha256=` and the hex HMAC-SHA256, keyed with the webhook's secret, of the timestamp, a
+    /// dot and the body. Receivers recompute it to check a payload came from us.
+    pub fn sign(&self, timestamp: i64, body: &[u8]) -> String {
+        let mut engine = hmac::HmacEngine::<sha256::Hash>::new(self.secret.as_bytes());
+        engine.input(timestamp.to_string().as_bytes());
+        engine.input(b".");
+        engine.input(body);
+        let mac = hmac::Hmac::<sha256::Hash>::from_engine(engine);
+        format!("sha256={}", hex::encode(mac.to_byte_array()))
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    pub async fn register(
+        db: &DatabaseConnection,
+        url: &str,
+        secret: &str,
+        events: &[String],
+    ) -> Result<Model, DbErr> {
+        Self {
+            url: ActiveValue::Set(url.to_string()),
+            secret: ActiveValue::Set(secret.to_string()),
+            events: ActiveValue::Set(serde_json::json!(events)),
+            active: ActiveValue::Set(true),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
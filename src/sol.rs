@@ -4,6 +4,7 @@ use bitcoin::io::Write;
 use bitcoin::key::rand::Fill;
 use bitcoin::Network;
 use ddk::builder::Builder;
+use ddk::chain::EsploraClient;
 use ddk::storage::postgres::PostgresStore;
 use ddk::DlcDevKit;
 use ernest_oracle::ErnestOracleClient;
@@ -15,8 +16,11 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::common::events::EventBus;
 use crate::common::nostr::Nostr;
+use crate::common::seed_encryption::SeedKey;
 use crate::common::settings::Settings;
+use crate::common::sync::Syncer;
 use crate::models::_entities::seeds;
 
 type SonsOfLiberyDdk = DlcDevKit<Squawkbox, PostgresStore, ErnestOracleClient>;
@@ -24,7 +28,17 @@ type SonsOfLiberyDdk = DlcDevKit<Squawkbox, PostgresStore, ErnestOracleClient>;
 #[derive(Clone)]
 pub struct SonsOfLiberty {
     pub dlcdevkit: Arc<SonsOfLiberyDdk>,
+    /// Esplora client used when building contract messages outside of DDK's manager.
+    pub blockchain: Arc<EsploraClient>,
+    pub network: Network,
     pub nostr: Nostr,
+    /// Every configured oracle, starting with the one DDK uses to close contracts.
+    pub oracles: Vec<Arc<ErnestOracleClient>>,
+    pub settings: Settings,
+    /// Shared by the background sync loop and manual syncs.
+    pub sync: Arc<Syncer>,
+    /// Contract and wallet changes found after each sync, streamed to the frontend.
+    pub events: Arc<EventBus>,
 }
 
 impl SonsOfLiberty {
@@ -33,11 +47,13 @@ impl SonsOfLiberty {
             loco_rs::Error::string(format!("Invalid network: {}", settings.network).as_str())
         })?;
 
-        let (entropy, _) = seeds::Model::create_or_load_seed(&ctx.db, &settings.name, network)
-            .await
-            .map_err(|e| {
-                loco_rs::Error::string(format!("Failed to create or load seed: {e}").as_str())
-            })?;
+        let seed_key = SeedKey::configured(settings)?;
+        let (entropy, _) =
+            seeds::Model::create_or_load_seed(&ctx.db, &settings.name, network, seed_key.as_ref())
+                .await
+                .map_err(|e| {
+                    loco_rs::Error::string(format!("Failed to create or load seed: {e}").as_str())
+                })?;
 
         let storage = Arc::new(
             PostgresStore::new(&ctx.config.database.uri, true, settings.name.clone())
@@ -67,6 +83,25 @@ impl SonsOfLiberty {
                 })?,
         );
 
+        let mut oracles = vec![oracle.clone()];
+        for oracle_host in &settings.oracle_hosts {
+            let additional_oracle = ErnestOracleClient::new(oracle_host).await.map_err(|e| {
+                loco_rs::Error::string(&format!(
+                    "Failed to create ernest oracle for {oracle_host}: {}",
+                    e.reason
+                ))
+            })?;
+            oracles.push(Arc::new(additional_oracle));
+        }
+
+        if settings.oracle_threshold == 0 || settings.oracle_threshold as usize > oracles.len() {
+            return Err(loco_rs::Error::string(&format!(
+                "Oracle threshold {} must be between 1 and the {} configured oracles",
+                settings.oracle_threshold,
+                oracles.len()
+            )));
+        }
+
         let nostr = Nostr::new(&entropy, vec![settings.nostr_relay.clone()])
             .await
             .map_err(|e| loco_rs::Error::string(format!("Failed to create nostr: {e}").as_str()))?;
@@ -87,7 +122,20 @@ impl SonsOfLiberty {
                 })?,
         );
 
-        Ok(Self { dlcdevkit, nostr })
+        let blockchain = Arc::new(EsploraClient::new(&settings.esplora_host, network).map_err(
+            |e| loco_rs::Error::string(format!("Failed to create esplora client: {e}").as_str()),
+        )?);
+
+        Ok(Self {
+            dlcdevkit,
+            blockchain,
+            network,
+            nostr,
+            oracles,
+            settings: settings.clone(),
+            sync: Arc::new(Syncer::new(settings.sync_interval_secs)),
+            events: Arc::new(EventBus::default()),
+        })
     }
 }
 
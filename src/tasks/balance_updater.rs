@@ -2,7 +2,11 @@ use std::sync::Arc;
 
 use crate::{
     app::SONS_OF_LIBERTY,
-    common::{bitcoin_price::get_bitcoin_price, settings::Settings},
+    common::{
+        bitcoin_price::{get_bitcoin_price, get_fiat_prices},
+        dlcdevkit::record_contract_events,
+        settings::Settings,
+    },
     models::_entities::balances,
     sol::SonsOfLiberty,
 };
@@ -21,7 +25,6 @@ impl Task for BalanceUpdater {
         }
     }
     async fn run(&self, app_context: &AppContext, _vars: &task::Vars) -> Result<()> {
-        let price = get_bitcoin_price().await?;
         let settings = match &app_context.config.settings {
             Some(settings) => Settings::from_json(settings)?,
             None => Settings::default(),
@@ -44,6 +47,9 @@ impl Task for BalanceUpdater {
             )
         })?;
 
+        let price = get_bitcoin_price(&app_context.db, &settings, Some(ddk.as_ref())).await?;
+        let valuations = get_fiat_prices(&app_context.db, &settings, Some(ddk.as_ref())).await?;
+
         // get sat value, amount, price, pnl, contract balance, and amount of contracts, then store in db..
 
         let num_contracts = ddk
@@ -59,11 +65,14 @@ impl Task for BalanceUpdater {
             })?
             .len();
 
+        record_contract_events(&app_context.db, ddk).await?;
+
         balances::ActiveModel::create_balance_update(
             &app_context.db,
             &settings,
             balance,
             price.amount,
+            &valuations,
             num_contracts,
         )
         .await?;
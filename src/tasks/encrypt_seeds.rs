@@ -0,0 +1,37 @@
+use loco_rs::prelude::*;
+
+use crate::{
+    common::{seed_encryption::SeedKey, settings::Settings},
+    models::seeds,
+};
+
+pub struct EncryptSeeds;
+#[async_trait]
+impl Task for EncryptSeeds {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "encrypt_seeds".to_string(),
+            detail: "Encrypts seeds stored in plain text with the configured seed_passphrase or \
+                     seed_key_file."
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let settings = match &app_context.config.settings {
+            Some(settings) => Settings::from_json(settings)?,
+            None => Settings::default(),
+        };
+        let key = SeedKey::configured(&settings)?.ok_or_else(|| {
+            Error::string("Set seed_passphrase or seed_key_file to encrypt the seeds")
+        })?;
+
+        let names = seeds::ActiveModel::encrypt_unencrypted(&app_context.db, &key).await?;
+        if names.is_empty() {
+            println!("Every seed is already encrypted.");
+        } else {
+            println!("Encrypted seeds: {}", names.join(", "));
+        }
+        Ok(())
+    }
+}
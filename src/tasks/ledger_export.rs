@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use crate::{
+    app::SONS_OF_LIBERTY,
+    common::{bitcoin_price::DEFAULT_CURRENCY, ledger, settings::Settings},
+    sol::SonsOfLiberty,
+};
+use loco_rs::prelude::*;
+
+pub struct LedgerExport;
+#[async_trait]
+impl Task for LedgerExport {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "ledger_export".to_string(),
+            detail: "Exports the wallet's confirmed activity of a tax year with fiat values and \
+                     realized gains: year:<year> [currency:<code>] [format:csv|json] \
+                     [output:<path>]"
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, vars: &task::Vars) -> Result<()> {
+        let settings = match &app_context.config.settings {
+            Some(settings) => Settings::from_json(settings)?,
+            None => Settings::default(),
+        };
+        let year = vars
+            .cli
+            .get("year")
+            .ok_or_else(|| Error::string("Missing year:<year>"))?
+            .parse::<i32>()
+            .map_err(|e| Error::string(format!("Invalid year: {e}").as_str()))?;
+        let currency = vars
+            .cli
+            .get("currency")
+            .map_or(DEFAULT_CURRENCY, String::as_str);
+        let csv = match vars.cli.get("format").map(String::as_str) {
+            None | Some("csv") => true,
+            Some("json") => false,
+            Some(format) => {
+                return Err(Error::string(
+                    format!("Unknown format {format}, expected csv or json").as_str(),
+                ))
+            }
+        };
+
+        let sol = SONS_OF_LIBERTY
+            .get_or_init(|| async {
+                tracing::warn!("Initializing DDK");
+                Arc::new(
+                    SonsOfLiberty::new(&settings, app_context)
+                        .await
+                        .expect("Failed to initialize DDK"),
+                )
+            })
+            .await;
+
+        let ledger = ledger::build_ledger(&app_context.db, sol, year, currency).await?;
+        let output = if csv {
+            ledger.to_csv()
+        } else {
+            serde_json::to_string_pretty(&ledger)?
+        };
+        match vars.cli.get("output") {
+            Some(path) => {
+                std::fs::write(path, output)?;
+                println!(
+                    "Wrote {} entries of {} to {path}, realized gain {} {}",
+                    ledger.entries.len(),
+                    ledger.year,
+                    ledger.totals.realized_gain,
+                    ledger.currency
+                );
+            }
+            None => print!("{output}"),
+        }
+        Ok(())
+    }
+}
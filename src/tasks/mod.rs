@@ -1,3 +1,9 @@
 pub mod balance_updater;
+pub mod encrypt_seeds;
+pub mod ledger_export;
+pub mod offer_expiry;
+pub mod rotate_seed;
+pub mod seed_backup;
+pub mod seed_restore;
 
-pub mod freshdb;
\ No newline at end of file
+pub mod freshdb;
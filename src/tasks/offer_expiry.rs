@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use crate::{
+    app::SONS_OF_LIBERTY,
+    common::{offer_expiry::expire_offers, settings::Settings},
+    sol::SonsOfLiberty,
+};
+use loco_rs::prelude::*;
+
+pub struct OfferExpiry;
+#[async_trait]
+impl Task for OfferExpiry {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "offer_expiry".to_string(),
+            detail:
+                "Rejects offers that are past their TTL or whose oracle event matures too soon."
+                    .to_string(),
+        }
+    }
+    async fn run(&self, app_context: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let settings = match &app_context.config.settings {
+            Some(settings) => Settings::from_json(settings)?,
+            None => Settings::default(),
+        };
+
+        let ddk = SONS_OF_LIBERTY
+            .get_or_init(|| async {
+                tracing::warn!("Initializing DDK");
+                Arc::new(
+                    SonsOfLiberty::new(&settings, app_context)
+                        .await
+                        .expect("Failed to initialize DDK"),
+                )
+            })
+            .await;
+
+        let expired = expire_offers(&app_context.db, ddk).await?;
+        tracing::info!("Expired {} offers", expired.len());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use crate::{
+    app::SONS_OF_LIBERTY,
+    common::{
+        dlcdevkit,
+        seed_encryption::SeedKey,
+        settings::Settings,
+        wallet::{self, Coin, SendAmount},
+    },
+    models::seeds,
+    sol::SonsOfLiberty,
+};
+use ddk_manager::{contract::Contract, Storage};
+use loco_rs::prelude::*;
+
+/// Confirmation target of the sweep when no fee rate is given.
+const SWEEP_CONF_TARGET: u16 = 6;
+
+pub struct RotateSeed;
+#[async_trait]
+impl Task for RotateSeed {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "rotate_seed".to_string(),
+            detail: "Creates a seed under a new wallet name and prints its backup words. Once \
+                     they are stored, rerun with backed_up:true to sweep the free funds to it: \
+                     new_name:<name> [backed_up:true] [fee_rate:<sat/vB>] [dry_run:true]"
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, vars: &task::Vars) -> Result<()> {
+        let settings = match &app_context.config.settings {
+            Some(settings) => Settings::from_json(settings)?,
+            None => Settings::default(),
+        };
+        let new_name = vars
+            .cli
+            .get("new_name")
+            .ok_or_else(|| Error::string("Missing new_name:<wallet name>"))?
+            .clone();
+        if new_name == settings.name {
+            return Err(Error::string(
+                "new_name must differ from the current wallet name",
+            ));
+        }
+        let dry_run = vars.cli.get("dry_run").is_some_and(|value| value == "true");
+        let backed_up = vars
+            .cli
+            .get("backed_up")
+            .is_some_and(|value| value == "true");
+        let fee_rate = vars
+            .cli
+            .get("fee_rate")
+            .map(|fee_rate| match fee_rate.parse::<u64>() {
+                Ok(0) => Err(Error::string("fee_rate must be at least 1 sat/vB")),
+                Ok(fee_rate) => Ok(fee_rate),
+                Err(e) => Err(Error::string(format!("Invalid fee_rate: {e}").as_str())),
+            })
+            .transpose()?;
+
+        let old = SONS_OF_LIBERTY
+            .get_or_init(|| async {
+                tracing::warn!("Initializing DDK");
+                Arc::new(
+                    SonsOfLiberty::new(&settings, app_context)
+                        .await
+                        .expect("Failed to initialize DDK"),
+                )
+            })
+            .await;
+        old.dlcdevkit
+            .wallet
+            .sync()
+            .await
+            .map_err(|e| Error::string(format!("Failed to sync wallet: {e}").as_str()))?;
+
+        // Coins funding pending contracts stay with the old key until those contracts settle.
+        let reserved = wallet::reserved_outpoints(old).await?;
+        let coins = dlcdevkit::get_utxos(old)?
+            .into_iter()
+            .filter(|utxo| !utxo.is_spent && !reserved.contains(&utxo.outpoint))
+            .map(Coin::from)
+            .collect::<Vec<_>>();
+        let fee_rate = match fee_rate {
+            Some(fee_rate) => fee_rate,
+            None => wallet::estimate_fee_rate(old, SWEEP_CONF_TARGET).await?,
+        };
+
+        if dry_run {
+            // Same script type as the new wallet's addresses, so the fee estimate holds.
+            let destination = wallet::change_script_pubkey(&app_context.db, old).await?;
+            report_sweep(coins, &destination, fee_rate)?;
+        } else {
+            if seeds::Model::find_by_name(&app_context.db, &new_name)
+                .await?
+                .is_some()
+            {
+                println!("Reusing the existing seed of {new_name}");
+            }
+            let new_settings = Settings {
+                name: new_name.clone(),
+                ..settings.clone()
+            };
+            let new = Arc::new(SonsOfLiberty::new(&new_settings, app_context).await?);
+
+            // Nothing is swept to a seed that only exists in this database.
+            if !backed_up {
+                let seed = seeds::Model::find_by_name(&app_context.db, &new_name)
+                    .await?
+                    .ok_or_else(|| Error::string("The new seed was not stored"))?;
+                let mnemonic = seed.mnemonic(SeedKey::configured(&new_settings)?.as_ref())?;
+                println!("Backup words of {new_name}:\n\n  {mnemonic}\n");
+                println!(
+                    "They only restore this node and are not a BIP39 seed. Store them, then \
+                     rerun with backed_up:true to sweep the free funds."
+                );
+                return Ok(());
+            }
+
+            let address = dlcdevkit::get_new_addresses(new).await?;
+            println!("New wallet {new_name} receives at {address}");
+
+            if let Some(plan) = report_sweep(coins, &address.script_pubkey(), fee_rate)? {
+                let tx = wallet::sign_psbt(old, wallet::build_psbt(&plan, &[])?).await?;
+                wallet::broadcast(old, &tx).await?;
+                println!("Broadcast sweep {}", tx.compute_txid());
+            }
+        }
+
+        report_bound_contracts(old).await?;
+        println!(
+            "Switch `name` to {new_name} once the contracts above are settled, then retire the \
+             seed of {}.",
+            settings.name
+        );
+        Ok(())
+    }
+}
+
+fn report_sweep(
+    coins: Vec<Coin>,
+    destination: &bitcoin::ScriptBuf,
+    fee_rate: u64,
+) -> Result<Option<wallet::TxPlan>> {
+    if coins.is_empty() {
+        println!("No free coins to sweep");
+        return Ok(None);
+    }
+    let plan = wallet::plan_send(coins, destination, SendAmount::Max, fee_rate, destination)
+        .map_err(|e| Error::string(e.to_string().as_str()))?;
+    println!(
+        "Sweeping {} coins: {} sats to the new wallet, {} sats fee at {} sat/vB",
+        plan.inputs.len(),
+        plan.amount,
+        plan.fee,
+        fee_rate
+    );
+    Ok(Some(plan))
+}
+
+/// Prints contracts whose funding or payout keys come from the old seed.
+async fn report_bound_contracts(sol: &SonsOfLiberty) -> Result<()> {
+    let contracts = sol
+        .dlcdevkit
+        .storage
+        .get_contracts()
+        .await
+        .map_err(|e| Error::string(format!("Failed to get contracts: {e}").as_str()))?;
+
+    let bound = contracts
+        .iter()
+        .filter(|contract| match contract {
+            Contract::Offered(offered) => offered.is_offer_party,
+            Contract::Accepted(_)
+            | Contract::Signed(_)
+            | Contract::Confirmed(_)
+            | Contract::PreClosed(_) => true,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+
+    if bound.is_empty() {
+        println!("No contracts are bound to the old seed");
+        return Ok(());
+    }
+    println!("Contracts still bound to the old seed:");
+    for contract in bound {
+        println!(
+            "  {} {} with {}",
+            hex::encode(contract.get_id()),
+            dlcdevkit::contract_state(contract),
+            contract.get_counter_party_id()
+        );
+    }
+    Ok(())
+}
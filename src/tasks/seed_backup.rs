@@ -0,0 +1,36 @@
+use loco_rs::prelude::*;
+
+use crate::{
+    common::{seed_encryption::SeedKey, settings::Settings},
+    models::seeds,
+};
+
+pub struct SeedBackup;
+#[async_trait]
+impl Task for SeedBackup {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "seed_backup".to_string(),
+            detail: "Prints the node seed as a BIP39 mnemonic. Write it down offline.".to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, vars: &task::Vars) -> Result<()> {
+        let settings = match &app_context.config.settings {
+            Some(settings) => Settings::from_json(settings)?,
+            None => Settings::default(),
+        };
+        let name = vars.cli.get("name").unwrap_or(&settings.name);
+
+        let seed = seeds::Model::find_by_name(&app_context.db, name)
+            .await?
+            .ok_or_else(|| Error::string(format!("No seed found for {name}").as_str()))?;
+
+        println!("Seed mnemonic for {name}. Anyone with these words controls the funds:");
+        println!(
+            "{}",
+            seed.mnemonic(SeedKey::configured(&settings)?.as_ref())?
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use loco_rs::prelude::*;
+
+use crate::{
+    common::{seed_encryption::SeedKey, settings::Settings},
+    models::seeds,
+};
+
+pub struct SeedRestore;
+#[async_trait]
+impl Task for SeedRestore {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "seed_restore".to_string(),
+            detail: "Restores the node seed from a BIP39 mnemonic. Run it before the first start \
+                     on an empty database: mnemonic:\"word1 ... word24\""
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, vars: &task::Vars) -> Result<()> {
+        let settings = match &app_context.config.settings {
+            Some(settings) => Settings::from_json(settings)?,
+            None => Settings::default(),
+        };
+        let name = vars.cli.get("name").unwrap_or(&settings.name);
+        let mnemonic = vars
+            .cli
+            .get("mnemonic")
+            .ok_or_else(|| Error::string("Missing mnemonic:\"word1 ... word24\""))?;
+
+        let key = SeedKey::configured(&settings)?;
+        seeds::ActiveModel::restore_from_mnemonic(&app_context.db, name, mnemonic, key.as_ref())
+            .await?;
+        println!("Restored seed for {name}. DDK will use it on the next start.");
+        Ok(())
+    }
+}
@@ -1,12 +1,194 @@
 pub use crate::models::_entities::balances::Model as Balances;
-use chrono::{DateTime, Utc};
+use crate::{common::bitcoin_price::DEFAULT_CURRENCY, models::_entities::balance_valuations};
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::prelude::{DateTimeWithTimeZone, Decimal};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+/// Balance history in a window given either as a period ending at `reference_date`, or as an
+/// explicit `start` and `end`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct BalanceHistoryRequest {
-    pub time_period: TimePeriod,
+    #[serde(default)]
+    pub time_period: Option<TimePeriod>,
     #[serde(default = "default_reference_date")]
     pub reference_date: DateTime<Utc>,
+    /// Start of the window, instead of `time_period`.
+    #[serde(default)]
+    pub start: Option<DateTime<Utc>>,
+    /// End of the window, `reference_date` by default.
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+    /// Size of the buckets snapshots are downsampled to, e.g. `5m`, `1h` or `1d`.
+    #[serde(default)]
+    pub bucket: Option<Bucket>,
+    #[serde(default)]
+    pub aggregate: HistoryAggregate,
+    /// Currency to value the history in. Without one, snapshots are returned as stored.
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+impl BalanceHistoryRequest {
+    /// Start and end of the requested window, if one was requested.
+    pub fn window(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let end = self.end.unwrap_or(self.reference_date);
+        let start = match (self.start, self.time_period) {
+            (Some(start), _) => start,
+            (None, Some(time_period)) => time_period.start(end),
+            (None, None) => return None,
+        };
+        Some((start, end))
+    }
+}
+
+/// How the snapshots of a bucket are combined.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryAggregate {
+    /// The last snapshot of each bucket.
+    #[default]
+    Last,
+    /// Open, high, low and close of each bucket.
+    Ohlc,
+}
+
+/// Width of a history bucket, written as a count and a unit of `m`, `h`, `d` or `w`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Bucket {
+    pub seconds: i64,
+}
+
+impl FromStr for Bucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid bucket '{s}', expected e.g. 5m, 1h or 1d");
+        let unit = s.chars().last().ok_or_else(invalid)?;
+        let count = s[..s.len() - unit.len_utf8()]
+            .parse::<i64>()
+            .map_err(|_| invalid())?;
+        let unit_seconds = match unit {
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 60 * 60 * 24,
+            'w' => 60 * 60 * 24 * 7,
+            _ => return Err(invalid()),
+        };
+        if count <= 0 {
+            return Err(invalid());
+        }
+        count
+            .checked_mul(unit_seconds)
+            .map(|seconds| Self { seconds })
+            .ok_or_else(invalid)
+    }
+}
+
+impl TryFrom<String> for Bucket {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Bucket> for String {
+    fn from(bucket: Bucket) -> Self {
+        bucket.to_string()
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, unit_seconds) = [('w', 60 * 60 * 24 * 7), ('d', 60 * 60 * 24), ('h', 60 * 60)]
+            .into_iter()
+            .find(|(_, unit_seconds)| self.seconds % unit_seconds == 0)
+            .unwrap_or(('m', 60));
+        write!(f, "{}{unit}", self.seconds / unit_seconds)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ohlc<T> {
+    pub open: T,
+    pub high: T,
+    pub low: T,
+    pub close: T,
+}
+
+/// Snapshots of one bucket. Balances are the sum of the wallet and contract balances.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BalanceCandle {
+    pub bucket: DateTimeWithTimeZone,
+    pub samples: i64,
+    pub currency: String,
+    pub bitcoin_price: Ohlc<Decimal>,
+    pub balance_sats: Ohlc<i64>,
+    pub balance: Ohlc<Decimal>,
+    pub pnl_sats: i64,
+    pub num_contracts: i64,
+}
+
+/// A balance snapshot valued in one currency, with the price it was valued at.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FiatBalance {
+    pub id: i32,
+    pub created_at: DateTimeWithTimeZone,
+    pub currency: String,
+    pub bitcoin_price: Decimal,
+    pub bitcoin_balance_sats: i64,
+    pub bitcoin_balance: Decimal,
+    pub contract_balance_sats: i64,
+    pub contract_balance: Decimal,
+    pub pnl_sats: i64,
+    pub pnl: Decimal,
+    pub num_contracts: i64,
+    pub name: String,
+    pub network: String,
+}
+
+impl FiatBalance {
+    /// Combines a snapshot with its valuation in `currency`. USD snapshots taken before
+    /// valuations were stored fall back to the snapshot's own USD columns.
+    pub fn new(
+        balance: Balances,
+        valuation: Option<balance_valuations::Model>,
+        currency: &str,
+    ) -> Option<Self> {
+        let (bitcoin_price, bitcoin_balance, contract_balance, pnl) = match valuation {
+            Some(valuation) => (
+                valuation.bitcoin_price,
+                valuation.bitcoin_balance,
+                valuation.contract_balance,
+                valuation.pnl,
+            ),
+            None if currency == DEFAULT_CURRENCY => (
+                balance.bitcoin_price,
+                balance.bitcoin_balance_usd,
+                balance.contract_balance_usd,
+                balance.pnl_usd,
+            ),
+            None => return None,
+        };
+        Some(Self {
+            id: balance.id,
+            created_at: balance.created_at,
+            currency: currency.to_string(),
+            bitcoin_price,
+            bitcoin_balance_sats: balance.bitcoin_balance_sats,
+            bitcoin_balance,
+            contract_balance_sats: balance.contract_balance_sats,
+            contract_balance,
+            pnl_sats: balance.pnl_sats,
+            pnl,
+            num_contracts: balance.num_contracts,
+            name: balance.name,
+            network: balance.network,
+        })
+    }
 }
 
 // Define the time period enum for the API request
@@ -20,8 +202,114 @@ pub enum TimePeriod {
     Month,
     #[serde(rename = "1y")]
     Year,
+    #[serde(rename = "all")]
+    All,
+}
+
+impl TimePeriod {
+    pub fn start(self, reference_date: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Self::Day => reference_date - Duration::hours(24),
+            Self::Week => reference_date - Duration::days(7),
+            Self::Month => reference_date - Duration::days(30),
+            Self::Year => reference_date - Duration::days(365),
+            Self::All => DateTime::<Utc>::UNIX_EPOCH,
+        }
+    }
 }
 
 pub fn default_reference_date() -> DateTime<Utc> {
     Utc::now()
 }
+
+/// Filters of the PnL report. Settled contracts are matched on when they settled, active ones on
+/// when they were first seen.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct PnlQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub counterparty: Option<String>,
+    pub event_type: Option<OracleEventType>,
+    /// Currency fiat totals are valued in, USD by default.
+    pub currency: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum OracleEventType {
+    Enum,
+    Numeric,
+    /// Closed contracts whose announcement could not be fetched again.
+    Unknown,
+}
+
+impl OracleEventType {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Enum => "enum",
+            Self::Numeric => "numeric",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// What the unrealized PnL of an active contract was estimated from.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MarkSource {
+    /// The CET that was broadcast to close the contract.
+    Cet,
+    /// The outcome the oracle attested.
+    Attestation,
+    /// The current BTC price, for numeric contracts whose event has not been attested yet.
+    PriceFeed,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ContractPnl {
+    pub id: String,
+    pub state: String,
+    pub counter_party: String,
+    pub event_type: OracleEventType,
+    pub oracle_event_id: Option<String>,
+    /// Collateral we put into the contract.
+    pub collateral: i64,
+    pub created_at: Option<DateTimeWithTimeZone>,
+    pub settled_at: Option<DateTimeWithTimeZone>,
+    pub realized_sats: Option<i64>,
+    /// Realized PnL valued at the price recorded when the contract settled.
+    pub realized_fiat: Option<Decimal>,
+    pub unrealized_sats: Option<i64>,
+    pub mark: Option<MarkSource>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PnlTotals {
+    pub contracts: usize,
+    pub realized_sats: i64,
+    pub unrealized_sats: i64,
+    pub total_sats: i64,
+    pub realized_fiat: Option<Decimal>,
+    pub unrealized_fiat: Option<Decimal>,
+    pub total_fiat: Option<Decimal>,
+    /// Active contracts without a mark-to-market estimate, left out of the unrealized totals.
+    pub unpriced: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PnlGroup {
+    pub key: String,
+    pub totals: PnlTotals,
+}
+
+/// PnL per contract, grouped by counterparty and oracle event type. Realized fiat values use the
+/// price when each contract settled, unrealized ones the current price.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PnlReport {
+    pub currency: String,
+    pub bitcoin_price: Option<f64>,
+    pub totals: PnlTotals,
+    pub by_counterparty: Vec<PnlGroup>,
+    pub by_event_type: Vec<PnlGroup>,
+    pub contracts: Vec<ContractPnl>,
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// Messages exchanged between the two parties of a contract to close it early.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CloseMessage {
+    Propose {
+        contract_id: String,
+        offer_payout: u64,
+        accept_payout: u64,
+        fee_rate: u64,
+        /// Compact signature of the proposer over the close transaction, hex encoded.
+        signature: String,
+    },
+    Accept {
+        contract_id: String,
+        txid: String,
+    },
+    /// The proposal with this signature should no longer be accepted.
+    Withdraw {
+        contract_id: String,
+        signature: String,
+    },
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use ddk_manager::contract::ContractDescriptor;
+use dlc_messages::oracle_msgs::OracleAnnouncement;
+use serde::{Deserialize, Serialize};
+
+use crate::common::preview::PayoutRow;
+pub use crate::models::_entities::contract_events::Model as ContractEvent;
+
+/// A contract decoded from its stored blob. Terms are missing once a contract is closed, as ddk
+/// only keeps the outcome of closed contracts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractDetail {
+    pub id: String,
+    pub temporary_id: String,
+    pub state: String,
+    pub counter_party: String,
+    pub is_offer_party: Option<bool>,
+    pub offer_collateral: Option<u64>,
+    pub accept_collateral: Option<u64>,
+    pub total_collateral: Option<u64>,
+    pub fee_rate_per_vb: Option<u64>,
+    pub cet_locktime: Option<u32>,
+    pub refund_locktime: Option<u32>,
+    pub pnl: Option<i64>,
+    pub contract_infos: Vec<ContractInfoDetail>,
+    pub transactions: ContractTransactions,
+    pub attestations: Vec<AttestationDetail>,
+    pub timeline: Vec<ContractEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractInfoDetail {
+    pub descriptor: ContractDescriptor,
+    pub payouts: Vec<PayoutRow>,
+    pub threshold: usize,
+    pub announcements: Vec<AnnouncementDetail>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncementDetail {
+    pub event_id: String,
+    pub oracle_public_key: String,
+    pub maturity: u32,
+    pub announcement: OracleAnnouncement,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContractTransactions {
+    pub funding_txid: Option<String>,
+    pub cet_txids: Vec<String>,
+    pub refund_txid: Option<String>,
+    /// The CET or close transaction that settled the contract.
+    pub closing_txid: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationDetail {
+    pub oracle_public_key: String,
+    pub outcomes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContractSort {
+    #[default]
+    Created,
+    Collateral,
+    State,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+pub const DEFAULT_PAGE_LIMIT: u64 = 50;
+pub const MAX_PAGE_LIMIT: u64 = 500;
+
+/// Query parameters shared by the contract and offer listings.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ContractListQuery {
+    pub limit: Option<u64>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub sort: ContractSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub counterparty: Option<String>,
+    /// Comma separated contract states, e.g. `confirmed,pre-closed`.
+    pub state: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub oracle_event_id: Option<String>,
+}
+
+impl ContractListQuery {
+    pub fn limit(&self) -> u64 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT)
+    }
+}
+
+/// A row of ddk's contract table without the contract blob.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractListItem {
+    pub id: String,
+    pub state: String,
+    pub is_offer_party: bool,
+    pub counter_party: String,
+    pub offer_collateral: i64,
+    pub accept_collateral: i64,
+    pub total_collateral: i64,
+    pub fee_rate_per_vb: i64,
+    pub cet_locktime: i32,
+    pub refund_locktime: i32,
+    pub pnl: Option<i64>,
+    /// When the contract, or the offer it was accepted from, was first stored.
+    pub created_at: DateTime<FixedOffset>,
+    pub oracle_event_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractPage {
+    pub items: Vec<ContractListItem>,
+    pub next_cursor: Option<String>,
+}
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// A contract an event is about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractRef {
+    /// Temporary id until the contract is accepted, then the contract id.
+    pub contract_id: String,
+    pub counter_party: String,
+}
+
+/// Wallet balance in sats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceSats {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+    pub contract: u64,
+    pub contract_pnl: i64,
+}
+
+/// Something that changed on the node since the previous sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeEvent {
+    OfferReceived(ContractRef),
+    ContractAccepted(ContractRef),
+    ContractSigned(ContractRef),
+    ContractConfirmed(ContractRef),
+    ContractClosed(ContractRef),
+    ContractRefunded(ContractRef),
+    WalletTxSeen {
+        txid: String,
+        /// Received minus sent, in sats.
+        net: i64,
+    },
+    WalletTxConfirmed {
+        txid: String,
+        net: i64,
+        block_height: Option<u32>,
+    },
+    BalanceChanged {
+        previous: BalanceSats,
+        current: BalanceSats,
+    },
+}
+
+/// Implements `NodeEvent::name` and `NodeEvent::NAMES` from one list of variant names.
+macro_rules! event_names {
+    ($($variant:ident => $name:literal,)*) => {
+        impl NodeEvent {
+            /// Every event name, in declaration order.
+            pub const NAMES: &'static [&'static str] = &[$($name),*];
+
+            /// Name of the server-sent event, same as the `type` field.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant { .. } => $name,)*
+                }
+            }
+        }
+    };
+}
+
+event_names! {
+    OfferReceived => "offer_received",
+    ContractAccepted => "contract_accepted",
+    ContractSigned => "contract_signed",
+    ContractConfirmed => "contract_confirmed",
+    ContractClosed => "contract_closed",
+    ContractRefunded => "contract_refunded",
+    WalletTxSeen => "wallet_tx_seen",
+    WalletTxConfirmed => "wallet_tx_confirmed",
+    BalanceChanged => "balance_changed",
+}
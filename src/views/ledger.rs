@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LedgerQuery {
+    pub year: i32,
+    /// Currency fiat values are in, USD by default.
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub format: LedgerFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LedgerKind {
+    /// Coins received from outside the wallet.
+    Deposit,
+    /// Coins sent outside the wallet.
+    Withdrawal,
+    /// A transaction between our own addresses, like a consolidation.
+    Transfer,
+    /// Collateral moved into a contract.
+    Funding,
+    /// A contract paid out through a CET or a cooperative close.
+    Settlement,
+    /// A contract paid back its collateral through the refund transaction.
+    Refund,
+}
+
+impl LedgerKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::Transfer => "transfer",
+            Self::Funding => "funding",
+            Self::Settlement => "settlement",
+            Self::Refund => "refund",
+        }
+    }
+}
+
+/// A confirmed wallet transaction with its fiat value and the gain it realized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub time: DateTime<Utc>,
+    pub kind: LedgerKind,
+    pub txid: String,
+    pub contract_id: Option<String>,
+    /// Change of the wallet balance, fee included.
+    pub amount_sats: i64,
+    pub fee_sats: Option<u64>,
+    /// Realized contract PnL of settlements and refunds.
+    pub pnl_sats: Option<i64>,
+    /// Price the entry was valued at, missing when no price was recorded near its time.
+    pub bitcoin_price: Option<Decimal>,
+    /// Value of `amount_sats`.
+    pub fiat_value: Option<Decimal>,
+    pub proceeds: Option<Decimal>,
+    pub cost_basis: Option<Decimal>,
+    pub realized_gain: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerTotals {
+    pub proceeds: Decimal,
+    pub cost_basis: Decimal,
+    pub realized_gain: Decimal,
+    /// Entries without a price, whose fiat values are missing.
+    pub unpriced: usize,
+    /// Settlements whose contract has no recorded PnL, left out of the gains.
+    pub missing_pnl: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ledger {
+    pub year: i32,
+    pub currency: String,
+    pub totals: LedgerTotals,
+    pub entries: Vec<LedgerEntry>,
+}
+
+const CSV_HEADER: &str = "time,kind,txid,contract_id,amount_sats,fee_sats,pnl_sats,bitcoin_price,\
+                          fiat_value,proceeds,cost_basis,realized_gain";
+
+impl Ledger {
+    /// One row per entry under a header row, fiat columns left empty when unpriced.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(CSV_HEADER);
+        csv.push('\n');
+        for entry in &self.entries {
+            let row = [
+                entry.time.to_rfc3339(),
+                entry.kind.name().to_string(),
+                entry.txid.clone(),
+                entry.contract_id.clone().unwrap_or_default(),
+                entry.amount_sats.to_string(),
+                optional(entry.fee_sats),
+                optional(entry.pnl_sats),
+                optional(entry.bitcoin_price),
+                optional(entry.fiat_value),
+                optional(entry.proceeds),
+                optional(entry.cost_basis),
+                optional(entry.realized_gain),
+            ];
+            let row = row.iter().map(|field| quote(field)).collect::<Vec<_>>();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Quotes a field holding a separator, quote or line break, doubling its quotes.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -1,2 +1,9 @@
 pub mod auth;
 pub mod balances;
+pub mod close;
+pub mod contracts;
+pub mod events;
+pub mod ledger;
+pub mod sync;
+pub mod wallet;
+pub mod webhooks;
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What started a sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncTrigger {
+    /// The background loop.
+    Interval,
+    /// A call to `/api/sync`.
+    Manual,
+}
+
+/// A failed step of a sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncError {
+    pub at: DateTime<Utc>,
+    pub trigger: SyncTrigger,
+    pub step: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// Seconds between background syncs, 0 when the loop is disabled.
+    pub interval_secs: u64,
+    pub running: bool,
+    pub last_started: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    /// Height of the wallet's chain tip after the last successful sync.
+    pub tip_height: Option<u32>,
+    /// Most recent errors, newest first.
+    pub errors: Vec<SyncError>,
+}
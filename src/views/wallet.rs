@@ -0,0 +1,64 @@
+use ddk::wallet::LocalOutput;
+use serde::{Deserialize, Serialize};
+
+/// A wallet UTXO with its coin control metadata.
+#[derive(Debug, Serialize)]
+pub struct LabeledUtxo {
+    #[serde(flatten)]
+    pub utxo: LocalOutput,
+    pub label: Option<String>,
+    pub frozen: bool,
+    /// Used as a funding input of a contract that is not funded yet.
+    pub reserved: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UtxoLabelBody {
+    /// New label. An empty or missing label clears it.
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxDirection {
+    Incoming,
+    Outgoing,
+    /// Every input and output belongs to the wallet, like a consolidation.
+    SelfTransfer,
+}
+
+/// Role of a transaction in a contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContractTxKind {
+    Funding,
+    Cet,
+    Refund,
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractLink {
+    pub contract_id: String,
+    pub kind: ContractTxKind,
+}
+
+/// A wallet transaction seen from the wallet's side.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletTransaction {
+    pub txid: String,
+    pub direction: TxDirection,
+    /// Received minus sent, in sats. Includes the fee for outgoing transactions.
+    pub net: i64,
+    pub received: u64,
+    pub sent: u64,
+    /// Unknown when some inputs spend outputs the wallet never saw.
+    pub fee: Option<u64>,
+    pub confirmed: bool,
+    pub block_height: Option<u32>,
+    pub block_time: Option<u64>,
+    pub confirmations: u32,
+    /// Unix time the transaction was last seen unconfirmed.
+    pub last_seen: Option<i64>,
+    pub contract: Option<ContractLink>,
+}
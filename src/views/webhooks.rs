@@ -0,0 +1,40 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+pub use crate::models::webhook_deliveries::Model as WebhookDelivery;
+use crate::models::webhooks;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RegisterWebhook {
+    pub url: String,
+    /// Event types to receive, every type when missing or empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Key payloads are signed with, generated when missing.
+    pub secret: Option<String>,
+}
+
+/// A registered webhook. The secret is only returned when the webhook is registered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookResponse {
+    pub id: i32,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_at: DateTime<FixedOffset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<webhooks::Model> for WebhookResponse {
+    fn from(webhook: webhooks::Model) -> Self {
+        Self {
+            id: webhook.id,
+            events: webhook.events(),
+            url: webhook.url,
+            active: webhook.active,
+            created_at: webhook.created_at,
+            secret: None,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{settings::Settings, webhooks},
+    models::webhook_deliveries::DeliveryStatus,
+};
+
+/// Makes one attempt at a logged webhook delivery. Failed attempts are retried by the delivery
+/// loop once their backoff has passed.
+pub struct WebhookWorker {
+    pub ctx: AppContext,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct WebhookWorkerArgs {
+    pub delivery_id: i32,
+}
+
+#[async_trait]
+impl BackgroundWorker<WebhookWorkerArgs> for WebhookWorker {
+    fn build(ctx: &AppContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn perform(&self, args: WebhookWorkerArgs) -> Result<()> {
+        let settings = match &self.ctx.config.settings {
+            Some(settings) => Settings::from_json(settings)?,
+            None => Settings::default(),
+        };
+        let delivery =
+            webhooks::deliver(&self.ctx.db, &settings.webhooks, args.delivery_id).await?;
+        if delivery.status == DeliveryStatus::Delivered.name() {
+            return Ok(());
+        }
+        Err(Error::string(&format!(
+            "Webhook delivery {} is {} after {} attempts: {}",
+            delivery.id,
+            delivery.status,
+            delivery.attempts,
+            delivery.error.unwrap_or_default()
+        )))
+    }
+}
@@ -0,0 +1,67 @@
+use loco_rs::testing::prelude::*;
+use sea_orm::{prelude::Decimal, ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+use sons_of_liberty::{
+    app::App,
+    models::{balance_valuations, balances},
+    views::balances::TimePeriod,
+};
+
+#[tokio::test]
+#[serial]
+async fn test_history_in_currency() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    let balance = balances::ActiveModel {
+        bitcoin_balance_sats: ActiveValue::Set(50_000_000),
+        bitcoin_balance_usd: ActiveValue::Set(Decimal::new(50_000, 0)),
+        bitcoin_price: ActiveValue::Set(Decimal::new(100_000, 0)),
+        contract_balance_sats: ActiveValue::Set(0),
+        contract_balance_usd: ActiveValue::Set(Decimal::ZERO),
+        pnl_sats: ActiveValue::Set(0),
+        pnl_usd: ActiveValue::Set(Decimal::ZERO),
+        num_contracts: ActiveValue::Set(0),
+        name: ActiveValue::Set("valuations".to_string()),
+        network: ActiveValue::Set("regtest".to_string()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    balance_valuations::ActiveModel {
+        balance_id: ActiveValue::Set(balance.id),
+        currency: ActiveValue::Set("EUR".to_string()),
+        bitcoin_price: ActiveValue::Set(Decimal::new(90_000, 0)),
+        bitcoin_balance: ActiveValue::Set(Decimal::new(45_000, 0)),
+        contract_balance: ActiveValue::Set(Decimal::ZERO),
+        pnl: ActiveValue::Set(Decimal::ZERO),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    let now = chrono::Utc::now();
+    let eur = balances::Model::get_history_in(db, TimePeriod::Day, now, "EUR")
+        .await
+        .unwrap();
+    let eur = eur.iter().find(|entry| entry.id == balance.id).unwrap();
+    assert_eq!(eur.bitcoin_price, Decimal::new(90_000, 0));
+    assert_eq!(eur.bitcoin_balance, Decimal::new(45_000, 0));
+    assert_eq!(eur.bitcoin_balance_sats, 50_000_000);
+
+    // Snapshots without a valuation fall back to their USD columns, other currencies skip them.
+    let usd = balances::Model::get_history_in(db, TimePeriod::Day, now, "USD")
+        .await
+        .unwrap();
+    let usd = usd.iter().find(|entry| entry.id == balance.id).unwrap();
+    assert_eq!(usd.bitcoin_balance, Decimal::new(50_000, 0));
+    assert!(
+        !balances::Model::get_history_in(db, TimePeriod::Day, now, "CHF")
+            .await
+            .unwrap()
+            .iter()
+            .any(|entry| entry.id == balance.id)
+    );
+}
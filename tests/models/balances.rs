@@ -29,3 +29,78 @@ async fn test_model() {
     // snapshot the result:
     // assert_debug_snapshot!(item);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_downsampled_history() {
+    use chrono::{Duration, TimeZone, Utc};
+    use sea_orm::{prelude::Decimal, ActiveModelTrait, ActiveValue};
+    use sons_of_liberty::{models::balances, views::balances::Bucket};
+
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    // A window in the past no other test writes to.
+    let start = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap()
+        + Duration::days(Utc::now().timestamp_micros() % 3000);
+    let snapshot = |minutes: i64, sats: i64, price: i64| balances::ActiveModel {
+        created_at: ActiveValue::Set((start + Duration::minutes(minutes)).into()),
+        updated_at: ActiveValue::Set((start + Duration::minutes(minutes)).into()),
+        bitcoin_balance_sats: ActiveValue::Set(sats),
+        bitcoin_balance_usd: ActiveValue::Set(Decimal::new(sats * price, 8)),
+        bitcoin_price: ActiveValue::Set(Decimal::new(price, 0)),
+        contract_balance_sats: ActiveValue::Set(0),
+        contract_balance_usd: ActiveValue::Set(Decimal::ZERO),
+        pnl_sats: ActiveValue::Set(0),
+        pnl_usd: ActiveValue::Set(Decimal::ZERO),
+        num_contracts: ActiveValue::Set(0),
+        name: ActiveValue::Set("downsampled".to_string()),
+        network: ActiveValue::Set("regtest".to_string()),
+        ..Default::default()
+    };
+    for (minutes, sats, price) in [(0, 100, 10), (20, 300, 30), (40, 200, 20), (70, 50, 40)] {
+        snapshot(minutes, sats, price).insert(db).await.unwrap();
+    }
+    let end = start + Duration::hours(2);
+    let hour: Bucket = "1h".parse().unwrap();
+
+    let raw = balances::Model::get_range(db, start, end, None)
+        .await
+        .unwrap();
+    assert_eq!(raw.len(), 4);
+
+    let last = balances::Model::get_range(db, start, end, Some(hour))
+        .await
+        .unwrap();
+    assert_eq!(
+        last.iter()
+            .map(|balance| balance.bitcoin_balance_sats)
+            .collect::<Vec<_>>(),
+        vec![200, 50]
+    );
+
+    let candles = balances::Model::candles(db, start, end, hour, "USD")
+        .await
+        .unwrap();
+    assert_eq!(candles.len(), 2);
+    assert_eq!(candles[0].samples, 3);
+    assert_eq!(
+        (
+            candles[0].balance_sats.open,
+            candles[0].balance_sats.high,
+            candles[0].balance_sats.low,
+            candles[0].balance_sats.close
+        ),
+        (100, 300, 100, 200)
+    );
+    assert_eq!(candles[0].bitcoin_price.high, Decimal::new(30, 0));
+    assert_eq!(candles[1].bitcoin_price.close, Decimal::new(40, 0));
+
+    assert!(balances::Model::candles(db, start, end, hour, "CHF")
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(hour.to_string(), "1h");
+    assert!("0m".parse::<Bucket>().is_err());
+    assert!("5y".parse::<Bucket>().is_err());
+}
@@ -0,0 +1,108 @@
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::{app::App, models::close_proposals};
+
+#[tokio::test]
+#[serial]
+async fn test_close_proposal_lifecycle() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let contract_id = format!("contract-{}", chrono::Utc::now().timestamp_micros());
+
+    let proposal = close_proposals::ActiveModel::create_proposal(
+        db,
+        &contract_id,
+        "counterparty",
+        true,
+        &serde_json::from_value(serde_json::json!({
+            "offer_payout": 60_000,
+            "accept_payout": 40_000,
+            "fee_rate": 2,
+        }))
+        .unwrap(),
+        "signature",
+    )
+    .await
+    .unwrap();
+    assert_eq!(proposal.terms().offer_payout, 60_000);
+    assert_eq!(proposal.terms().accept_payout, 40_000);
+
+    let open = close_proposals::Model::find_open(db, &contract_id, true)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(open.id, proposal.id);
+    assert!(close_proposals::Model::find_open(db, &contract_id, false)
+        .await
+        .unwrap()
+        .is_none());
+
+    let closed = close_proposals::ActiveModel::mark_closed(db, open, "txid")
+        .await
+        .unwrap();
+    assert_eq!(closed.status, close_proposals::STATUS_CLOSED);
+    assert!(close_proposals::Model::find_open(db, &contract_id, true)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_withdrawn_proposal_is_no_longer_open() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let contract_id = format!("contract-{}", chrono::Utc::now().timestamp_micros());
+    let terms = serde_json::from_value(serde_json::json!({
+        "offer_payout": 60_000,
+        "accept_payout": 40_000,
+        "fee_rate": 2,
+    }))
+    .unwrap();
+
+    let first = close_proposals::ActiveModel::create_proposal(
+        db,
+        &contract_id,
+        "counterparty",
+        true,
+        &terms,
+        &format!("first-{contract_id}"),
+    )
+    .await
+    .unwrap();
+    let second = close_proposals::ActiveModel::create_proposal(
+        db,
+        &contract_id,
+        "counterparty",
+        true,
+        &terms,
+        &format!("second-{contract_id}"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        close_proposals::Model::all_open(db, &contract_id, true)
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let withdrawn = close_proposals::ActiveModel::mark_withdrawn(db, first)
+        .await
+        .unwrap();
+    assert_eq!(withdrawn.status, close_proposals::STATUS_WITHDRAWN);
+    let open = close_proposals::Model::all_open(db, &contract_id, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        open.iter().map(|proposal| proposal.id).collect::<Vec<_>>(),
+        vec![second.id]
+    );
+
+    // The counterparty still holds the signature of a withdrawn proposal.
+    let signed = close_proposals::Model::signed_unclosed(db, &contract_id)
+        .await
+        .unwrap();
+    assert_eq!(signed.len(), 2);
+}
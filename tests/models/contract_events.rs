@@ -0,0 +1,112 @@
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::{app::App, models::contract_events};
+
+#[tokio::test]
+#[serial]
+async fn test_timeline_follows_contract_id_change() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let suffix = chrono::Utc::now().timestamp_micros();
+    let temporary_id = format!("temporary-{suffix}");
+    let contract_id = format!("contract-{suffix}");
+
+    contract_events::ActiveModel::record(db, &temporary_id, &temporary_id, "offered", None)
+        .await
+        .unwrap();
+    contract_events::ActiveModel::record(
+        db,
+        &contract_id,
+        &temporary_id,
+        "signed",
+        Some("event".to_string()),
+    )
+    .await
+    .unwrap();
+
+    let states = |events: Vec<contract_events::Model>| {
+        events
+            .into_iter()
+            .map(|event| event.state)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        states(
+            contract_events::Model::timeline(db, &contract_id)
+                .await
+                .unwrap()
+        ),
+        vec!["offered", "signed"]
+    );
+    assert_eq!(
+        states(
+            contract_events::Model::timeline(db, &temporary_id)
+                .await
+                .unwrap()
+        ),
+        vec!["offered", "signed"]
+    );
+
+    let latest = contract_events::Model::latest_states(db).await.unwrap();
+    assert_eq!(
+        latest.get(&temporary_id).map(String::as_str),
+        Some("signed")
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_first_seen_and_settlement_times() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let suffix = chrono::Utc::now().timestamp_micros();
+    let temporary_id = format!("temporary-{suffix}");
+    let contract_id = format!("contract-{suffix}");
+
+    let offered =
+        contract_events::ActiveModel::record(db, &temporary_id, &temporary_id, "offered", None)
+            .await
+            .unwrap();
+    contract_events::ActiveModel::record(db, &contract_id, &temporary_id, "confirmed", None)
+        .await
+        .unwrap();
+    let closed =
+        contract_events::ActiveModel::record(db, &contract_id, &temporary_id, "closed", None)
+            .await
+            .unwrap();
+
+    let first_seen = contract_events::Model::first_seen(db).await.unwrap();
+    assert_eq!(first_seen[&temporary_id], offered.created_at);
+    assert_eq!(first_seen[&contract_id], offered.created_at);
+
+    let settled = contract_events::Model::reached(db, &["closed", "refunded"])
+        .await
+        .unwrap();
+    assert_eq!(settled[&contract_id], closed.created_at);
+    assert!(!settled.contains_key(&temporary_id));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_events_are_published_once() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let temporary_id = format!("temporary-{}", chrono::Utc::now().timestamp_micros());
+
+    let offered =
+        contract_events::ActiveModel::record(db, &temporary_id, &temporary_id, "offered", None)
+            .await
+            .unwrap();
+    assert!(offered.published_at.is_none());
+    let unpublished = contract_events::Model::unpublished(db).await.unwrap();
+    assert!(unpublished.iter().any(|event| event.id == offered.id));
+
+    assert_eq!(
+        contract_events::ActiveModel::mark_published(db, &[offered.id])
+            .await
+            .unwrap(),
+        1
+    );
+    let unpublished = contract_events::Model::unpublished(db).await.unwrap();
+    assert!(unpublished.iter().all(|event| event.id != offered.id));
+}
@@ -2,4 +2,20 @@ mod users;
 
 mod seeds;
 
-mod balances;
\ No newline at end of file
+mod balances;
+
+mod offer_expiries;
+
+mod close_proposals;
+
+mod contract_events;
+
+mod utxo_labels;
+
+mod prices;
+
+mod balance_valuations;
+
+mod webhooks;
+
+mod psbt_reservations;
@@ -0,0 +1,46 @@
+use chrono::{Duration, Utc};
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::{app::App, models::offer_expiries};
+
+#[tokio::test]
+#[serial]
+async fn test_offer_expiry_is_tracked_once() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let now = Utc::now();
+    let contract_id = format!("offer-{}", now.timestamp_micros());
+
+    let expiry = offer_expiries::Model::find_or_track(db, &contract_id, 60, now)
+        .await
+        .unwrap();
+    assert!(!expiry.is_expired(now));
+    assert!(expiry.is_expired(now + Duration::seconds(61)));
+
+    // Seeing the offer again keeps the original expiry.
+    let again = offer_expiries::Model::find_or_track(db, &contract_id, 3_600, now)
+        .await
+        .unwrap();
+    assert_eq!(again.expires_at, expiry.expires_at);
+
+    let expired = offer_expiries::ActiveModel::mark_expired(db, again, now)
+        .await
+        .unwrap();
+    assert!(expired.is_expired(now));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_offer_ttl_counts_from_when_it_was_offered() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let now = Utc::now();
+    let contract_id = format!("offer-{}", now.timestamp_micros());
+
+    // An offer stored two hours ago with a one hour TTL is already expired when first tracked.
+    let expiry =
+        offer_expiries::Model::find_or_track(db, &contract_id, 3_600, now - Duration::hours(2))
+            .await
+            .unwrap();
+    assert!(expiry.is_expired(now));
+}
@@ -0,0 +1,29 @@
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::{app::App, models::prices};
+
+#[tokio::test]
+#[serial]
+async fn test_latest_price_per_currency() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let currency = format!("T{}", chrono::Utc::now().timestamp_micros());
+
+    assert!(prices::Model::latest(db, &currency)
+        .await
+        .unwrap()
+        .is_none());
+
+    prices::ActiveModel::record(db, &currency, 60_000.5, "coinbase")
+        .await
+        .unwrap();
+    let recorded = prices::ActiveModel::record(db, &currency, 61_000.25, "median(kraken,bitstamp)")
+        .await
+        .unwrap();
+
+    let latest = prices::Model::latest(db, &currency).await.unwrap().unwrap();
+    assert_eq!(latest.id, recorded.id);
+    assert_eq!(latest.source, "median(kraken,bitstamp)");
+    assert!((latest.amount_f64() - 61_000.25).abs() < f64::EPSILON);
+    assert!(latest.age_secs() < 60);
+}
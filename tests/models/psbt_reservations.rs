@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+use bitcoin::{OutPoint, Txid};
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::{app::App, models::psbt_reservations};
+
+#[tokio::test]
+#[serial]
+async fn test_reserve_and_release_psbt_inputs() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let micros = chrono::Utc::now().timestamp_micros();
+    let txid = Txid::from_str(&format!("{micros:064x}")).unwrap();
+    let outpoints =
+        [0, 1].map(|vout| OutPoint::from_str(&format!("{:064x}:{vout}", micros + 1)).unwrap());
+
+    psbt_reservations::ActiveModel::reserve(db, &txid, &outpoints)
+        .await
+        .unwrap();
+    let reserved = psbt_reservations::Model::reserved_outpoints(db)
+        .await
+        .unwrap();
+    assert!(outpoints.iter().all(|outpoint| reserved.contains(outpoint)));
+
+    // A coin can only be reserved by one PSBT at a time.
+    let other = Txid::from_str(&format!("{:064x}", micros + 2)).unwrap();
+    assert!(
+        psbt_reservations::ActiveModel::reserve(db, &other, &outpoints[..1])
+            .await
+            .is_err()
+    );
+
+    assert_eq!(
+        psbt_reservations::ActiveModel::release(db, &txid)
+            .await
+            .unwrap(),
+        2
+    );
+    let reserved = psbt_reservations::Model::reserved_outpoints(db)
+        .await
+        .unwrap();
+    assert!(outpoints
+        .iter()
+        .all(|outpoint| !reserved.contains(outpoint)));
+}
@@ -1,6 +1,6 @@
 use loco_rs::testing::prelude::*;
 use serial_test::serial;
-use sons_of_liberty::app::App;
+use sons_of_liberty::{app::App, models::seeds};
 
 macro_rules! configure_insta {
     ($($expr:expr),*) => {
@@ -29,3 +29,28 @@ async fn test_model() {
     // snapshot the result:
     // assert_debug_snapshot!(item);
 }
+
+#[tokio::test]
+#[serial]
+async fn test_seed_mnemonic_round_trip() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let name = format!("restore-{}", chrono::Utc::now().timestamp_micros());
+    let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                  abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                  abandon abandon abandon abandon abandon art";
+
+    let restored = seeds::ActiveModel::restore_from_mnemonic(db, &name, phrase, None)
+        .await
+        .unwrap();
+    assert_eq!(restored.seed, vec![0; 32]);
+    assert_eq!(restored.mnemonic(None).unwrap().to_string(), phrase);
+
+    // A seed that already exists is never overwritten.
+    assert!(
+        seeds::ActiveModel::restore_from_mnemonic(db, &name, phrase, None)
+            .await
+            .is_err()
+    );
+    assert!(seeds::entropy_from_mnemonic("abandon abandon abandon").is_err());
+}
@@ -0,0 +1,44 @@
+use std::str::FromStr;
+
+use bitcoin::OutPoint;
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::{app::App, models::utxo_labels};
+
+#[tokio::test]
+#[serial]
+async fn test_label_and_freeze_utxo() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let outpoint = OutPoint::from_str(&format!(
+        "{:064x}:{}",
+        chrono::Utc::now().timestamp_micros(),
+        1
+    ))
+    .unwrap();
+
+    let labeled = utxo_labels::ActiveModel::set_label(db, &outpoint, Some("cold".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(labeled.label.as_deref(), Some("cold"));
+    assert!(!labeled.frozen);
+
+    // Freezing keeps the label and updates the same row.
+    let frozen = utxo_labels::ActiveModel::set_frozen(db, &outpoint, true)
+        .await
+        .unwrap();
+    assert_eq!(frozen.id, labeled.id);
+    assert_eq!(frozen.label.as_deref(), Some("cold"));
+    assert!(utxo_labels::Model::frozen_outpoints(db)
+        .await
+        .unwrap()
+        .contains(&outpoint));
+
+    utxo_labels::ActiveModel::set_frozen(db, &outpoint, false)
+        .await
+        .unwrap();
+    assert!(!utxo_labels::Model::frozen_outpoints(db)
+        .await
+        .unwrap()
+        .contains(&outpoint));
+}
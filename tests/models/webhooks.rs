@@ -0,0 +1,81 @@
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::app::App;
+use sons_of_liberty::models::{
+    webhook_deliveries::{self, DeliveryStatus},
+    webhooks,
+};
+
+#[tokio::test]
+#[serial]
+async fn test_webhook_subscriptions_and_delivery_log() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    let closed = webhooks::ActiveModel::register(
+        db,
+        "http://127.0.0.1:9/closed",
+        "secret",
+        &["contract_closed".to_string()],
+    )
+    .await
+    .unwrap();
+    let every = webhooks::ActiveModel::register(db, "http://127.0.0.1:9/every", "secret", &[])
+        .await
+        .unwrap();
+
+    let ids = |webhooks: Vec<webhooks::Model>| {
+        webhooks
+            .into_iter()
+            .map(|webhook| webhook.id)
+            .collect::<Vec<_>>()
+    };
+    let subscribed = ids(webhooks::Model::subscribed(db, "contract_closed")
+        .await
+        .unwrap());
+    assert!(subscribed.contains(&closed.id) && subscribed.contains(&every.id));
+    let subscribed = ids(webhooks::Model::subscribed(db, "wallet_tx_seen")
+        .await
+        .unwrap());
+    assert!(!subscribed.contains(&closed.id) && subscribed.contains(&every.id));
+
+    let delivery = webhook_deliveries::ActiveModel::enqueue(
+        db,
+        closed.id,
+        "contract_closed",
+        serde_json::json!({ "event": "contract_closed" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(delivery.status, DeliveryStatus::Pending.name());
+
+    let delivery = delivery
+        .attempted(
+            db,
+            DeliveryStatus::Pending,
+            Some(500),
+            Some("Webhook answered 500".to_string()),
+        )
+        .await
+        .unwrap();
+    let delivery = delivery
+        .attempted(db, DeliveryStatus::Delivered, Some(200), None)
+        .await
+        .unwrap();
+    assert_eq!(delivery.attempts, 2);
+    assert_eq!(delivery.response_status, Some(200));
+    assert!(delivery.delivered_at.is_some());
+
+    // Replays keep the original delivery in the log.
+    let replayed = delivery.replay(db).await.unwrap();
+    assert_ne!(replayed.id, delivery.id);
+    assert_eq!(replayed.payload, delivery.payload);
+    assert_eq!(replayed.attempts, 0);
+    let log = webhook_deliveries::Model::for_webhook(db, closed.id)
+        .await
+        .unwrap();
+    assert_eq!(
+        log.iter().map(|delivery| delivery.id).collect::<Vec<_>>(),
+        [replayed.id, delivery.id]
+    );
+}
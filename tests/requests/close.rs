@@ -0,0 +1,225 @@
+use ::nostr::Keys;
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+use sons_of_liberty::{
+    app::{App, SONS_OF_LIBERTY},
+    controllers::close::handle_close_message,
+    models::close_proposals,
+    views::close::CloseMessage,
+};
+
+use super::prepare_data;
+
+fn contract_id() -> String {
+    format!("{:064x}", chrono::Utc::now().timestamp_micros())
+}
+
+#[tokio::test]
+#[serial]
+async fn can_get_close_proposals() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (cookie, value) = prepare_data::auth_cookie(&user.token);
+
+        let res = request
+            .get("/api/contracts/close/")
+            .add_header(cookie, value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_propose_close_of_unknown_contract() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (cookie, value) = prepare_data::auth_cookie(&user.token);
+        let contract_id = contract_id();
+
+        let res = request
+            .post("/api/contracts/close/propose")
+            .add_header(cookie, value)
+            .json(&serde_json::json!({
+                "contract_id": contract_id,
+                "payout": 50_000,
+                "fee_rate": 2,
+            }))
+            .await;
+        assert_eq!(res.status_code(), 404);
+        assert!(
+            close_proposals::Model::find_open(&ctx.db, &contract_id, true)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_accept_close_without_received_proposal() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (cookie, value) = prepare_data::auth_cookie(&user.token);
+
+        let res = request
+            .post("/api/contracts/close/accept")
+            .add_header(cookie, value)
+            .json(&serde_json::json!({ "contract_id": contract_id() }))
+            .await;
+        assert_eq!(res.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_proposal_for_unknown_contract_is_dropped() {
+    request::<App, _, _>(|_request, ctx| async move {
+        let sol = SONS_OF_LIBERTY.get().unwrap();
+        let contract_id = contract_id();
+
+        handle_close_message(
+            &ctx.db,
+            sol,
+            Keys::generate().public_key(),
+            CloseMessage::Propose {
+                contract_id: contract_id.clone(),
+                offer_payout: 60_000,
+                accept_payout: 40_000,
+                fee_rate: 2,
+                signature: format!("signature-{contract_id}"),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            close_proposals::Model::find_open(&ctx.db, &contract_id, false)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_accept_from_forged_sender_is_rejected() {
+    request::<App, _, _>(|_request, ctx| async move {
+        let sol = SONS_OF_LIBERTY.get().unwrap();
+        let contract_id = contract_id();
+        let counterparty = Keys::generate();
+        let proposal = close_proposals::ActiveModel::create_proposal(
+            &ctx.db,
+            &contract_id,
+            &counterparty.public_key().to_hex(),
+            true,
+            &serde_json::from_value(serde_json::json!({
+                "offer_payout": 60_000,
+                "accept_payout": 40_000,
+                "fee_rate": 2,
+            }))
+            .unwrap(),
+            &format!("signature-{contract_id}"),
+        )
+        .await
+        .unwrap();
+
+        handle_close_message(
+            &ctx.db,
+            sol,
+            Keys::generate().public_key(),
+            CloseMessage::Accept {
+                contract_id: contract_id.clone(),
+                txid: format!("{:064x}", 1),
+            },
+        )
+        .await
+        .unwrap();
+
+        let open = close_proposals::Model::find_open(&ctx.db, &contract_id, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(open.id, proposal.id);
+        assert_eq!(open.txid, None);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_withdraw_without_sent_proposal() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (cookie, value) = prepare_data::auth_cookie(&user.token);
+
+        let res = request
+            .post("/api/contracts/close/withdraw")
+            .add_header(cookie, value)
+            .json(&serde_json::json!({ "contract_id": contract_id() }))
+            .await;
+        assert_eq!(res.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_withdrawal_is_only_taken_from_the_proposer() {
+    request::<App, _, _>(|_request, ctx| async move {
+        let sol = SONS_OF_LIBERTY.get().unwrap();
+        let contract_id = contract_id();
+        let counterparty = Keys::generate();
+        let signature = format!("signature-{contract_id}");
+        close_proposals::ActiveModel::create_proposal(
+            &ctx.db,
+            &contract_id,
+            &counterparty.public_key().to_hex(),
+            false,
+            &serde_json::from_value(serde_json::json!({
+                "offer_payout": 60_000,
+                "accept_payout": 40_000,
+                "fee_rate": 2,
+            }))
+            .unwrap(),
+            &signature,
+        )
+        .await
+        .unwrap();
+        let withdraw = CloseMessage::Withdraw {
+            contract_id: contract_id.clone(),
+            signature: signature.clone(),
+        };
+
+        handle_close_message(
+            &ctx.db,
+            sol,
+            Keys::generate().public_key(),
+            withdraw.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(
+            close_proposals::Model::find_open(&ctx.db, &contract_id, false)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        handle_close_message(&ctx.db, sol, counterparty.public_key(), withdraw)
+            .await
+            .unwrap();
+        let proposal = close_proposals::Model::find_by_signature(&ctx.db, &signature)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(proposal.status, close_proposals::STATUS_WITHDRAWN);
+    })
+    .await;
+}
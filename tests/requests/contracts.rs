@@ -1,6 +1,10 @@
 use loco_rs::testing::prelude::*;
+use sea_orm::{ConnectionTrait, DbBackend, Statement};
 use serial_test::serial;
-use sons_of_liberty::app::App;
+use sons_of_liberty::{
+    app::App,
+    models::{contract_events, contracts},
+};
 
 #[tokio::test]
 #[serial]
@@ -14,3 +18,46 @@ async fn can_get_contracts() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn test_accepted_contract_keeps_offer_created_at() {
+    request::<App, _, _>(|_request, ctx| async move {
+        let db = &ctx.db;
+        let suffix = chrono::Utc::now().timestamp_micros();
+        let temporary_id = format!("temporary-{suffix}");
+        let contract_id = format!("contract-{suffix}");
+
+        let offered =
+            contract_events::ActiveModel::record(db, &temporary_id, &temporary_id, "offered", None)
+                .await
+                .unwrap();
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r"INSERT INTO contracts (id, state, is_offer_party, counter_party, offer_collateral,
+                accept_collateral, total_collateral, fee_rate_per_vb, cet_locktime,
+                refund_locktime, contract_data)
+            VALUES ($1, 3, true, 'counterparty', 1000, 1000, 2000, 1, 0, 0, '\x00')",
+            [contract_id.clone().into()],
+        ))
+        .await
+        .unwrap();
+
+        // Not recorded by the sync yet.
+        let stored = contracts::Model::find_row(db, &contract_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.created_at > offered.created_at);
+
+        contract_events::ActiveModel::record(db, &contract_id, &temporary_id, "signed", None)
+            .await
+            .unwrap();
+        let recorded = contracts::Model::find_row(db, &contract_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(recorded.created_at, offered.created_at);
+    })
+    .await;
+}
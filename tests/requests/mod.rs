@@ -2,6 +2,7 @@ mod auth;
 mod prepare_data;
 
 pub mod balance;
+pub mod close;
 pub mod contracts;
 pub mod create;
 pub mod hashrate;
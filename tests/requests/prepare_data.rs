@@ -1,6 +1,6 @@
 use axum::http::{HeaderName, HeaderValue};
 use loco_rs::{app::AppContext, TestServer};
-use sons_of_liberty::{models::users, views::auth::LoginResponse};
+use sons_of_liberty::{controllers, models::users, views::auth::LoginResponse};
 
 const USER_EMAIL: &str = "test@loco.com";
 const USER_PASSWORD: &str = "1234";
@@ -55,3 +55,11 @@ pub fn auth_header(token: &str) -> (HeaderName, HeaderValue) {
 
     (HeaderName::from_static("authorization"), auth_header_value)
 }
+
+/// The API authenticates with the cookie set at login.
+pub fn auth_cookie(token: &str) -> (HeaderName, HeaderValue) {
+    let cookie_value =
+        HeaderValue::from_str(&format!("{}={token}", controllers::auth::COOKIE_NAME)).unwrap();
+
+    (HeaderName::from_static("cookie"), cookie_value)
+}
@@ -2,6 +2,8 @@ use loco_rs::testing::prelude::*;
 use serial_test::serial;
 use sons_of_liberty::app::App;
 
+use super::prepare_data;
+
 #[tokio::test]
 #[serial]
 async fn can_get_wallets() {
@@ -14,3 +16,19 @@ async fn can_get_wallets() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn test_ledger_of_last_representable_year_is_rejected() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (cookie, value) = prepare_data::auth_cookie(&user.token);
+
+        let res = request
+            .get(&format!("/api/wallet/ledger?year={}", i32::MAX))
+            .add_header(cookie, value)
+            .await;
+        assert_eq!(res.status_code(), 400);
+    })
+    .await;
+}
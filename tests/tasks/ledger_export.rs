@@ -0,0 +1,96 @@
+use loco_rs::{task, testing::prelude::*};
+use sons_of_liberty::app::App;
+
+use loco_rs::boot::run_task;
+use serial_test::serial;
+
+#[tokio::test]
+#[serial]
+async fn test_ledger_export_requires_year() {
+    let boot = boot_test::<App>().await.unwrap();
+
+    assert!(run_task::<App>(
+        &boot.app_context,
+        Some(&"ledger_export".to_string()),
+        &task::Vars::default()
+    )
+    .await
+    .is_err());
+}
+
+fn export_path(format: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "sol-ledger-{}.{format}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    ))
+}
+
+fn vars(year: &str, format: &str, output: &std::path::Path) -> task::Vars {
+    task::Vars::from_cli_args(vec![
+        ("year".to_string(), year.to_string()),
+        ("format".to_string(), format.to_string()),
+        ("output".to_string(), output.display().to_string()),
+    ])
+}
+
+#[tokio::test]
+#[serial]
+async fn test_ledger_export_writes_csv_rows() {
+    let boot = boot_test::<App>().await.unwrap();
+    let path = export_path("csv");
+
+    run_task::<App>(
+        &boot.app_context,
+        Some(&"ledger_export".to_string()),
+        &vars("2024", "csv", &path),
+    )
+    .await
+    .unwrap();
+
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some(
+            "time,kind,txid,contract_id,amount_sats,fee_sats,pnl_sats,bitcoin_price,fiat_value,\
+             proceeds,cost_basis,realized_gain"
+        )
+    );
+    // Every row after the header is an entry of the exported year with all twelve columns.
+    for line in lines {
+        assert_eq!(line.split(',').count(), 12, "{line}");
+        assert!(line.starts_with("2024-"), "{line}");
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_ledger_export_writes_json() {
+    let boot = boot_test::<App>().await.unwrap();
+    let path = export_path("json");
+
+    run_task::<App>(
+        &boot.app_context,
+        Some(&"ledger_export".to_string()),
+        &vars("2024", "json", &path),
+    )
+    .await
+    .unwrap();
+
+    let ledger: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(ledger["year"], 2024);
+    assert_eq!(ledger["currency"], "USD");
+    assert!(ledger["entries"].is_array());
+    assert_eq!(ledger["totals"]["missing_pnl"], 0);
+
+    assert!(run_task::<App>(
+        &boot.app_context,
+        Some(&"ledger_export".to_string()),
+        &vars("2024", "xml", &path),
+    )
+    .await
+    .is_err());
+}
@@ -1,3 +1,6 @@
 pub mod balance_updater;
+pub mod ledger_export;
+pub mod offer_expiry;
+pub mod rotate_seed;
 
-pub mod freshdb;
\ No newline at end of file
+pub mod freshdb;
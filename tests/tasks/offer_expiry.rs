@@ -0,0 +1,40 @@
+use chrono::{Duration, Utc};
+use loco_rs::{task, testing::prelude::*};
+use sons_of_liberty::{app::App, models::offer_expiries};
+
+use loco_rs::boot::run_task;
+use serial_test::serial;
+
+#[tokio::test]
+#[serial]
+async fn test_can_run_offer_expiry() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let now = Utc::now();
+
+    // Tracked expiries without a stored offer behind them are left alone.
+    let fresh = format!("fresh-{}", now.timestamp_micros());
+    let stale = format!("stale-{}", now.timestamp_micros());
+    offer_expiries::Model::find_or_track(db, &fresh, 3_600, now)
+        .await
+        .unwrap();
+    offer_expiries::Model::find_or_track(db, &stale, 3_600, now - Duration::hours(2))
+        .await
+        .unwrap();
+
+    assert!(run_task::<App>(
+        &boot.app_context,
+        Some(&"offer_expiry".to_string()),
+        &task::Vars::default()
+    )
+    .await
+    .is_ok());
+
+    for contract_id in [&fresh, &stale] {
+        let expiry = offer_expiries::Model::find_by_contract_id(db, contract_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(expiry.expired_at, None);
+    }
+}
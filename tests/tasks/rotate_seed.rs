@@ -0,0 +1,80 @@
+use loco_rs::{task, testing::prelude::*};
+use sons_of_liberty::{app::App, models::seeds};
+
+use loco_rs::boot::run_task;
+use serial_test::serial;
+
+fn vars(args: &[(&str, &str)]) -> task::Vars {
+    task::Vars::from_cli_args(
+        args.iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect(),
+    )
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rotate_seed_requires_new_name() {
+    let boot = boot_test::<App>().await.unwrap();
+
+    assert!(run_task::<App>(
+        &boot.app_context,
+        Some(&"rotate_seed".to_string()),
+        &task::Vars::default()
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rotate_seed_rejects_zero_fee_rate() {
+    let boot = boot_test::<App>().await.unwrap();
+
+    assert!(run_task::<App>(
+        &boot.app_context,
+        Some(&"rotate_seed".to_string()),
+        &vars(&[("new_name", "rotated"), ("fee_rate", "0")]),
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_rotate_seed_records_new_seed_before_sweeping() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let new_name = format!("rotated-{}", chrono::Utc::now().timestamp_micros());
+
+    // The first run only creates the seed and prints its backup words.
+    run_task::<App>(
+        &boot.app_context,
+        Some(&"rotate_seed".to_string()),
+        &vars(&[("new_name", &new_name), ("fee_rate", "2")]),
+    )
+    .await
+    .unwrap();
+    let seed = seeds::Model::find_by_name(db, &new_name)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Once backed up, the sweep reuses the recorded seed.
+    run_task::<App>(
+        &boot.app_context,
+        Some(&"rotate_seed".to_string()),
+        &vars(&[
+            ("new_name", &new_name),
+            ("fee_rate", "2"),
+            ("backed_up", "true"),
+        ]),
+    )
+    .await
+    .unwrap();
+    let reused = seeds::Model::find_by_name(db, &new_name)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reused.id, seed.id);
+}
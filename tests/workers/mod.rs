@@ -1 +1,2 @@
 
+mod webhook;
@@ -0,0 +1,171 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Router,
+};
+use loco_rs::{bgworker::BackgroundWorker, testing::prelude::*};
+use sea_orm::EntityTrait;
+use serial_test::serial;
+use sons_of_liberty::{
+    app::App,
+    models::{
+        webhook_deliveries::{self, DeliveryStatus},
+        webhooks,
+    },
+    workers::webhook::{WebhookWorker, WebhookWorkerArgs},
+};
+
+/// Requests received by the stand-in receiver, which fails the first `failures` of them.
+#[derive(Clone, Default)]
+struct Receiver {
+    failures: usize,
+    calls: Arc<AtomicUsize>,
+    requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+}
+
+async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    receiver.requests.lock().unwrap().push((headers, body));
+    if receiver.calls.fetch_add(1, Ordering::SeqCst) < receiver.failures {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::OK
+    }
+}
+
+/// Serves the receiver on a local port and returns its url.
+async fn serve(receiver: Receiver) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let app = Router::new()
+        .route("/hook", post(receive))
+        .with_state(receiver);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{address}/hook")
+}
+
+#[tokio::test]
+#[serial]
+async fn test_delivery_is_signed_and_retried() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = &boot.app_context;
+    let receiver = Receiver {
+        failures: 1,
+        ..Receiver::default()
+    };
+    let url = serve(receiver.clone()).await;
+
+    let webhook = webhooks::ActiveModel::register(&ctx.db, &url, "secret", &[])
+        .await
+        .unwrap();
+    let payload = serde_json::json!({ "event": "contract_closed", "data": {} });
+    let delivery = webhook_deliveries::ActiveModel::enqueue(
+        &ctx.db,
+        webhook.id,
+        "contract_closed",
+        payload.clone(),
+    )
+    .await
+    .unwrap();
+
+    let worker = WebhookWorker::build(ctx);
+    let args = || WebhookWorkerArgs {
+        delivery_id: delivery.id,
+    };
+
+    // Every attempt is its own job, a failed one stays pending and is due after the backoff.
+    assert!(worker.perform(args()).await.is_err());
+    let pending = webhook_deliveries::Entity::find_by_id(delivery.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(pending.status, DeliveryStatus::Pending.name());
+    assert_eq!(pending.attempts, 1);
+    assert_eq!(pending.response_status, Some(500));
+    let due = webhook_deliveries::Model::due(&ctx.db, chrono::Utc::now())
+        .await
+        .unwrap();
+    assert!(due.iter().any(|due| due.id == delivery.id));
+
+    worker.perform(args()).await.unwrap();
+    let delivery = webhook_deliveries::Entity::find_by_id(delivery.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(delivery.status, DeliveryStatus::Delivered.name());
+    assert!(delivery.next_attempt_at.is_none());
+    assert_eq!(delivery.attempts, 2);
+    assert_eq!(delivery.response_status, Some(200));
+
+    let requests = receiver.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    let (headers, body) = &requests[1];
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(body).unwrap(),
+        payload
+    );
+    assert_eq!(headers["x-webhook-event"], "contract_closed");
+    let timestamp = headers["x-webhook-timestamp"]
+        .to_str()
+        .unwrap()
+        .parse::<i64>()
+        .unwrap();
+    assert_eq!(
+        headers["x-webhook-signature"].to_str().unwrap(),
+        webhook.sign(timestamp, body)
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_delivery_fails_after_max_attempts() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = &boot.app_context;
+    let receiver = Receiver {
+        failures: usize::MAX,
+        ..Receiver::default()
+    };
+    let url = serve(receiver.clone()).await;
+
+    let webhook = webhooks::ActiveModel::register(&ctx.db, &url, "secret", &[])
+        .await
+        .unwrap();
+    let delivery = webhook_deliveries::ActiveModel::enqueue(
+        &ctx.db,
+        webhook.id,
+        "contract_refunded",
+        serde_json::json!({}),
+    )
+    .await
+    .unwrap();
+
+    let worker = WebhookWorker::build(ctx);
+    for _ in 0..4 {
+        let performed = worker
+            .perform(WebhookWorkerArgs {
+                delivery_id: delivery.id,
+            })
+            .await;
+        assert!(performed.is_err());
+    }
+
+    let delivery = webhook_deliveries::Entity::find_by_id(delivery.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    // config/test.yaml allows three attempts, the job after them does not post again.
+    assert_eq!(delivery.status, DeliveryStatus::Failed.name());
+    assert_eq!(delivery.attempts, 3);
+    assert_eq!(delivery.response_status, Some(500));
+    assert_eq!(receiver.calls.load(Ordering::SeqCst), 3);
+    assert!(delivery.next_attempt_at.is_none());
+}